@@ -0,0 +1,33 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use moska::{Card, Moska, MoskaAI, Rank, Suit};
+
+// Deals `n` cards to both players, ranks cycling through all suits
+fn game_with_hands(n: usize) -> Moska {
+    let mut game = Moska::new(2);
+    game.new_round();
+    game.trump_card = Card::new(Suit::Spades, Rank::Two);
+
+    let cards: Vec<Card> = [Suit::Clubs, Suit::Hearts, Suit::Diamonds, Suit::Spades]
+        .into_iter()
+        .flat_map(|suit| (1..=13).map(move |rank| Card::new(suit, Rank::try_from(rank).unwrap())))
+        .collect();
+
+    game.table.players[0].cards = cards.iter().step_by(2).take(n).cloned().collect();
+    game.table.players[1].cards = cards.iter().skip(1).step_by(2).take(n).cloned().collect();
+    game
+}
+
+fn bench_attack(c: &mut Criterion) {
+    let mut group = c.benchmark_group("attack");
+    for n in [6, 12, 20, 26] {
+        let game = game_with_hands(n);
+        let bot = MoskaAI::new(0);
+        group.bench_with_input(BenchmarkId::from_parameter(n), &game, |b, game| {
+            b.iter(|| bot.get_actions(game))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_attack);
+criterion_main!(benches);
@@ -0,0 +1,63 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use moska::{
+    game::{compact::CompactMoska, engine::Engine, moska::State},
+    Moska,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const PLAYOUTS: u64 = 100;
+
+fn new_game(players: u8) -> Moska {
+    let mut game = Moska::new(players);
+    game.new_round_with_seed(1);
+    game
+}
+
+fn bench_copy(c: &mut Criterion) {
+    let game = new_game(4);
+    let compact = CompactMoska::from_moska(&game).unwrap();
+
+    let mut group = c.benchmark_group("copy");
+    group.bench_function("moska", |b| b.iter(|| black_box(&game).clone()));
+    group.bench_function("compact", |b| b.iter(|| *black_box(&compact)));
+    group.finish();
+}
+
+// Random legal actions until the game is over, `PLAYOUTS` games per iteration
+fn bench_playouts(c: &mut Criterion) {
+    let game = new_game(4);
+    let compact = CompactMoska::from_moska(&game).unwrap();
+
+    let mut group = c.benchmark_group("playouts");
+    group.throughput(Throughput::Elements(PLAYOUTS));
+    group.sample_size(10);
+    group.bench_function("moska", |b| {
+        let mut rng = StdRng::seed_from_u64(0);
+        b.iter(|| {
+            for _ in 0..PLAYOUTS {
+                let mut game = game.clone();
+                while game.state != State::GameOver {
+                    let actions = game.legal_actions();
+                    game.apply(&actions[rng.gen_range(0..actions.len())]);
+                }
+            }
+        })
+    });
+    group.bench_function("compact", |b| {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut actions = Vec::with_capacity(64);
+        b.iter(|| {
+            for _ in 0..PLAYOUTS {
+                let mut game = compact;
+                while game.state != State::GameOver {
+                    game.legal_actions_into(&mut actions);
+                    game.play(actions[rng.gen_range(0..actions.len())]);
+                }
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_copy, bench_playouts);
+criterion_main!(benches);
@@ -0,0 +1,85 @@
+// Plays external bots against each other and the heuristic bot.
+//
+// Usage: cargo run --example bot_match -- <games> <seat>...
+// where each seat is `heuristic` or the command of an external bot.
+
+use std::{env, time::Duration};
+
+use moska::{
+    bot_protocol::process::{ExternalBot, ExternalSeat},
+    game::moska::State,
+    Action, Moska, MoskaAI,
+};
+
+enum Seat {
+    Heuristic(MoskaAI),
+    External(ExternalSeat),
+}
+
+impl Seat {
+    fn get_actions(&mut self, game: &Moska) -> Vec<Action> {
+        match self {
+            Seat::Heuristic(bot) => bot.get_actions(game),
+            Seat::External(bot) => bot.get_actions(game),
+        }
+    }
+}
+
+fn main() -> Result<(), String> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some((games, commands)) = args.split_first() else {
+        return Err("usage: bot_match <games> <seat>...".to_string());
+    };
+    let games: u64 = games.parse().map_err(|_| "Games must be a number")?;
+    if !(2..=8).contains(&commands.len()) {
+        return Err("Seat 2 to 8 players".to_string());
+    }
+    let players = commands.len();
+
+    let mut wins = vec![0; players];
+    let mut errors = vec![0; players];
+    for seed in 0..games {
+        let mut seats = commands
+            .iter()
+            .enumerate()
+            .map(|(index, command)| {
+                if command == "heuristic" {
+                    return Ok(Seat::Heuristic(MoskaAI::new(index)));
+                }
+                let args: Vec<String> = command.split_whitespace().map(String::from).collect();
+                let bot = ExternalBot::spawn(&args[0], &args[1..], Duration::from_secs(5))?;
+                Ok(Seat::External(ExternalSeat::new(bot, players, index, 3)))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let mut game = Moska::new(players as u8);
+        game.new_round_with_seed(seed);
+        for _ in 0..10_000 {
+            if game.state == State::GameOver {
+                break;
+            }
+            let player = game.table.player_index;
+            for action in seats[player].get_actions(&game) {
+                game.player_action(action.action, action.card_index);
+            }
+            game.player_action(3, 0);
+        }
+
+        for (index, seat) in seats.iter().enumerate() {
+            if game.state == State::GameOver && game.table.players[index].cards.is_empty() {
+                wins[index] += 1;
+            }
+            if let Seat::External(seat) = seat {
+                errors[index] += seat.errors.len();
+            }
+        }
+    }
+
+    for (index, command) in commands.iter().enumerate() {
+        println!(
+            "Seat {index} ({command}): won {}/{games}, {} rejected moves",
+            wins[index], errors[index]
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,13 @@
+// Heuristic bot speaking the external bot protocol on stdin/stdout.
+//
+// Usage: cargo build --example reference_bot && moska --bot external=target/debug/examples/reference_bot
+
+use std::io;
+
+use moska::{bot_protocol::run_bot, MoskaAI};
+
+fn main() -> io::Result<()> {
+    run_bot("reference", io::stdin().lock(), io::stdout(), |game| {
+        MoskaAI::new(game.table.player_index).get_actions(game)
+    })
+}
@@ -0,0 +1,39 @@
+// Writes self-play training data.
+//
+// Usage: cargo run --release --example selfplay -- <output.csv|output.npy> [games] [players] [seed]
+
+use std::{env, fs::File, io::BufWriter};
+
+use moska::ai::selfplay::{write_csv, write_npy, SelfPlay};
+
+fn main() -> std::io::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(path) = args.first() else {
+        eprintln!("usage: selfplay <output.csv|output.npy> [games] [players] [seed]");
+        std::process::exit(1);
+    };
+    let arg = |index: usize, default: u64| {
+        args.get(index)
+            .map(|value| value.parse().expect("expected a number"))
+            .unwrap_or(default)
+    };
+
+    let games = arg(1, 100) as usize;
+    let players = arg(2, 2) as u8;
+    let seed = arg(3, 0);
+
+    let samples = SelfPlay::new(players, seed).generate(games);
+    let writer = BufWriter::new(File::create(path)?);
+
+    if path.ends_with(".npy") {
+        write_npy(&samples, writer)?;
+    } else {
+        write_csv(&samples, writer)?;
+    }
+
+    println!(
+        "Wrote {} samples from {games} games to {path}",
+        samples.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,67 @@
+// Trains the evaluation bot from self-play and writes its weights.
+//
+// Usage: cargo run --release --example train_eval -- <weights.txt> [games] [rounds] [players] [seed]
+
+use std::{env, fs};
+
+use moska::{
+    ai::eval::{train, EvalAI, TrainConfig},
+    game::moska::State,
+    Moska, MoskaAI,
+};
+
+fn main() -> std::io::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(path) = args.first() else {
+        eprintln!("usage: train_eval <weights.txt> [games] [rounds] [players] [seed]");
+        std::process::exit(1);
+    };
+    let arg = |index: usize, default: u64| {
+        args.get(index)
+            .map(|value| value.parse().expect("expected a number"))
+            .unwrap_or(default)
+    };
+
+    let defaults = TrainConfig::default();
+    let config = TrainConfig {
+        games: arg(1, defaults.games as u64) as usize,
+        rounds: arg(2, defaults.rounds as u64) as usize,
+        players: arg(3, defaults.players as u64) as u8,
+        seed: arg(4, defaults.seed),
+        ..defaults
+    };
+
+    let model = train(&config);
+    fs::write(path, model.to_text())?;
+    println!("Wrote weights to {path}");
+
+    // Evaluate against the heuristic bot in seat 0
+    let games = 200;
+    let mut wins = 0;
+    for seed in 0..games {
+        let mut game = Moska::new(config.players);
+        game.new_round_with_seed(u64::MAX - seed);
+
+        let eval_bot = EvalAI::new(0, model.clone());
+        for _ in 0..10_000 {
+            if game.state == State::GameOver {
+                break;
+            }
+            let player = game.table.player_index;
+            let actions = if player == 0 {
+                eval_bot.get_actions(&game)
+            } else {
+                MoskaAI::new(player).get_actions(&game)
+            };
+            for action in actions {
+                game.player_action(action.action, action.card_index);
+            }
+            game.player_action(3, 0);
+        }
+        if game.state == State::GameOver && game.table.players[0].cards.is_empty() {
+            wins += 1;
+        }
+    }
+    println!("Won {wins}/{games} games against the heuristic bot");
+    Ok(())
+}
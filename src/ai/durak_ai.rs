@@ -0,0 +1,200 @@
+use itertools::Itertools;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{
+    deck::Rank,
+    game::{
+        durak::{Durak, DurakAction},
+        moska::{card_cmp, card_rank_order, State},
+    },
+};
+
+#[wasm_bindgen]
+pub struct DurakAI {
+    player_index: usize,
+}
+
+#[wasm_bindgen]
+impl DurakAI {
+    #[wasm_bindgen(constructor)]
+    pub fn new(player_index: usize) -> Self {
+        Self { player_index }
+    }
+
+    // Plays the chosen action.
+    // Returns false when it is not the bot's turn.
+    pub fn play(&self, game: &mut Durak) -> bool {
+        match self.choose(game) {
+            Some(action) => game.apply(action),
+            None => false,
+        }
+    }
+}
+
+impl DurakAI {
+    // Chooses the action for current turn
+    pub fn choose(&self, game: &Durak) -> Option<DurakAction> {
+        if game.table.player_index != self.player_index {
+            return None;
+        }
+        let player = game.table.players.get(self.player_index)?;
+        let trump_suit = game.trump_card.suit;
+
+        // Hand indices from the lowest card up
+        let order: Vec<usize> = (0..player.cards.len())
+            .sorted_by(|a, b| card_cmp(&player.cards[*a], &player.cards[*b], trump_suit))
+            .collect();
+        let legal = game.legal_actions();
+        let is_trump = |index: usize| player.cards[index].suit == trump_suit;
+
+        match game.state {
+            State::PlayerAttacking => {
+                let lowest = order
+                    .iter()
+                    .copied()
+                    .find(|index| legal.contains(&DurakAction::Attack(*index)));
+
+                // Lead with the lowest card
+                if game.attack_cards.is_empty() {
+                    return lowest.map(DurakAction::Attack);
+                }
+
+                // Throw in cheap cards, saving trumps and high cards
+                // while there are cards left to draw
+                let deck_empty = game.table.deck.count() == 0;
+                match lowest {
+                    Some(index)
+                        if deck_empty
+                            || (!is_trump(index)
+                                && card_rank_order(player.cards[index].rank)
+                                    < card_rank_order(Rank::Jack)) =>
+                    {
+                        Some(DurakAction::Attack(index))
+                    }
+                    _ => Some(DurakAction::Pass),
+                }
+            }
+            State::PlayerDefending => {
+                // Pass the attack on when it costs no trump
+                let transfer = order.iter().copied().find(|index| {
+                    !is_trump(*index) && legal.contains(&DurakAction::Transfer(*index))
+                });
+                if let Some(index) = transfer {
+                    return Some(DurakAction::Transfer(index));
+                }
+
+                // Cover with the lowest card, keeping high trumps
+                // while there are cards left to draw
+                let defense = order
+                    .iter()
+                    .copied()
+                    .find(|index| legal.contains(&DurakAction::Defend(*index)));
+                match defense {
+                    Some(index)
+                        if !is_trump(index)
+                            || game.table.deck.count() == 0
+                            || card_rank_order(player.cards[index].rank)
+                                < card_rank_order(Rank::Ten) =>
+                    {
+                        Some(DurakAction::Defend(index))
+                    }
+                    _ => Some(DurakAction::Take),
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        deck::{Card, Deck, Suit},
+        game::durak::DurakVariant,
+    };
+
+    // Game of two with spades as trumps
+    fn game(variant: DurakVariant, hands: [Vec<Card>; 2], deck: Vec<Card>) -> Durak {
+        let mut game = Durak::new(2, variant).unwrap();
+        game.new_round_with_seed(0);
+        game.trump_card = Card::new(Suit::Spades, Rank::Six);
+        game.table.deck = Deck::from_cards(deck);
+        for (player, hand) in game.table.players.iter_mut().zip(hands) {
+            player.cards = hand;
+        }
+        game.table.player_index = 0;
+        game.attacker_index = 0;
+        game.defender_index = 1;
+        game
+    }
+
+    #[test]
+    fn test_attack() {
+        let mut game = game(
+            DurakVariant::Podkidnoy,
+            [
+                vec![
+                    Card::new(Suit::Spades, Rank::Seven),
+                    Card::new(Suit::Clubs, Rank::Queen),
+                    Card::new(Suit::Hearts, Rank::Queen),
+                    Card::new(Suit::Clubs, Rank::Nine),
+                ],
+                vec![
+                    Card::new(Suit::Clubs, Rank::King),
+                    Card::new(Suit::Hearts, Rank::King),
+                ],
+            ],
+            vec![Card::new(Suit::Spades, Rank::Ace)],
+        );
+        let bot = DurakAI::new(0);
+
+        // lowest card leads, trumps last
+        assert_eq!(bot.choose(&game), Some(DurakAction::Attack(3)));
+        assert_eq!(DurakAI::new(1).choose(&game), None);
+
+        // queens are kept while the deck lasts
+        game.attack_cards = vec![Card::new(Suit::Diamonds, Rank::Queen)];
+        game.defense_cards = vec![Card::new(Suit::Diamonds, Rank::King)];
+        assert_eq!(bot.choose(&game), Some(DurakAction::Pass));
+
+        game.table.deck = Deck::from_cards(vec![]);
+        assert_eq!(bot.choose(&game), Some(DurakAction::Attack(1)));
+    }
+
+    #[test]
+    fn test_defend() {
+        let mut game = game(
+            DurakVariant::Perevodnoy,
+            [
+                vec![
+                    Card::new(Suit::Clubs, Rank::Eight),
+                    Card::new(Suit::Hearts, Rank::Seven),
+                    Card::new(Suit::Diamonds, Rank::Seven),
+                ],
+                vec![
+                    Card::new(Suit::Hearts, Rank::Nine),
+                    Card::new(Suit::Spades, Rank::Ace),
+                    Card::new(Suit::Clubs, Rank::Nine),
+                ],
+            ],
+            vec![Card::new(Suit::Spades, Rank::Six)],
+        );
+        let bot = DurakAI::new(1);
+
+        game.attack(0);
+        assert_eq!(bot.choose(&game), Some(DurakAction::Defend(2)));
+
+        // a nine passes the attack on instead
+        let mut transferable = game.clone();
+        transferable.attack_cards = vec![Card::new(Suit::Diamonds, Rank::Nine)];
+        assert_eq!(bot.choose(&transferable), Some(DurakAction::Transfer(2)));
+
+        // only the ace of trumps covers, take instead
+        game.table.players[1].cards.remove(2);
+        assert_eq!(bot.choose(&game), Some(DurakAction::Take));
+
+        game.table.deck = Deck::from_cards(vec![]);
+        assert_eq!(bot.choose(&game), Some(DurakAction::Defend(1)));
+    }
+}
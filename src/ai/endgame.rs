@@ -0,0 +1,616 @@
+/*
+ * Exact solver for two player Moska endgames.
+ *
+ * Once the deck is empty and only two players hold cards,
+ * both hands are known and the game is of perfect information.
+ */
+
+use std::collections::{HashMap, HashSet};
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    ai::moska::Action,
+    card_set::{card_ordinal, CardSet},
+    deck::{Card, Suit},
+    game::moska::{card_beats, card_rank_order, State},
+    Moska,
+};
+
+// Bit of a card in a hand mask, masks are `CardSet` masks
+fn card_bit(card: &Card) -> u64 {
+    1 << card_ordinal(card)
+}
+
+fn mask_cards(mask: u64) -> impl Iterator<Item = Card> {
+    CardSet::from_mask(mask).iter()
+}
+
+fn cards_mask<'a>(cards: impl IntoIterator<Item = &'a Card>) -> u64 {
+    cards.into_iter().collect::<CardSet>().mask()
+}
+
+// Endgame position between two players.
+// Hands are indexed by seat order, `to_move` selects the hand in turn.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Position {
+    hands: [u64; 2],
+    table: u64,
+    to_move: usize,
+    defending: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Move {
+    // Attack with the cards in the mask
+    Attack(u64),
+    // Cover the table with the cards in the mask
+    Defend(u64),
+    // Take the attacking cards
+    Take,
+}
+
+enum Next {
+    // Player who made the move emptied their hand and won
+    Won,
+    Position(Position),
+}
+
+// Game-theoretic result and best move of an endgame position
+#[derive(Clone, Debug)]
+#[wasm_bindgen(getter_with_clone)]
+pub struct EndgameSolution {
+    // Player in turn wins with best play
+    pub win: bool,
+    // Best move as bot actions. Empty when taking the cards.
+    pub actions: Vec<Action>,
+    // Positions searched and moves generated
+    pub nodes: usize,
+}
+
+#[wasm_bindgen]
+pub struct EndgameSolver {
+    trump_suit: Suit,
+    // Transposition table: does the player to move win
+    tt: HashMap<Position, bool>,
+    nodes: usize,
+    max_nodes: Option<usize>,
+}
+
+impl Default for EndgameSolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl EndgameSolver {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            trump_suit: Suit::Hearts,
+            tt: HashMap::new(),
+            nodes: 0,
+            max_nodes: None,
+        }
+    }
+
+    // Solver that gives up after given amount of search work
+    pub fn with_node_limit(max_nodes: usize) -> Self {
+        Self {
+            max_nodes: Some(max_nodes),
+            ..Self::new()
+        }
+    }
+
+    // Solves the position for the current player.
+    //
+    // Returns nothing when the game is not in an endgame:
+    // deck must be empty, exactly two players must hold cards
+    // and the current player must not have cards on the table yet.
+    pub fn solve(&mut self, game: &Moska) -> Option<EndgameSolution> {
+        let (pos, seats) = self.position(game)?;
+        let current = &game.table.players[seats[pos.to_move]];
+
+        if self.trump_suit != game.trump_card.suit {
+            self.trump_suit = game.trump_card.suit;
+            self.tt.clear();
+        }
+        self.nodes = 0;
+
+        let mut best = None;
+        let mut win = false;
+        for mv in self.moves(&pos)? {
+            if best.is_none() {
+                best = Some(mv);
+            }
+            if self.move_wins(&pos, mv)? {
+                best = Some(mv);
+                win = true;
+                break;
+            }
+        }
+
+        let mask = match best? {
+            Move::Attack(mask) | Move::Defend(mask) => mask,
+            Move::Take => 0,
+        };
+
+        let mut actions: Vec<Action> = mask_cards(mask)
+            .filter_map(|card| current.card_index(&card))
+            .map(|card_index| Action {
+                action: 1,
+                card_index,
+            })
+            .collect();
+
+        // sort actions in descending order
+        actions.sort_by_key(|a| std::cmp::Reverse(a.card_index));
+
+        Some(EndgameSolution {
+            win,
+            actions,
+            nodes: self.nodes,
+        })
+    }
+}
+
+impl EndgameSolver {
+    // Builds the solver position from the game.
+    // Returns the position along with table seats of the two hands.
+    fn position(&self, game: &Moska) -> Option<(Position, [usize; 2])> {
+        if game.table.deck.count() > 0 {
+            return None;
+        }
+
+        let seats: Vec<usize> = game
+            .table
+            .players
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| !p.cards.is_empty())
+            .map(|(index, _)| index)
+            .collect();
+        if seats.len() != 2 {
+            return None;
+        }
+
+        let to_move = seats.iter().position(|&s| s == game.table.player_index)?;
+        let hands = [
+            cards_mask(&game.table.players[seats[0]].cards),
+            cards_mask(&game.table.players[seats[1]].cards),
+        ];
+
+        let pos = match game.state {
+            State::PlayerAttacking if game.attacker_cards.is_empty() => Position {
+                hands,
+                table: 0,
+                to_move,
+                defending: false,
+            },
+            State::PlayerDefending
+                if game.defender_cards.is_empty() && !game.attacker_cards.is_empty() =>
+            {
+                Position {
+                    hands,
+                    table: cards_mask(&game.attacker_cards),
+                    to_move,
+                    defending: true,
+                }
+            }
+            _ => return None,
+        };
+
+        Some((pos, [seats[0], seats[1]]))
+    }
+
+    // Checks if the player to move wins the position.
+    // Returns nothing when the node limit was hit.
+    //
+    // The outcome is either a win or a loss, so alpha-beta
+    // reduces to cutting off on the first winning move.
+    fn wins(&mut self, pos: &Position) -> Option<bool> {
+        if let Some(&win) = self.tt.get(pos) {
+            return Some(win);
+        }
+
+        self.nodes += 1;
+        if self.max_nodes.is_some_and(|max| self.nodes > max) {
+            return None;
+        }
+
+        let mut win = false;
+        for mv in self.moves(pos)? {
+            if self.move_wins(pos, mv)? {
+                win = true;
+                break;
+            }
+        }
+
+        self.tt.insert(*pos, win);
+        Some(win)
+    }
+
+    fn move_wins(&mut self, pos: &Position, mv: Move) -> Option<bool> {
+        match self.apply(pos, mv) {
+            Next::Won => Some(true),
+            Next::Position(next) if next.to_move == pos.to_move => self.wins(&next),
+            Next::Position(next) => self.wins(&next).map(|win| !win),
+        }
+    }
+
+    // Applies the move following the rules of `Moska::player_action`
+    fn apply(&self, pos: &Position, mv: Move) -> Next {
+        let me = pos.to_move;
+        let other = 1 - me;
+        let mut next = *pos;
+
+        match mv {
+            Move::Attack(mask) => {
+                next.hands[me] &= !mask;
+                if next.hands[me] == 0 {
+                    return Next::Won;
+                }
+                next.table = mask;
+                next.to_move = other;
+                next.defending = true;
+            }
+            Move::Defend(mask) => {
+                next.hands[me] &= !mask;
+                if next.hands[me] == 0 {
+                    return Next::Won;
+                }
+                next.table = 0;
+                next.defending = false;
+            }
+            Move::Take => {
+                next.hands[me] |= pos.table;
+                next.table = 0;
+                next.to_move = other;
+                next.defending = false;
+            }
+        }
+
+        Next::Position(next)
+    }
+
+    // Lists moves of the player in turn.
+    // Work done generating the moves is counted towards the node limit,
+    // returns nothing when the limit was hit.
+    fn moves(&mut self, pos: &Position) -> Option<Vec<Move>> {
+        let mut budget = self
+            .max_nodes
+            .map_or(usize::MAX, |max| max.saturating_sub(self.nodes));
+        let initial = budget;
+        let hand = pos.hands[pos.to_move];
+
+        let moves = if pos.defending {
+            defenses(hand, pos.table, self.trump_suit, &mut budget).map(|defenses| {
+                let mut moves: Vec<Move> = defenses.into_iter().map(Move::Defend).collect();
+                moves.push(Move::Take);
+                moves
+            })
+        } else {
+            let max_cards = pos.hands[1 - pos.to_move].count_ones();
+            attacks(hand, max_cards, &mut budget).map(|mut attacks| {
+                // Try to get rid of most cards first
+                attacks.sort_by_key(|mask| std::cmp::Reverse(mask.count_ones()));
+                attacks.into_iter().map(Move::Attack).collect()
+            })
+        };
+
+        self.nodes += initial - budget;
+        moves
+    }
+}
+
+// Spends a unit of work from the budget.
+// Returns false when the budget is exhausted.
+fn spend(budget: &mut usize) -> bool {
+    if *budget == 0 {
+        return false;
+    }
+    *budget -= 1;
+    true
+}
+
+// Lists valid attacks: a single card or paired cards,
+// no more than there are cards in defender's hand.
+fn attacks(hand: u64, max_cards: u32, budget: &mut usize) -> Option<Vec<u64>> {
+    let mut attacks: Vec<u64> = mask_cards(hand).map(|card| card_bit(&card)).collect();
+
+    // Subsets of two or more cards for each rank
+    let hand_set = CardSet::from_mask(hand);
+    let groups: Vec<Vec<u64>> = hand_set
+        .ranks()
+        .map(|rank| hand_set.of_rank(rank).mask())
+        .filter(|mask| mask.count_ones() > 1)
+        .map(|mask| {
+            subsets(mask)
+                .filter(|subset| subset.count_ones() > 1)
+                .collect()
+        })
+        .collect();
+
+    fn combine(
+        groups: &[Vec<u64>],
+        acc: u64,
+        max_cards: u32,
+        out: &mut Vec<u64>,
+        budget: &mut usize,
+    ) -> bool {
+        if !spend(budget) {
+            return false;
+        }
+
+        match groups.split_first() {
+            None => {
+                if acc != 0 {
+                    out.push(acc);
+                }
+                true
+            }
+            Some((group, rest)) => {
+                combine(rest, acc, max_cards, out, budget)
+                    && group.iter().all(|subset| {
+                        (acc | subset).count_ones() > max_cards
+                            || combine(rest, acc | subset, max_cards, out, budget)
+                    })
+            }
+        }
+    }
+
+    if !combine(&groups, 0, max_cards, &mut attacks, budget) {
+        return None;
+    }
+
+    Some(
+        attacks
+            .into_iter()
+            .filter(|mask| mask.count_ones() <= max_cards)
+            .collect(),
+    )
+}
+
+// Lists sets of cards in hand that cover all the attacking cards,
+// cheapest sets first.
+fn defenses(hand: u64, table: u64, trump_suit: Suit, budget: &mut usize) -> Option<Vec<u64>> {
+    let attackers: Vec<Card> = mask_cards(table).collect();
+    let cards: Vec<Card> = mask_cards(hand).collect();
+
+    fn assign(
+        attackers: &[Card],
+        cards: &[Card],
+        trump_suit: Suit,
+        used: u64,
+        out: &mut HashSet<u64>,
+        budget: &mut usize,
+    ) -> bool {
+        if !spend(budget) {
+            return false;
+        }
+
+        match attackers.split_first() {
+            None => {
+                out.insert(used);
+                true
+            }
+            Some((atk, rest)) => cards.iter().all(|def| {
+                let bit = card_bit(def);
+                used & bit != 0
+                    || !card_beats(atk, def, trump_suit)
+                    || assign(rest, cards, trump_suit, used | bit, out, budget)
+            }),
+        }
+    }
+
+    let mut found = HashSet::new();
+    if !assign(&attackers, &cards, trump_suit, 0, &mut found, budget) {
+        return None;
+    }
+
+    let cost = |mask: &u64| -> usize {
+        mask_cards(*mask)
+            .map(|card| card_rank_order(card.rank) + if card.suit == trump_suit { 14 } else { 0 })
+            .sum()
+    };
+
+    let mut defenses: Vec<u64> = found.into_iter().collect();
+    defenses.sort_by_key(|mask| (cost(mask), *mask));
+    Some(defenses)
+}
+
+// Iterates all non-empty subsets of the mask
+fn subsets(mask: u64) -> impl Iterator<Item = u64> {
+    let mut subset = mask;
+    let mut done = mask == 0;
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        let current = subset;
+        subset = (subset - 1) & mask;
+        done = subset == 0;
+        Some(current)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::testing::{play_out, play_turn},
+        MoskaAI, Rank,
+    };
+
+    // Sets up a two player game with an empty deck
+    fn endgame(hands: [Vec<Card>; 2], trump_suit: Suit) -> Moska {
+        let mut game = Moska::new(2);
+        game.new_round();
+        while game.table.deck.pop().is_some() {}
+        game.trump_card = Card::new(trump_suit, Rank::Two);
+        let [a, b] = hands;
+        game.table.players[0].cards = a;
+        game.table.players[1].cards = b;
+        game
+    }
+
+    #[test]
+    fn test_not_endgame() {
+        let mut game = Moska::new(2);
+        game.new_round();
+        assert!(EndgameSolver::new().solve(&game).is_none());
+    }
+
+    #[test]
+    fn test_single_card_wins() {
+        // attacker empties their hand with the last card
+        let game = endgame(
+            [
+                vec![Card::new(Suit::Clubs, Rank::Two)],
+                vec![Card::new(Suit::Hearts, Rank::Ace)],
+            ],
+            Suit::Spades,
+        );
+
+        let solution = EndgameSolver::new().solve(&game).unwrap();
+        assert!(solution.win);
+        assert_eq!(
+            solution.actions,
+            vec![Action {
+                action: 1,
+                card_index: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn test_pair_wins() {
+        // playing the pair at once wins, single cards get covered
+        let game = endgame(
+            [
+                vec![
+                    Card::new(Suit::Clubs, Rank::Five),
+                    Card::new(Suit::Diamonds, Rank::Five),
+                ],
+                vec![
+                    Card::new(Suit::Clubs, Rank::Six),
+                    Card::new(Suit::Diamonds, Rank::Six),
+                    Card::new(Suit::Hearts, Rank::Two),
+                ],
+            ],
+            Suit::Spades,
+        );
+
+        let solution = EndgameSolver::new().solve(&game).unwrap();
+        assert!(solution.win);
+        assert_eq!(solution.actions.len(), 2);
+    }
+
+    #[test]
+    fn test_defender_loses() {
+        // defender can cover, but the attacker plays out first
+        let mut game = endgame(
+            [
+                vec![Card::new(Suit::Clubs, Rank::Three)],
+                vec![
+                    Card::new(Suit::Clubs, Rank::Four),
+                    Card::new(Suit::Hearts, Rank::Two),
+                ],
+            ],
+            Suit::Spades,
+        );
+        game.player_action(1, 0);
+        game.player_action(3, 0);
+
+        // attacker ran out of cards
+        assert_eq!(game.state, State::GameOver);
+        assert!(EndgameSolver::new().solve(&game).is_none());
+    }
+
+    #[test]
+    fn test_defend_or_take() {
+        // covering leaves a single card that can be played out
+        let mut game = endgame(
+            [
+                vec![
+                    Card::new(Suit::Clubs, Rank::Three),
+                    Card::new(Suit::Hearts, Rank::King),
+                ],
+                vec![
+                    Card::new(Suit::Clubs, Rank::Four),
+                    Card::new(Suit::Diamonds, Rank::Two),
+                ],
+            ],
+            Suit::Spades,
+        );
+        game.player_action(1, 0);
+        game.player_action(3, 0);
+        assert_eq!(game.state, State::PlayerDefending);
+
+        let solution = EndgameSolver::new().solve(&game).unwrap();
+        assert!(solution.win);
+        assert_eq!(
+            solution.actions,
+            vec![Action {
+                action: 1,
+                card_index: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let game = endgame(
+            [
+                vec![
+                    Card::new(Suit::Clubs, Rank::Three),
+                    Card::new(Suit::Hearts, Rank::King),
+                    Card::new(Suit::Diamonds, Rank::Nine),
+                ],
+                vec![
+                    Card::new(Suit::Clubs, Rank::Four),
+                    Card::new(Suit::Diamonds, Rank::Two),
+                    Card::new(Suit::Spades, Rank::Seven),
+                ],
+            ],
+            Suit::Spades,
+        );
+
+        assert!(EndgameSolver::with_node_limit(1).solve(&game).is_none());
+        assert!(EndgameSolver::new().solve(&game).is_some());
+    }
+
+    // Plays the solver's moves against the heuristic bot.
+    // The solver must never lose a position it claims to win.
+    #[test]
+    fn test_oracle() {
+        for _ in 0..5 {
+            let mut game = Moska::new(2);
+            game.new_round();
+
+            let bot = MoskaAI::new(1);
+            let mut solver = EndgameSolver::with_node_limit(20_000);
+            let mut claimed_win = false;
+
+            assert!(play_out(&mut game, |game| {
+                let actions = if game.table.player_index == 0 {
+                    match solver.solve(game) {
+                        Some(solution) => {
+                            claimed_win |= solution.win;
+                            solution.actions
+                        }
+                        None => MoskaAI::new(0).get_actions(game),
+                    }
+                } else {
+                    bot.get_actions(game)
+                };
+                play_turn(game, actions);
+            }));
+
+            if claimed_win {
+                assert!(game.table.players[0].cards.is_empty());
+            }
+        }
+    }
+}
@@ -0,0 +1,480 @@
+/*
+ * Learned evaluation bot.
+ *
+ * A linear model scores the position after each candidate action,
+ * predicting the chance of getting rid of all cards.
+ * Weights are trained offline from self-play and stored as text.
+ */
+
+use itertools::Itertools;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    ai::moska::{cheapest_defense, Action, MoskaAI},
+    game::moska::{card_cmp, card_rank_order, State},
+    Card, Moska,
+};
+
+// Header line of the weights file format
+const WEIGHTS_HEADER: &str = "moska-eval v1";
+
+// Games taking more actions than this are considered stalled
+const MAX_STEPS: usize = 1_000;
+
+// Length of model inputs:
+// hand, table and discard pile as trump and non-trump rank counts,
+// hand sizes, deck count and whether the player is in turn.
+pub const NUM_INPUTS: usize = 3 * 26 + 4;
+
+// Adds cards to rank counts, trumps first.
+// Suits other than trump are alike to the model.
+fn push_ranks<'a>(inputs: &mut Vec<f32>, cards: impl IntoIterator<Item = &'a Card>, game: &Moska) {
+    let mut counts = [0.0; 26];
+    for card in cards {
+        let rank = card_rank_order(card.rank);
+        if rank < 13 {
+            if card.suit == game.trump_card.suit {
+                counts[rank] += 1.0;
+            } else {
+                counts[13 + rank] += 1.0 / 3.0;
+            }
+        }
+    }
+    inputs.extend(counts);
+}
+
+// Encodes the game as seen by the player for the model
+pub fn inputs(game: &Moska, player_index: usize) -> Vec<f32> {
+    let mut inputs = Vec::with_capacity(NUM_INPUTS);
+
+    let hand = game.player_cards(player_index);
+    push_ranks(&mut inputs, &hand, game);
+    push_ranks(
+        &mut inputs,
+        game.attacker_cards.iter().chain(&game.defender_cards),
+        game,
+    );
+    push_ranks(&mut inputs, &game.discarded, game);
+
+    // Smallest hand among opponents still playing
+    let opponent_cards = game
+        .table
+        .players
+        .iter()
+        .enumerate()
+        .filter(|(index, player)| *index != player_index && !player.cards.is_empty())
+        .map(|(_, player)| player.cards.len())
+        .min()
+        .unwrap_or(0);
+
+    inputs.push(hand.len() as f32 / 10.0);
+    inputs.push(opponent_cards as f32 / 10.0);
+    inputs.push(game.table.deck.count() as f32 / 52.0);
+    inputs.push(
+        (game.state != State::GameOver && game.table.player_index == player_index) as u8 as f32,
+    );
+
+    inputs
+}
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+// Logistic regression over model inputs
+#[derive(Clone, Debug, PartialEq)]
+#[wasm_bindgen]
+pub struct EvalModel {
+    // Feature weights followed by the bias
+    weights: Vec<f32>,
+}
+
+impl Default for EvalModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl EvalModel {
+    // Model with all weights zero
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            weights: vec![0.0; NUM_INPUTS + 1],
+        }
+    }
+
+    // Parses weights written by `to_text`
+    pub fn from_text(text: &str) -> Result<EvalModel, String> {
+        let mut lines = text.lines();
+        if lines.next().map(str::trim) != Some(WEIGHTS_HEADER) {
+            return Err(format!("Expected header '{WEIGHTS_HEADER}'"));
+        }
+
+        let weights = lines
+            .flat_map(str::split_whitespace)
+            .map(|value| {
+                value
+                    .parse::<f32>()
+                    .map_err(|_| format!("Invalid weight '{value}'"))
+            })
+            .collect::<Result<Vec<f32>, String>>()?;
+
+        if weights.len() != NUM_INPUTS + 1 {
+            return Err(format!(
+                "Expected {} weights, found {}",
+                NUM_INPUTS + 1,
+                weights.len()
+            ));
+        }
+
+        Ok(Self { weights })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{WEIGHTS_HEADER}\n");
+        for chunk in self.weights.chunks(13) {
+            text.push_str(&chunk.iter().map(f32::to_string).join(" "));
+            text.push('\n');
+        }
+        text
+    }
+
+    // Predicts the chance of winning from model inputs
+    pub fn predict(&self, inputs: &[f32]) -> f32 {
+        sigmoid(self.logit(inputs))
+    }
+}
+
+impl EvalModel {
+    fn logit(&self, inputs: &[f32]) -> f32 {
+        inputs
+            .iter()
+            .zip(&self.weights)
+            .map(|(value, weight)| value * weight)
+            .sum::<f32>()
+            + self.weights[NUM_INPUTS]
+    }
+
+    // Single gradient step of logistic loss towards the outcome
+    fn update(&mut self, inputs: &[f32], outcome: f32, learning_rate: f32) {
+        let error = outcome - self.predict(inputs);
+        for (weight, value) in self.weights.iter_mut().zip(inputs) {
+            *weight += learning_rate * error * value;
+        }
+        self.weights[NUM_INPUTS] += learning_rate * error;
+    }
+}
+
+// Bot choosing the action with the best evaluated outcome
+#[wasm_bindgen]
+pub struct EvalAI {
+    player_index: usize,
+    model: EvalModel,
+}
+
+#[wasm_bindgen]
+impl EvalAI {
+    #[wasm_bindgen(constructor)]
+    pub fn new(player_index: usize, model: EvalModel) -> Self {
+        Self {
+            player_index,
+            model,
+        }
+    }
+
+    pub fn get_actions(&self, game: &Moska) -> Vec<Action> {
+        if game.table.player_index != self.player_index {
+            return vec![];
+        }
+
+        candidates(game, self.player_index)
+            .into_iter()
+            .map(|actions| {
+                let score = self
+                    .model
+                    .predict(&afterstate(game, self.player_index, &actions));
+                (actions, score)
+            })
+            // First candidate wins ties
+            .rev()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(actions, _)| actions)
+            .unwrap_or_default()
+    }
+}
+
+// Model inputs for the player after the actions and submitting them
+fn afterstate(game: &Moska, player_index: usize, actions: &[Action]) -> Vec<f32> {
+    let mut game = game.search_copy();
+    for action in actions {
+        game.player_action(action.action, action.card_index);
+    }
+    game.player_action(3, 0);
+
+    inputs(&game, player_index)
+}
+
+// Lists candidate actions for the player in turn.
+//
+// The heuristic bot's choice comes first, followed by single cards
+// and paired cards by rank when attacking, or the cheapest defense
+// and taking when defending. Trump card is swapped when the
+// heuristic bot would swap it.
+pub fn candidates(game: &Moska, player_index: usize) -> Vec<Vec<Action>> {
+    let hint = MoskaAI::new(player_index).hint(game);
+    let swap = hint.actions.first().copied().filter(|a| a.action == 4);
+
+    // Plan the rest with the hand after swapping
+    let mut swapped = game.search_copy();
+    if let Some(swap) = swap {
+        swapped.swap_trumpcard(swap.card_index);
+    }
+    let Some(player) = swapped.table.players.get(player_index) else {
+        return vec![];
+    };
+    let trump_suit = swapped.trump_card.suit;
+
+    let to_actions = |cards: &[&Card]| -> Vec<Action> {
+        let mut actions: Vec<Action> = cards
+            .iter()
+            .filter_map(|card| player.card_index(card))
+            .map(|card_index| Action {
+                action: 1,
+                card_index,
+            })
+            .collect();
+
+        // sort actions in descending order
+        actions.sort_by_key(|a| std::cmp::Reverse(a.card_index));
+        swap.into_iter().chain(actions).collect()
+    };
+
+    let cards: Vec<&Card> = player
+        .cards
+        .iter()
+        .sorted_by(|a, b| card_cmp(a, b, trump_suit))
+        .collect();
+
+    let mut candidates = vec![hint.actions];
+    match swapped.state {
+        State::PlayerDefending => {
+            if let Some(defense) = cheapest_defense(&cards, &swapped.attacker_cards, trump_suit) {
+                candidates.push(to_actions(&defense));
+            }
+            candidates.push(to_actions(&[]));
+        }
+        State::PlayerAttacking => {
+            let max_cards = swapped.table.players[swapped.next_player()].cards.len();
+
+            for card in &cards {
+                candidates.push(to_actions(&[card]));
+            }
+
+            // Cheapest cards of each rank that has pairs
+            for (_, group) in &cards
+                .iter()
+                .copied()
+                .sorted_by_key(|card| card_rank_order(card.rank))
+                .chunk_by(|card| card.rank as u8)
+            {
+                let group: Vec<&Card> = group.take(max_cards).collect();
+                if group.len() > 1 {
+                    candidates.push(to_actions(&group));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    candidates.into_iter().unique().collect()
+}
+
+// Settings for training the evaluation model
+#[derive(Clone, Debug)]
+pub struct TrainConfig {
+    pub players: u8,
+    // Self-play and fit rounds
+    pub rounds: usize,
+    // Games played per round
+    pub games: usize,
+    // Passes over the collected positions per round
+    pub epochs: usize,
+    pub learning_rate: f32,
+    // Chance of playing a random candidate for exploration
+    pub epsilon: f32,
+    pub seed: u64,
+}
+
+impl Default for TrainConfig {
+    fn default() -> Self {
+        Self {
+            players: 2,
+            rounds: 8,
+            games: 200,
+            epochs: 4,
+            learning_rate: 0.003,
+            epsilon: 0.1,
+            seed: 0,
+        }
+    }
+}
+
+// Trains the model from self-play.
+//
+// Each round the bots play with the current model, and the model
+// is fitted to the outcomes of the positions they played into.
+// Same config yields the same weights.
+pub fn train(config: &TrainConfig) -> EvalModel {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut model = EvalModel::new();
+    let mut positions: Vec<(Vec<f32>, f32)> = vec![];
+
+    for _ in 0..config.rounds {
+        for _ in 0..config.games {
+            positions.extend(self_play(&model, config, &mut rng));
+        }
+
+        for _ in 0..config.epochs {
+            positions.shuffle(&mut rng);
+            for (inputs, outcome) in &positions {
+                model.update(inputs, *outcome, config.learning_rate);
+            }
+        }
+    }
+
+    model
+}
+
+// Plays a single game with epsilon-greedy bots.
+// Returns positions played into with the outcome of their player.
+// Nobody wins a game that goes around in circles.
+fn self_play(model: &EvalModel, config: &TrainConfig, rng: &mut StdRng) -> Vec<(Vec<f32>, f32)> {
+    let mut game = Moska::new(config.players);
+    game.new_round_with_seed(rng.gen());
+
+    let mut played: Vec<(Vec<f32>, usize)> = vec![];
+
+    for _ in 0..MAX_STEPS {
+        if game.state == State::GameOver {
+            break;
+        }
+
+        let player = game.table.player_index;
+        let candidates = candidates(&game, player);
+
+        let actions = if rng.gen::<f32>() < config.epsilon {
+            candidates.choose(rng).cloned().unwrap_or_default()
+        } else {
+            EvalAI::new(player, model.clone()).get_actions(&game)
+        };
+
+        played.push((afterstate(&game, player, &actions), player));
+
+        for action in actions {
+            game.player_action(action.action, action.card_index);
+        }
+        game.player_action(3, 0);
+    }
+
+    let finished = game.state == State::GameOver;
+
+    played
+        .into_iter()
+        .map(|(inputs, player)| {
+            let won = finished && game.table.players[player].cards.is_empty();
+            let outcome = won as u8 as f32;
+            (inputs, outcome)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_config(seed: u64) -> TrainConfig {
+        TrainConfig {
+            rounds: 2,
+            games: 5,
+            epochs: 2,
+            seed,
+            ..TrainConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_train_deterministic() {
+        let a = train(&small_config(3));
+        let b = train(&small_config(3));
+        let c = train(&small_config(4));
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(a, EvalModel::new());
+    }
+
+    #[test]
+    fn test_weights_text() {
+        let model = train(&small_config(1));
+        let text = model.to_text();
+        assert_eq!(EvalModel::from_text(&text), Ok(model));
+
+        assert!(EvalModel::from_text("").is_err());
+        assert!(EvalModel::from_text("moska-eval v1\n1 2 3").is_err());
+        assert!(EvalModel::from_text(&text.replace(' ', " x ")).is_err());
+    }
+
+    #[test]
+    fn test_candidates() {
+        let mut game = Moska::new(2);
+        game.new_round_with_seed(9);
+
+        let candidates = candidates(&game, 0);
+        // heuristic choice and each single card at least
+        assert!(candidates.len() > game.table.players[0].cards.len());
+
+        for actions in candidates {
+            let mut game = game.clone();
+            for action in actions {
+                game.player_action(action.action, action.card_index);
+            }
+            assert!(game.valid());
+        }
+    }
+
+    #[test]
+    fn test_eval_bot() {
+        let model = train(&small_config(2));
+
+        let mut game = Moska::new(3);
+        game.new_round_with_seed(5);
+
+        let eval_bot = EvalAI::new(0, model);
+
+        // bot should be able to play against the heuristic bots
+        for _ in 0..MAX_STEPS {
+            if game.state == State::GameOver {
+                break;
+            }
+
+            let i = game.table.player_index;
+            let actions = if i == 0 {
+                eval_bot.get_actions(&game)
+            } else {
+                MoskaAI::new(i).get_actions(&game)
+            };
+            for action in actions {
+                game.player_action(action.action, action.card_index);
+            }
+            assert!(
+                game.valid()
+                    || (game.state == State::PlayerDefending && game.defender_cards.is_empty())
+            );
+            game.player_action(3, 0);
+        }
+        assert_eq!(game.state, State::GameOver);
+    }
+}
@@ -0,0 +1,49 @@
+/*
+ * Fixed-size feature encoding of Moska decision points
+ */
+
+use crate::{card_set::CardSet, deck::Suit, Moska};
+
+// Cards in a standard deck
+pub const NUM_CARDS: usize = 52;
+
+// Length of a feature vector:
+// hand, table, discard pile and known opponent cards as card masks,
+// one-hot trump suit and deck count.
+pub const NUM_FEATURES: usize = 4 * NUM_CARDS + 4 + 1;
+
+// Writes the cards as one-hot values by `card_set::card_ordinal`.
+// Jokers have no position.
+pub fn push_cards(features: &mut Vec<f32>, cards: CardSet) {
+    features.extend((0..NUM_CARDS).map(|i| ((cards.mask() >> i) & 1) as f32));
+}
+
+// Encodes the game as seen by the player.
+//
+// `known_opponent` holds opponent cards the player has seen,
+// for example cards taken from the table.
+pub fn encode(game: &Moska, player_index: usize, known_opponent: CardSet) -> Vec<f32> {
+    let mut features = Vec::with_capacity(NUM_FEATURES);
+
+    push_cards(
+        &mut features,
+        game.player_cards(player_index).iter().collect(),
+    );
+    push_cards(
+        &mut features,
+        game.attacker_cards
+            .iter()
+            .chain(&game.defender_cards)
+            .collect(),
+    );
+    push_cards(&mut features, game.discarded.iter().collect());
+    push_cards(&mut features, known_opponent);
+
+    for suit in [Suit::Clubs, Suit::Hearts, Suit::Diamonds, Suit::Spades] {
+        features.push((game.trump_card.suit == suit) as u8 as f32);
+    }
+
+    features.push(game.table.deck.count() as f32);
+
+    features
+}
@@ -1,3 +1,83 @@
+pub mod durak_ai;
+pub mod endgame;
+pub mod eval;
+pub mod features;
 pub mod moska_ai;
+pub mod paskahousu_ai;
+pub mod search;
+pub mod selfplay;
+pub mod simulate;
 
 pub use moska_ai as moska;
+
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use wasm_bindgen::prelude::*;
+
+use crate::Moska;
+use moska_ai::{Action, MoskaAI};
+use search::{moska_turn, MctsBot, MonteCarloBot, RandomBot};
+
+// Search budgets of the bot strategies
+const MONTE_CARLO_PLAYOUTS: usize = 10;
+const MCTS_ITERATIONS: usize = 300;
+
+// Bots that can play a seat without further setup
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[wasm_bindgen]
+pub enum BotStrategy {
+    Heuristic,
+    Random,
+    MonteCarlo,
+    Mcts,
+}
+
+impl BotStrategy {
+    // Actions for the player in turn, see `MoskaAI::get_actions`
+    pub fn get_actions(self, game: &Moska) -> Vec<Action> {
+        self.get_actions_with_seed(game, rand::random())
+    }
+
+    // Actions for the player in turn, searching bots choosing by the seed
+    pub fn get_actions_with_seed(self, game: &Moska, seed: u64) -> Vec<Action> {
+        let player_index = game.table.player_index;
+        match self {
+            BotStrategy::Heuristic => MoskaAI::new(player_index).get_actions(game),
+            BotStrategy::Random => moska_turn(game, &mut RandomBot::new(seed)),
+            BotStrategy::MonteCarlo => {
+                moska_turn(game, &mut MonteCarloBot::new(MONTE_CARLO_PLAYOUTS, seed))
+            }
+            BotStrategy::Mcts => moska_turn(game, &mut MctsBot::new(MCTS_ITERATIONS, seed)),
+        }
+    }
+}
+
+// Strategy by the name it is shown with
+impl FromStr for BotStrategy {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "heuristic" => Ok(BotStrategy::Heuristic),
+            "random" => Ok(BotStrategy::Random),
+            "montecarlo" => Ok(BotStrategy::MonteCarlo),
+            "mcts" => Ok(BotStrategy::Mcts),
+            _ => Err(format!("Unknown bot strategy '{name}'")),
+        }
+    }
+}
+
+impl Display for BotStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            BotStrategy::Heuristic => "heuristic",
+            BotStrategy::Random => "random",
+            BotStrategy::MonteCarlo => "montecarlo",
+            BotStrategy::Mcts => "mcts",
+        };
+        write!(f, "{name}")
+    }
+}
@@ -1,118 +1,203 @@
-use std::ops::Deref;
+use std::cmp::Ordering;
 
 use crate::{
-    game::moska::{card_cmp, find_pairs, State},
+    deck::{Rank, Suit},
+    game::moska::{card_beats, card_cmp, card_rank_order, State},
     Card, Moska,
 };
 use itertools::Itertools;
 use wasm_bindgen::prelude::wasm_bindgen;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[wasm_bindgen]
 pub struct Action {
     pub action: usize,
     pub card_index: usize,
 }
 
+// Machine-readable reason behind a suggested play
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[wasm_bindgen]
+pub enum HintReason {
+    // Not the player's turn, nothing to suggest
+    Wait,
+    // Cover the attacking cards with the lowest cards that hold
+    DefendWithLowestCoveringCards,
+    // No covering cards in hand, take the attacking cards
+    TakeCards,
+    // Covering would cost more than taking, take the attacking cards
+    TakeToSaveTrumps,
+    // Attack with as many paired cards as the defender can take
+    AttackWithPairs,
+    // No pairs to play, attack with the lowest single card
+    AttackWithLowestCard,
+    // Endgame solver found a move that wins with best play
+    PlayForcedWin,
+}
+
+// Suggested actions for a player along with the reason for them
+#[derive(Clone, Debug)]
+#[wasm_bindgen(getter_with_clone)]
+pub struct Hint {
+    pub actions: Vec<Action>,
+    pub reason: HintReason,
+}
+
+// Weights for choosing between defending and taking the cards
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[wasm_bindgen]
+pub struct DefenseWeights {
+    // Cost of a card taken into hand while the deck is full
+    pub take_early: f32,
+    // Cost of a card taken into hand once the deck is empty
+    pub take_late: f32,
+    // Extra cost of spending a trump card, on top of its rank.
+    // Values below `MIN_TRUMP_WEIGHT` are raised to it, so that any trump
+    // costs more than any other card as `cheapest_defense` assumes.
+    pub trump: f32,
+}
+
+// Trump two must cost more than an ace of another suit
+pub const MIN_TRUMP_WEIGHT: f32 = 13.0;
+
+impl Default for DefenseWeights {
+    fn default() -> Self {
+        Self {
+            take_early: 6.0,
+            take_late: 25.0,
+            trump: MIN_TRUMP_WEIGHT,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl DefenseWeights {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl DefenseWeights {
+    // Cost of spending the card on defense
+    pub fn card_cost(&self, card: &Card, trump_suit: Suit) -> f32 {
+        let trump = match card.suit == trump_suit {
+            true => self.trump.max(MIN_TRUMP_WEIGHT),
+            false => 0.0,
+        };
+        card_rank_order(card.rank) as f32 + trump
+    }
+}
+
 #[wasm_bindgen]
 pub struct MoskaAI {
     player_index: usize,
+    weights: DefenseWeights,
 }
 
 #[wasm_bindgen]
 impl MoskaAI {
     #[wasm_bindgen(constructor)]
     pub fn new(player_index: usize) -> Self {
-        Self { player_index }
+        Self::with_weights(player_index, DefenseWeights::default())
+    }
+
+    pub fn with_weights(player_index: usize, weights: DefenseWeights) -> Self {
+        Self {
+            player_index,
+            weights,
+        }
     }
 
     pub fn get_actions(&self, game: &Moska) -> Vec<Action> {
-        let mut actions = vec![];
+        self.hint(game).actions
+    }
 
-        //let resolve_card_index = |mut action: Action, previous_action: Option<&Action>| {
-        //    if let Some(previous_action) = previous_action {
-        //        if previous_action.card_index < action.card_index {
-        //            action.card_index -= 1;
-        //        }
-        //    }
-        //    action
-        //};
+    // Decides the actions for current turn and explains the choice
+    pub fn hint(&self, game: &Moska) -> Hint {
+        // Swap the trump card first and plan rest of the turn with the new hand
+        if let Some(card_index) = self.trump_swap(game) {
+            let mut game = game.search_copy();
+            game.swap_trumpcard(card_index);
+
+            let mut hint = self.hint(&game);
+            hint.actions.insert(
+                0,
+                Action {
+                    action: 4,
+                    card_index,
+                },
+            );
+            return hint;
+        }
+
+        let mut actions = vec![];
+        let mut reason = HintReason::Wait;
 
         if game.table.player_index == self.player_index {
             if let Some(self_player) = game.table.players.get(self.player_index) {
+                let trump_suit = game.trump_card.suit;
+
+                // Order hand by card
+                let cards: Vec<&Card> = self_player
+                    .cards
+                    .iter()
+                    .sorted_by(|a, b| card_cmp(a, b, trump_suit))
+                    .collect::<Vec<&Card>>();
+
                 match game.state {
                     State::PlayerDefending => {
-                        // Check if any combination yields successfull defense.
+                        // Find the cheapest covering cards and compare
+                        // with the cost of taking the attacking cards.
                         // Otherwise withdraw cards.
-                        if let Some(cards) = self_player
-                            .cards
-                            .iter()
-                            .permutations(game.attacker_cards.len())
-                            .into_iter()
-                            .find(|chunk| {
-                                chunk
-                                    .iter()
-                                    .zip(&game.attacker_cards)
-                                    .all(|(def, atk)| game.resolve_pair(atk, def))
-                            })
-                        {
-                            // Play the cards to the table
-                            for card in cards {
-                                // Find index in hand
-                                if let Some(card_index) = self_player.card_index(card) {
-                                    // Add card to actions
-                                    actions.push(Action {
-                                        action: 1,
-                                        card_index,
-                                    });
+                        match cheapest_defense(&cards, &game.attacker_cards, trump_suit) {
+                            Some(defense) if self.should_defend(game, &defense) => {
+                                reason = HintReason::DefendWithLowestCoveringCards;
+
+                                // Play the cards to the table
+                                for card in defense {
+                                    // Find index in hand
+                                    if let Some(card_index) = self_player.card_index(card) {
+                                        // Add card to actions
+                                        actions.push(Action {
+                                            action: 1,
+                                            card_index,
+                                        });
+                                    }
                                 }
                             }
+                            Some(_) => {
+                                reason = HintReason::TakeToSaveTrumps;
+                            }
+                            None => {
+                                reason = HintReason::TakeCards;
+                            }
                         }
                     }
                     State::PlayerAttacking => {
-                        let trump_suit = game.trump_card.suit;
                         let max_cards = game.table.players[game.next_player()].cards.len();
 
-                        // Order hand by card
-                        let cards: Vec<&Card> = self_player
-                            .cards
-                            .iter()
-                            .sorted_by(|a, b| card_cmp(*a, *b, trump_suit))
-                            .collect::<Vec<&Card>>();
-
-                        // Optimize best pair combination while keeping number of cards below or
+                        // Pick best scoring pairs while keeping number of cards below or
                         // equal to card count in defenders hand.
-                        'a: for i in (2..=max_cards).rev() {
-                            for cards in cards.iter().combinations(i) {
-                                let pairs = find_pairs(
-                                    &cards.into_iter().map(Deref::deref).collect::<Vec<&Card>>(),
-                                );
-                                let num_cards: usize = pairs.iter().map(|v| v.len()).sum();
-                                if num_cards <= max_cards {
-                                    println!("Found pairs of len {num_cards}, {:?}", pairs);
-                                    for pairs in pairs {
-                                        for card in pairs {
-                                            if let Some(card_index) = self_player.card_index(card) {
-                                                actions.push(Action {
-                                                    action: 1,
-                                                    card_index,
-                                                });
-                                            }
-                                        }
-                                    }
-                                    break 'a;
-                                }
+                        for card in pick_pairs(&cards, max_cards, trump_suit) {
+                            if let Some(card_index) = self_player.card_index(card) {
+                                actions.push(Action {
+                                    action: 1,
+                                    card_index,
+                                });
                             }
                         }
 
-                        if actions.len() == 0 && cards.len() > 0 {
+                        if !actions.is_empty() {
+                            reason = HintReason::AttackWithPairs;
+                        } else if let Some(card) = cards.first() {
                             // Add first card from sorted cards
-                            if let Some(card) = cards.get(0) {
-                                if let Some(card_index) = self_player.card_index(card) {
-                                    actions.push(Action {
-                                        action: 1,
-                                        card_index,
-                                    });
-                                }
+                            if let Some(card_index) = self_player.card_index(card) {
+                                reason = HintReason::AttackWithLowestCard;
+                                actions.push(Action {
+                                    action: 1,
+                                    card_index,
+                                });
                             }
                         }
                     }
@@ -123,23 +208,177 @@ impl MoskaAI {
             }
         }
 
-        println!(
-            "{:?}",
-            actions.iter().map(|a| a.card_index).collect::<Vec<usize>>()
-        );
-
         // sort actions in descending order
-        actions.sort_by(|a, b| b.card_index.cmp(&a.card_index));
+        actions.sort_by_key(|a| std::cmp::Reverse(a.card_index));
 
-        println!(
-            "{:?}",
-            actions.iter().map(|a| a.card_index).collect::<Vec<usize>>()
-        );
+        Hint { actions, reason }
+    }
+}
+
+impl MoskaAI {
+    // Finds the trump two in hand if swapping it is worth it.
+    //
+    // The swap gets the trump card from the bottom of the deck,
+    // which is available only until the deck runs out.
+    fn trump_swap(&self, game: &Moska) -> Option<usize> {
+        if game.table.player_index != self.player_index
+            || game.table.deck.count() == 0
+            || !matches!(game.state, State::PlayerAttacking | State::PlayerDefending)
+        {
+            return None;
+        }
+
+        let trump_suit = game.trump_card.suit;
+        let player = game.table.players.get(self.player_index)?;
+        let card_index = player
+            .cards
+            .iter()
+            .position(|card| card.suit == trump_suit && card.rank == Rank::Two)?;
+
+        // Check the card we would receive is better than the two
+        (card_cmp(&game.trump_card, &player.cards[card_index], trump_suit) == Ordering::Greater)
+            .then_some(card_index)
+    }
+
+    // Compares cost of the defending cards with the cost of taking.
+    //
+    // Defending costs the rank of each card spent, with extra for trumps.
+    // Taking costs per card taken, growing from early to late weight
+    // as the deck runs out.
+    fn should_defend(&self, game: &Moska, defense: &[&Card]) -> bool {
+        let trump_suit = game.trump_card.suit;
+        let defend_cost: f32 = defense
+            .iter()
+            .map(|card| self.weights.card_cost(card, trump_suit))
+            .sum();
+
+        // Game phase from 0 at the start to 1 once the deck is empty
+        let full_deck = 52usize.saturating_sub(6 * game.table.players.len()).max(1);
+        let phase = 1.0 - (game.table.deck.count() as f32 / full_deck as f32).min(1.0);
+        let take_cost = game.attacker_cards.len() as f32
+            * (self.weights.take_early
+                + (self.weights.take_late - self.weights.take_early) * phase);
+
+        defend_cost <= take_cost
+    }
+}
+
+// Finds the cheapest cards covering all the attacking cards.
+//
+// Attacking cards are covered from the highest down, each with the
+// cheapest card left that beats it. Expects hand sorted by `card_cmp`.
+pub fn cheapest_defense<'a>(
+    cards: &[&'a Card],
+    attacker_cards: &[Card],
+    trump_suit: Suit,
+) -> Option<Vec<&'a Card>> {
+    let mut used = vec![false; cards.len()];
+    let mut defense = vec![];
+
+    for atk in attacker_cards
+        .iter()
+        .sorted_by(|a, b| card_cmp(b, a, trump_suit))
+    {
+        let index =
+            (0..cards.len()).find(|&i| !used[i] && card_beats(atk, cards[i], trump_suit))?;
+        used[index] = true;
+        defense.push(cards[index]);
+    }
+
+    Some(defense)
+}
+
+// Score for getting rid of a card when attacking.
+// Dumping low cards pays off, high cards and trumps are worth keeping.
+fn dump_score(card: &Card, trump_suit: Suit) -> i32 {
+    const DUMP_BONUS: i32 = 20;
 
-        actions
+    let rank = card_rank_order(card.rank) as i32;
+    if card.suit == trump_suit {
+        -rank
+    } else {
+        DUMP_BONUS - rank
     }
 }
 
+// Picks paired cards to attack with.
+//
+// Cards are grouped by rank and each rank contributes either none
+// or two or more of its cheapest cards. The choice per rank is
+// made with a knapsack over the number of cards the defender can take,
+// so the search stays linear in hand size.
+//
+// Expects cards sorted by `card_cmp`. Returns nothing when no
+// combination of pairs scores positive.
+pub fn pick_pairs<'a>(cards: &[&'a Card], max_cards: usize, trump_suit: Suit) -> Vec<&'a Card> {
+    // Group by rank, cheapest cards first within the rank
+    let groups: Vec<Vec<&Card>> = cards
+        .iter()
+        .copied()
+        .sorted_by_key(|card| card_rank_order(card.rank))
+        .chunk_by(|card| card.rank as u8)
+        .into_iter()
+        .map(|(_, group)| group.collect::<Vec<&Card>>())
+        .filter(|group| group.len() > 1)
+        .collect();
+
+    // best[n] is the best score with n cards played so far,
+    // choices[i][n] the number of cards taken from rank i to get there.
+    let mut best: Vec<Option<i32>> = vec![None; max_cards + 1];
+    best[0] = Some(0);
+    let mut choices: Vec<Vec<usize>> = vec![];
+
+    for group in &groups {
+        let mut prefix = vec![0];
+        for card in group {
+            prefix.push(prefix.last().unwrap() + dump_score(card, trump_suit));
+        }
+
+        let mut next: Vec<Option<i32>> = vec![None; max_cards + 1];
+        let mut choice = vec![0; max_cards + 1];
+        for (n, score) in best.iter().enumerate() {
+            let Some(score) = score else {
+                continue;
+            };
+            for k in std::iter::once(0).chain(2..=group.len()) {
+                if n + k > max_cards {
+                    break;
+                }
+                let score = score + prefix[k];
+                if next[n + k].is_none_or(|best| score > best) {
+                    next[n + k] = Some(score);
+                    choice[n + k] = k;
+                }
+            }
+        }
+
+        best = next;
+        choices.push(choice);
+    }
+
+    // Highest score, preferring more cards on ties
+    let Some((score, mut n)) = best
+        .iter()
+        .enumerate()
+        .filter_map(|(n, score)| score.map(|score| (score, n)))
+        .filter(|(_, n)| *n > 1)
+        .max()
+    else {
+        return vec![];
+    };
+    if score <= 0 {
+        return vec![];
+    }
+
+    let mut picked = vec![];
+    for (group, choice) in groups.iter().zip(&choices).rev() {
+        let k = choice[n];
+        picked.extend_from_slice(&group[..k]);
+        n -= k;
+    }
+    picked
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -163,7 +402,7 @@ mod tests {
             println!("iteration {iteration}");
 
             let i = game.table.player_index;
-            println!("");
+            println!();
             println!("hand {:?}", game.table.players[i].cards);
 
             bots[i].get_actions(&game).into_iter().for_each(|action| {
@@ -173,17 +412,380 @@ mod tests {
             println!("def {:?}", game.defender_cards);
             println!("hand2 {:?}", game.table.players[i].cards);
 
-            assert_eq!(
+            assert!(
                 game.valid()
-                    || (game.state == State::PlayerAttacking && game.attacker_cards.is_empty()),
-                true
+                    || (game.state == State::PlayerAttacking && game.attacker_cards.is_empty())
             );
             game.player_action(3, 0);
 
-            println!("");
+            println!();
             if game.state == State::GameOver {
                 break;
             }
         }
     }
+
+    #[test]
+    fn test_hint() {
+        let mut game = Moska::new(2);
+        game.new_round();
+        game.trump_card = Card::new(Suit::Spades, Rank::Ace);
+
+        // not the player's turn
+        let hint = game.hint(1);
+        assert_eq!(hint.reason, HintReason::Wait);
+        assert!(hint.actions.is_empty());
+
+        // pair of sevens should be played together
+        game.table.players[0].cards = vec![
+            Card::new(Suit::Hearts, Rank::Seven),
+            Card::new(Suit::Clubs, Rank::King),
+            Card::new(Suit::Diamonds, Rank::Seven),
+        ];
+        let hint = game.hint(0);
+        assert_eq!(hint.reason, HintReason::AttackWithPairs);
+        assert_eq!(
+            hint.actions,
+            vec![
+                Action {
+                    action: 1,
+                    card_index: 2
+                },
+                Action {
+                    action: 1,
+                    card_index: 0
+                },
+            ]
+        );
+
+        // no pairs, lowest card goes first
+        game.table.players[0].cards = vec![
+            Card::new(Suit::Spades, Rank::Three),
+            Card::new(Suit::Clubs, Rank::King),
+            Card::new(Suit::Diamonds, Rank::Four),
+        ];
+        let hint = game.hint(0);
+        assert_eq!(hint.reason, HintReason::AttackWithLowestCard);
+        assert_eq!(hint.actions.len(), 1);
+        assert_ne!(hint.actions[0].card_index, 0);
+
+        // defend with the lowest covering card
+        game.player_action(1, 2);
+        game.player_action(3, 0);
+        assert_eq!(game.state, State::PlayerDefending);
+        game.table.players[1].cards = vec![
+            Card::new(Suit::Diamonds, Rank::Ace),
+            Card::new(Suit::Spades, Rank::Three),
+            Card::new(Suit::Diamonds, Rank::Six),
+        ];
+        let hint = game.hint(1);
+        assert_eq!(hint.reason, HintReason::DefendWithLowestCoveringCards);
+        assert_eq!(
+            hint.actions,
+            vec![Action {
+                action: 1,
+                card_index: 2
+            }]
+        );
+
+        // nothing covers, take the cards
+        game.table.players[1].cards = vec![
+            Card::new(Suit::Diamonds, Rank::Two),
+            Card::new(Suit::Hearts, Rank::Ace),
+        ];
+        let hint = game.hint(1);
+        assert_eq!(hint.reason, HintReason::TakeCards);
+        assert!(hint.actions.is_empty());
+    }
+
+    #[test]
+    fn test_pick_pairs() {
+        let trump = Suit::Spades;
+        let hand = [
+            Card::new(Suit::Clubs, Rank::Three),
+            Card::new(Suit::Hearts, Rank::Three),
+            Card::new(Suit::Spades, Rank::Ace),
+            Card::new(Suit::Spades, Rank::King),
+            Card::new(Suit::Hearts, Rank::Ace),
+            Card::new(Suit::Diamonds, Rank::Nine),
+            Card::new(Suit::Clubs, Rank::Nine),
+            Card::new(Suit::Spades, Rank::Nine),
+        ];
+        let cards: Vec<&Card> = hand
+            .iter()
+            .sorted_by(|a, b| card_cmp(a, b, trump))
+            .collect();
+
+        // low pairs go, trump nine stays in hand
+        let picked = pick_pairs(&cards, 6, trump);
+        assert_eq!(picked.len(), 4);
+        assert!(picked.iter().all(|card| card.suit != trump));
+        assert!(picked
+            .iter()
+            .all(|card| card.rank == Rank::Three || card.rank == Rank::Nine));
+
+        // defender can only take three cards, lowest pair goes
+        let picked = pick_pairs(&cards, 3, trump);
+        assert_eq!(picked.len(), 2);
+        assert!(picked.iter().all(|card| card.rank == Rank::Three));
+
+        // not enough room for a pair
+        assert!(pick_pairs(&cards, 1, trump).is_empty());
+
+        // pair of high trumps is not worth playing
+        let hand = [
+            Card::new(Suit::Spades, Rank::Ace),
+            Card::new(Suit::Spades, Rank::King),
+            Card::new(Suit::Hearts, Rank::King),
+        ];
+        let cards: Vec<&Card> = hand.iter().collect();
+        assert!(pick_pairs(&cards, 6, trump).is_empty());
+    }
+
+    #[test]
+    fn test_large_hand() {
+        let mut game = Moska::new(2);
+        game.new_round();
+        game.trump_card = Card::new(Suit::Spades, Rank::Two);
+
+        // give player 0 every card of the first two suits and pair them up
+        let hand: Vec<Card> = [Suit::Clubs, Suit::Hearts]
+            .into_iter()
+            .flat_map(|suit| {
+                (1..=13).map(move |rank| Card::new(suit, Rank::try_from(rank).unwrap()))
+            })
+            .collect();
+        game.table.players[0].cards = hand;
+        game.table.players[1].cards = vec![Card::new(Suit::Diamonds, Rank::Two); 20];
+
+        let hint = game.hint(0);
+        assert_eq!(hint.reason, HintReason::AttackWithPairs);
+        assert_eq!(hint.actions.len(), 20);
+
+        for action in hint.actions {
+            game.player_action(action.action, action.card_index);
+        }
+        assert!(game.valid());
+    }
+
+    #[test]
+    fn test_swap_trumpcard() {
+        let mut game = Moska::new(2);
+        game.new_round();
+
+        let trump_card = Card::new(Suit::Spades, Rank::Ace);
+        let two = Card::new(Suit::Spades, Rank::Two);
+        game.trump_card = trump_card;
+        game.table.deck.replace_last(trump_card);
+        game.table.players[0].cards = vec![
+            Card::new(Suit::Hearts, Rank::Five),
+            two,
+            Card::new(Suit::Clubs, Rank::Nine),
+        ];
+
+        // bot holding the trump two swaps it before playing
+        let actions = MoskaAI::new(0).get_actions(&game);
+        assert_eq!(
+            actions[0],
+            Action {
+                action: 4,
+                card_index: 1
+            }
+        );
+        assert_eq!(actions.len(), 2);
+
+        for action in actions {
+            assert!(game.player_action(action.action, action.card_index) || action.action == 1);
+        }
+        assert!(game.table.players[0].cards.contains(&trump_card));
+        assert!(!game.table.players[0].cards.contains(&two));
+        assert_eq!(game.trump_card, two);
+        assert_eq!(
+            game.attacker_cards,
+            vec![Card::new(Suit::Hearts, Rank::Five)]
+        );
+
+        // swapped trump card is not attacked with
+        assert!(game.valid());
+
+        // nothing to swap for once the deck is empty
+        let mut game = Moska::new(2);
+        game.new_round();
+        game.trump_card = trump_card;
+        game.table.players[0].cards = vec![two, Card::new(Suit::Clubs, Rank::Nine)];
+        while game.table.deck.pop().is_some() {}
+
+        let actions = MoskaAI::new(0).get_actions(&game);
+        assert!(actions.iter().all(|action| action.action != 4));
+
+        // defending bot swaps too
+        let mut game = Moska::new(2);
+        game.new_round();
+        game.trump_card = trump_card;
+        game.table.deck.replace_last(trump_card);
+        game.player_action(1, 0);
+        game.player_action(3, 0);
+        assert_eq!(game.state, State::PlayerDefending);
+        game.table.players[1].cards.push(two);
+
+        let actions = MoskaAI::new(1).get_actions(&game);
+        assert_eq!(actions[0].action, 4);
+    }
+
+    // Sets up player 1 defending against the attacking cards
+    fn defending(attacker_cards: Vec<Card>, hand: Vec<Card>, deck_count: usize) -> Moska {
+        let mut game = Moska::new(2);
+        game.new_round();
+        game.trump_card = Card::new(Suit::Spades, Rank::Two);
+        while game.table.deck.count() > deck_count {
+            game.table.deck.pop();
+        }
+
+        game.table.player_index = 1;
+        game.state = State::PlayerDefending;
+        game.attacker_cards = attacker_cards;
+        game.table.players[1].cards = hand;
+        game
+    }
+
+    #[test]
+    fn test_cheapest_defense() {
+        use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+        let trump = Suit::Spades;
+        let weights = DefenseWeights::default();
+        let cost = |cards: &[&Card]| -> f32 {
+            cards
+                .iter()
+                .map(|card| weights.card_cost(card, trump))
+                .sum()
+        };
+
+        // greedy covering must match the cheapest of all permutations
+        // under the bot's own costs
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut deck: Vec<Card> = [Suit::Clubs, Suit::Hearts, Suit::Diamonds, Suit::Spades]
+            .into_iter()
+            .flat_map(|suit| {
+                (1..=13).map(move |rank| Card::new(suit, Rank::try_from(rank).unwrap()))
+            })
+            .collect();
+
+        for _ in 0..500 {
+            deck.shuffle(&mut rng);
+            let attacker_cards = &deck[..3];
+            let hand: Vec<&Card> = deck[3..9]
+                .iter()
+                .sorted_by(|a, b| card_cmp(a, b, trump))
+                .collect();
+
+            let best = hand
+                .iter()
+                .copied()
+                .permutations(attacker_cards.len())
+                .filter(|chunk| {
+                    chunk
+                        .iter()
+                        .zip(attacker_cards)
+                        .all(|(def, atk)| card_beats(atk, def, trump))
+                })
+                .map(|chunk| cost(&chunk))
+                .min_by(f32::total_cmp);
+
+            let defense = cheapest_defense(&hand, attacker_cards, trump);
+            assert_eq!(defense.as_deref().map(cost), best);
+        }
+
+        // lower trump weights would make the greedy order wrong
+        let weights = DefenseWeights {
+            trump: 0.0,
+            ..weights
+        };
+        let two = Card::new(trump, Rank::Two);
+        let ace = Card::new(Suit::Hearts, Rank::Ace);
+        assert!(weights.card_cost(&two, trump) > weights.card_cost(&ace, trump));
+    }
+
+    #[test]
+    fn test_take_or_defend() {
+        let low_cards = vec![
+            Card::new(Suit::Hearts, Rank::Three),
+            Card::new(Suit::Hearts, Rank::Four),
+        ];
+        let trumps = vec![
+            Card::new(Suit::Spades, Rank::Ace),
+            Card::new(Suit::Spades, Rank::King),
+            Card::new(Suit::Clubs, Rank::Seven),
+        ];
+
+        // early in the game, keep the high trumps and take
+        let game = defending(low_cards.clone(), trumps.clone(), 40);
+        let hint = game.hint(1);
+        assert_eq!(hint.reason, HintReason::TakeToSaveTrumps);
+        assert!(hint.actions.is_empty());
+
+        // deck is running out, taking costs more than the trumps
+        let game = defending(low_cards.clone(), trumps.clone(), 1);
+        let hint = game.hint(1);
+        assert_eq!(hint.reason, HintReason::DefendWithLowestCoveringCards);
+        assert_eq!(hint.actions.len(), 2);
+
+        // cheap covering cards are always played
+        let game = defending(
+            low_cards.clone(),
+            vec![
+                Card::new(Suit::Hearts, Rank::Five),
+                Card::new(Suit::Hearts, Rank::Six),
+                Card::new(Suit::Spades, Rank::Ace),
+            ],
+            40,
+        );
+        let hint = game.hint(1);
+        assert_eq!(hint.reason, HintReason::DefendWithLowestCoveringCards);
+        assert_eq!(
+            hint.actions,
+            vec![
+                Action {
+                    action: 1,
+                    card_index: 1
+                },
+                Action {
+                    action: 1,
+                    card_index: 0
+                }
+            ]
+        );
+
+        // nothing covers
+        let game = defending(
+            low_cards.clone(),
+            vec![Card::new(Suit::Clubs, Rank::Seven)],
+            40,
+        );
+        assert_eq!(game.hint(1).reason, HintReason::TakeCards);
+
+        // weights decide when trumps are worth spending
+        let game = defending(low_cards.clone(), trumps.clone(), 40);
+        let bot = MoskaAI::with_weights(
+            1,
+            DefenseWeights {
+                take_early: 25.0,
+                ..DefenseWeights::default()
+            },
+        );
+        assert_eq!(
+            bot.hint(&game).reason,
+            HintReason::DefendWithLowestCoveringCards
+        );
+
+        let game = defending(low_cards, trumps, 1);
+        let bot = MoskaAI::with_weights(
+            1,
+            DefenseWeights {
+                take_late: 15.0,
+                ..DefenseWeights::default()
+            },
+        );
+        assert_eq!(bot.hint(&game).reason, HintReason::TakeToSaveTrumps);
+    }
 }
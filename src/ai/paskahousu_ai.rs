@@ -0,0 +1,154 @@
+use itertools::Itertools;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{
+    ai::moska::Action,
+    deck::Rank,
+    game::{
+        moska::{card_rank_order, State},
+        paskahousu::{can_play, Paskahousu},
+    },
+};
+
+#[wasm_bindgen]
+pub struct PaskahousuAI {
+    player_index: usize,
+}
+
+#[wasm_bindgen]
+impl PaskahousuAI {
+    #[wasm_bindgen(constructor)]
+    pub fn new(player_index: usize) -> Self {
+        Self { player_index }
+    }
+
+    // Decides the actions for current turn, submitting is left to the caller.
+    //
+    // Plays all cards of the lowest rank that goes, keeping the twos,
+    // tens and aces for when nothing else does. Otherwise tries the deck,
+    // and once the deck is empty picks up the pile.
+    pub fn get_actions(&self, game: &Paskahousu) -> Vec<Action> {
+        if game.table.player_index != self.player_index || game.state != State::PlayerAttacking {
+            return vec![];
+        }
+        let Some(player) = game.table.players.get(self.player_index) else {
+            return vec![];
+        };
+        let top = game.pile.last();
+        let is_special = |rank: Rank| matches!(rank, Rank::Two | Rank::Ten | Rank::Ace);
+
+        let lowest_rank = player
+            .cards
+            .iter()
+            .map(|card| card.rank)
+            .filter(|rank| !is_special(*rank) && can_play(*rank, top))
+            .min_by_key(|rank| card_rank_order(*rank));
+
+        let mut card_indices: Vec<usize> = match lowest_rank {
+            Some(rank) => player
+                .cards
+                .iter()
+                .positions(|card| card.rank == rank)
+                .collect(),
+            None => [Rank::Ten, Rank::Ace, Rank::Two]
+                .into_iter()
+                .filter(|rank| can_play(*rank, top))
+                .find_map(|rank| player.cards.iter().position(|card| card.rank == rank))
+                .into_iter()
+                .collect(),
+        };
+
+        if card_indices.is_empty() {
+            if game.table.deck.count() > 0 && !game.pile.is_empty() {
+                return vec![Action {
+                    action: 4,
+                    card_index: 0,
+                }];
+            }
+            return vec![];
+        }
+
+        // Descending so the indices stay valid while cards are played
+        card_indices.sort_by_key(|index| std::cmp::Reverse(*index));
+        card_indices
+            .into_iter()
+            .map(|card_index| Action {
+                action: 1,
+                card_index,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deck::{Card, Deck, Suit};
+
+    fn game(hand: Vec<Card>, pile: Vec<Card>, deck: Vec<Card>) -> Paskahousu {
+        let mut game = Paskahousu::new(2);
+        game.new_round_with_seed(0);
+        game.table.player_index = 0;
+        game.table.players[0].cards = hand;
+        game.pile = pile;
+        game.table.deck = Deck::from_cards(deck);
+        game
+    }
+
+    fn indices(actions: Vec<Action>) -> Vec<usize> {
+        actions.iter().map(|action| action.card_index).collect()
+    }
+
+    #[test]
+    fn test_get_actions() {
+        let bot = PaskahousuAI::new(0);
+        let hand = vec![
+            Card::new(Suit::Clubs, Rank::Two),
+            Card::new(Suit::Clubs, Rank::Eight),
+            Card::new(Suit::Clubs, Rank::Six),
+            Card::new(Suit::Hearts, Rank::Eight),
+            Card::new(Suit::Clubs, Rank::Ten),
+        ];
+        let deck = vec![Card::new(Suit::Spades, Rank::Ace)];
+
+        // lowest rank that goes, all of it
+        let game = game(hand.clone(), vec![], deck.clone());
+        assert_eq!(indices(bot.get_actions(&game)), vec![2]);
+        let game = self::game(
+            hand.clone(),
+            vec![Card::new(Suit::Spades, Rank::Seven)],
+            vec![],
+        );
+        assert_eq!(indices(bot.get_actions(&game)), vec![3, 1]);
+        assert_eq!(PaskahousuAI::new(1).get_actions(&game), vec![]);
+
+        // burn before spending the two
+        let game = self::game(
+            hand.clone(),
+            vec![Card::new(Suit::Spades, Rank::Nine)],
+            vec![],
+        );
+        assert_eq!(indices(bot.get_actions(&game)), vec![4]);
+
+        let game = self::game(
+            hand.clone(),
+            vec![Card::new(Suit::Spades, Rank::Jack)],
+            vec![],
+        );
+        assert_eq!(indices(bot.get_actions(&game)), vec![0]);
+
+        // nothing goes, try the deck first
+        let hand = vec![Card::new(Suit::Clubs, Rank::Six)];
+        let pile = vec![Card::new(Suit::Spades, Rank::King)];
+        let game = self::game(hand.clone(), pile.clone(), deck);
+        assert_eq!(
+            bot.get_actions(&game),
+            vec![Action {
+                action: 4,
+                card_index: 0
+            }]
+        );
+        let game = self::game(hand, pile, vec![]);
+        assert_eq!(bot.get_actions(&game), vec![]);
+    }
+}
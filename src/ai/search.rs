@@ -0,0 +1,327 @@
+/*
+ * Search bots for any game implementing `Engine`.
+ *
+ * Hidden cards are guessed by determinizing the game for each
+ * playout, so the bots only use what their player can see.
+ * Same seed yields the same choices.
+ */
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    ai::moska::Action,
+    game::{engine::Engine, moska::State},
+    Moska,
+};
+
+// Playouts longer than this are scored as draws
+const MAX_PLAYOUT_STEPS: usize = 2_000;
+
+pub trait SearchBot {
+    // Chooses an action for the player in turn, nothing once the game is over
+    fn choose<G: Engine>(&mut self, game: &G) -> Option<G::Action>;
+}
+
+// Plays random legal actions until the game is over.
+// Returns the outcome, nothing if the game went on too long.
+fn playout<G: Engine>(mut game: G, rng: &mut StdRng) -> Option<Vec<f32>> {
+    for _ in 0..MAX_PLAYOUT_STEPS {
+        let actions = game.legal_actions();
+        if actions.is_empty() {
+            break;
+        }
+        game.apply(&actions[rng.gen_range(0..actions.len())]);
+    }
+    game.outcome()
+}
+
+fn score(outcome: &Option<Vec<f32>>, player_index: usize) -> f32 {
+    outcome
+        .as_ref()
+        .and_then(|outcome| outcome.get(player_index).copied())
+        .unwrap_or(0.5)
+}
+
+// Picks any legal action
+pub struct RandomBot {
+    rng: StdRng,
+}
+
+impl RandomBot {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl SearchBot for RandomBot {
+    fn choose<G: Engine>(&mut self, game: &G) -> Option<G::Action> {
+        let mut actions = game.legal_actions();
+        match actions.len() {
+            0 => None,
+            len => Some(actions.swap_remove(self.rng.gen_range(0..len))),
+        }
+    }
+}
+
+// Scores each legal action by random playouts from it
+pub struct MonteCarloBot {
+    rng: StdRng,
+    playouts: usize,
+}
+
+impl MonteCarloBot {
+    // Bot with the given number of playouts for each legal action
+    pub fn new(playouts: usize, seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            playouts,
+        }
+    }
+}
+
+impl SearchBot for MonteCarloBot {
+    fn choose<G: Engine>(&mut self, game: &G) -> Option<G::Action> {
+        let player_index = game.current_player();
+        let actions = game.legal_actions();
+        if actions.len() <= 1 {
+            return actions.into_iter().next();
+        }
+
+        let mut best = None;
+        let mut best_score = f32::MIN;
+        for action in actions {
+            let mut total = 0.0;
+            for _ in 0..self.playouts {
+                let mut game = game.determinize(player_index, &mut self.rng);
+                game.apply(&action);
+                total += score(&playout(game, &mut self.rng), player_index);
+            }
+            if total > best_score {
+                best_score = total;
+                best = Some(action);
+            }
+        }
+        best
+    }
+}
+
+struct Node<A> {
+    // Action leading here and the player who took it
+    action: Option<A>,
+    player_index: usize,
+    children: Vec<usize>,
+    visits: u32,
+    // Times the node could have been chosen, see `MctsBot`
+    available: u32,
+    score: f32,
+}
+
+// Monte Carlo tree search over determinized games.
+//
+// Each iteration redeals the hidden cards and walks down the single tree
+// using only the actions legal in that deal, so the statistics are shared
+// between all guesses of the hidden cards. A child is scored against the
+// times it was available instead of the visits of its parent.
+pub struct MctsBot {
+    rng: StdRng,
+    iterations: usize,
+    // Weight of exploring rarely visited actions
+    pub exploration: f32,
+}
+
+impl MctsBot {
+    pub fn new(iterations: usize, seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            iterations,
+            exploration: 0.7,
+        }
+    }
+
+    fn ucb<A>(&self, node: &Node<A>) -> f32 {
+        let visits = node.visits.max(1) as f32;
+        node.score / visits
+            + self.exploration * ((node.available.max(1) as f32).ln() / visits).sqrt()
+    }
+}
+
+impl SearchBot for MctsBot {
+    fn choose<G: Engine>(&mut self, game: &G) -> Option<G::Action> {
+        let root_player = game.current_player();
+        let actions = game.legal_actions();
+        if actions.len() <= 1 {
+            return actions.into_iter().next();
+        }
+
+        let mut nodes: Vec<Node<G::Action>> = vec![Node {
+            action: None,
+            player_index: root_player,
+            children: vec![],
+            visits: 0,
+            available: 0,
+            score: 0.0,
+        }];
+
+        for _ in 0..self.iterations {
+            let mut game = game.determinize(root_player, &mut self.rng);
+            let mut path = vec![0];
+            let mut node = 0;
+
+            // Select down the tree until an untried action is found
+            loop {
+                let legal = game.legal_actions();
+                if legal.is_empty() {
+                    break;
+                }
+
+                let children: Vec<usize> = nodes[node]
+                    .children
+                    .iter()
+                    .copied()
+                    .filter(|child| legal.contains(nodes[*child].action.as_ref().unwrap()))
+                    .collect();
+                for child in &children {
+                    nodes[*child].available += 1;
+                }
+
+                let untried: Vec<&G::Action> = legal
+                    .iter()
+                    .filter(|action| {
+                        !children
+                            .iter()
+                            .any(|child| nodes[*child].action.as_ref() == Some(*action))
+                    })
+                    .collect();
+
+                let player_index = game.current_player();
+                if !untried.is_empty() {
+                    let action = untried[self.rng.gen_range(0..untried.len())].clone();
+                    game.apply(&action);
+                    nodes.push(Node {
+                        action: Some(action),
+                        player_index,
+                        children: vec![],
+                        visits: 0,
+                        available: 1,
+                        score: 0.0,
+                    });
+                    let child = nodes.len() - 1;
+                    nodes[node].children.push(child);
+                    path.push(child);
+                    break;
+                }
+
+                node = children
+                    .iter()
+                    .copied()
+                    .max_by(|a, b| self.ucb(&nodes[*a]).total_cmp(&self.ucb(&nodes[*b])))
+                    .unwrap();
+                game.apply(nodes[node].action.as_ref().unwrap());
+                path.push(node);
+            }
+
+            // Score the rest by a random playout
+            let outcome = playout(game, &mut self.rng);
+            for index in path {
+                let node = &mut nodes[index];
+                node.visits += 1;
+                node.score += score(&outcome, node.player_index);
+            }
+        }
+
+        nodes[0]
+            .children
+            .iter()
+            .max_by_key(|child| nodes[**child].visits)
+            .and_then(|child| nodes[*child].action.clone())
+    }
+}
+
+// Actions for the rest of the Moska turn, submitting is left to the caller
+// as with `MoskaAI::get_actions`. Taking the attack returns no actions.
+pub fn moska_turn(game: &Moska, bot: &mut impl SearchBot) -> Vec<Action> {
+    let player_index = game.table.player_index;
+    let mut game = game.search_copy();
+    let mut actions = vec![];
+
+    while game.table.player_index == player_index && game.state != State::GameOver {
+        match bot.choose(&game) {
+            Some(action) if action.action != 3 => {
+                game.apply(&action);
+                actions.push(action);
+            }
+            _ => break,
+        }
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        durak::{Durak, DurakVariant},
+        paskahousu::Paskahousu,
+        testing::play_out,
+    };
+
+    // Plays the game with the bot in seat 0 and random players elsewhere.
+    // Returns the outcome of seat 0.
+    fn play<G: Engine>(mut game: G, bot: &mut impl SearchBot, seed: u64) -> f32 {
+        let mut random = RandomBot::new(seed);
+        assert!(play_out(&mut game, |game| {
+            let action = match game.current_player() {
+                0 => bot.choose(game),
+                _ => random.choose(game),
+            };
+            assert!(game.apply(&action.unwrap()));
+        }));
+        game.outcome().unwrap()[0]
+    }
+
+    #[test]
+    fn test_random_bot() {
+        let mut game = Paskahousu::new(3);
+        game.new_round_with_seed(1);
+        let choices = |seed| {
+            let mut bot = RandomBot::new(seed);
+            (0..10).map(|_| bot.choose(&game)).collect::<Vec<_>>()
+        };
+        assert_eq!(choices(3), choices(3));
+
+        let mut bot = RandomBot::new(0);
+        assert!(play(game, &mut bot, 1) >= 0.0);
+    }
+
+    #[test]
+    fn test_search_bots_beat_random() {
+        let mut mc_wins = 0.0;
+        let mut mcts_wins = 0.0;
+        for seed in 0..6 {
+            let mut game = Durak::new(2, DurakVariant::Podkidnoy).unwrap();
+            game.new_round_with_seed(seed);
+            mc_wins += play(game.clone(), &mut MonteCarloBot::new(4, seed), seed);
+            mcts_wins += play(game, &mut MctsBot::new(40, seed), seed);
+        }
+        assert!(mc_wins >= 5.0);
+        assert!(mcts_wins >= 5.0);
+    }
+
+    #[test]
+    fn test_moska_turn() {
+        let mut game = Moska::new(2);
+        game.new_round_with_seed(4);
+        let mut bot = MctsBot::new(50, 1);
+
+        assert!(play_out(&mut game, |game| {
+            let player_index = game.table.player_index;
+            for action in moska_turn(game, &mut bot) {
+                assert!(game.apply(&action));
+                assert_eq!(game.table.player_index, player_index);
+            }
+            game.player_action(3, 0);
+        }));
+    }
+}
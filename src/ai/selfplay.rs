@@ -0,0 +1,277 @@
+/*
+ * Self-play training data for learned evaluation
+ */
+
+use std::io::{self, Write};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    ai::features::{self, NUM_CARDS, NUM_FEATURES},
+    card_set::CardSet,
+    game::moska::State,
+    Moska, MoskaAI,
+};
+
+// Bots play until this many actions before the game is given up
+const MAX_STEPS: usize = 10_000;
+
+// Decision point of a player along with the chosen action
+// and the outcome of the game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub game: usize,
+    pub player: usize,
+    pub features: Vec<f32>,
+    // Cards put on the table
+    pub played: CardSet,
+    // Attacking cards were taken
+    pub took: bool,
+    // Trump card was swapped
+    pub swapped: bool,
+    // 1 when the player got rid of their cards, 0 when they lost
+    pub outcome: f32,
+}
+
+impl Sample {
+    // Row values in the order of `columns`
+    pub fn row(&self) -> Vec<f32> {
+        let mut row = Vec::with_capacity(num_columns());
+        row.push(self.game as f32);
+        row.push(self.player as f32);
+        row.extend(&self.features);
+        features::push_cards(&mut row, self.played);
+        row.push(self.took as u8 as f32);
+        row.push(self.swapped as u8 as f32);
+        row.push(self.outcome);
+        row
+    }
+}
+
+pub fn num_columns() -> usize {
+    2 + NUM_FEATURES + NUM_CARDS + 3
+}
+
+// Column names of sample rows
+pub fn columns() -> Vec<String> {
+    let mut columns = vec!["game".to_string(), "player".to_string()];
+    for mask in ["hand", "table", "discard", "known"] {
+        columns.extend((0..NUM_CARDS).map(|i| format!("{mask}_{i}")));
+    }
+    columns.extend((0..4).map(|i| format!("trump_{i}")));
+    columns.push("deck_count".to_string());
+    columns.extend((0..NUM_CARDS).map(|i| format!("play_{i}")));
+    columns.extend(["take", "swap", "outcome"].map(String::from));
+    columns
+}
+
+// Generates games played by `MoskaAI` bots.
+// Same seed yields the same games.
+pub struct SelfPlay {
+    players: u8,
+    rng: StdRng,
+    games: usize,
+}
+
+impl SelfPlay {
+    pub fn new(players: u8, seed: u64) -> Self {
+        Self {
+            players,
+            rng: StdRng::seed_from_u64(seed),
+            games: 0,
+        }
+    }
+
+    // Plays the given number of games and collects their samples
+    pub fn generate(&mut self, games: usize) -> Vec<Sample> {
+        (0..games).flat_map(|_| self.play_game()).collect()
+    }
+
+    // Plays a single game.
+    // Returns no samples if the game did not finish.
+    pub fn play_game(&mut self) -> Vec<Sample> {
+        let game_index = self.games;
+        self.games += 1;
+
+        let mut game = Moska::new(self.players);
+        game.new_round_with_seed(self.rng.gen());
+
+        let bots: Vec<MoskaAI> = (0..self.players as usize).map(MoskaAI::new).collect();
+
+        // Cards each player is known to hold
+        let mut known = vec![CardSet::EMPTY; self.players as usize];
+        let mut samples = vec![];
+
+        for _ in 0..MAX_STEPS {
+            if game.state == State::GameOver {
+                break;
+            }
+
+            let player = game.table.player_index;
+            let known_opponent = known
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| *index != player)
+                .fold(CardSet::EMPTY, |set, (_, cards)| set | *cards);
+
+            let mut sample = Sample {
+                game: game_index,
+                player,
+                features: features::encode(&game, player, known_opponent),
+                played: CardSet::EMPTY,
+                took: false,
+                swapped: false,
+                outcome: 0.0,
+            };
+
+            for action in bots[player].get_actions(&game) {
+                match action.action {
+                    1 => {
+                        if let Some(card) = game.table.players[player].cards.get(action.card_index)
+                        {
+                            sample.played.insert(*card);
+                        }
+                    }
+                    4 => {
+                        // Everyone sees the trump card going to the player
+                        sample.swapped = true;
+                        known[player].insert(game.trump_card);
+                    }
+                    _ => {}
+                }
+                game.player_action(action.action, action.card_index);
+            }
+
+            // Taking the attacking cards reveals them
+            if game.state == State::PlayerDefending && game.defender_cards.is_empty() {
+                sample.took = true;
+                known[player].extend(game.attacker_cards.iter().copied());
+            }
+            known[player] -= sample.played;
+
+            game.player_action(3, 0);
+            samples.push(sample);
+        }
+
+        if game.state != State::GameOver {
+            return vec![];
+        }
+
+        for sample in samples.iter_mut() {
+            sample.outcome = game.table.players[sample.player].cards.is_empty() as u8 as f32;
+        }
+        samples
+    }
+}
+
+// Writes samples as CSV with a header row
+pub fn write_csv<W: Write>(samples: &[Sample], mut writer: W) -> io::Result<()> {
+    writeln!(writer, "{}", columns().join(","))?;
+    for sample in samples {
+        let row: Vec<String> = sample.row().iter().map(f32::to_string).collect();
+        writeln!(writer, "{}", row.join(","))?;
+    }
+    Ok(())
+}
+
+// Writes samples as a 2-dimensional float32 NPY array
+pub fn write_npy<W: Write>(samples: &[Sample], mut writer: W) -> io::Result<()> {
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        samples.len(),
+        num_columns()
+    );
+
+    // Pad header so data starts at a multiple of 64 bytes
+    let unpadded = 10 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    writer.write_all(b"\x93NUMPY\x01\x00")?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+
+    for sample in samples {
+        for value in sample.row() {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reproducible() {
+        let a = SelfPlay::new(3, 42).generate(3);
+        let b = SelfPlay::new(3, 42).generate(3);
+        let c = SelfPlay::new(3, 43).generate(3);
+
+        assert!(!a.is_empty());
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn test_samples() {
+        let samples = SelfPlay::new(2, 1).generate(5);
+
+        for sample in &samples {
+            assert_eq!(sample.features.len(), NUM_FEATURES);
+            assert_eq!(sample.row().len(), num_columns());
+
+            // played cards come from the hand, unless swapped in
+            let hand = card_set_of(&sample.features[..NUM_CARDS]);
+            if !sample.swapped {
+                assert!(sample.played.is_subset(&hand));
+            }
+
+            // exactly one trump suit
+            let trump = &sample.features[4 * NUM_CARDS..4 * NUM_CARDS + 4];
+            assert_eq!(trump.iter().sum::<f32>(), 1.0);
+        }
+
+        // one loser per game
+        for game in 0..5 {
+            let losers: Vec<usize> = samples
+                .iter()
+                .filter(|s| s.game == game && s.outcome == 0.0)
+                .map(|s| s.player)
+                .collect();
+            assert!(losers.windows(2).all(|w| w[0] == w[1]));
+        }
+    }
+
+    #[test]
+    fn test_writers() {
+        let samples = SelfPlay::new(2, 7).generate(1);
+
+        let mut csv = vec![];
+        write_csv(&samples, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), samples.len() + 1);
+        assert!(csv.starts_with("game,player,hand_0"));
+
+        let mut npy = vec![];
+        write_npy(&samples, &mut npy).unwrap();
+        assert_eq!(&npy[..6], b"\x93NUMPY");
+        let header_len = u16::from_le_bytes([npy[8], npy[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        assert_eq!(
+            npy.len(),
+            10 + header_len + samples.len() * num_columns() * 4
+        );
+    }
+
+    fn card_set_of(values: &[f32]) -> CardSet {
+        CardSet::from_mask(
+            values
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| **v == 1.0)
+                .fold(0, |mask, (i, _)| mask | 1 << i),
+        )
+    }
+}
@@ -0,0 +1,306 @@
+/*
+ * Batch simulation of bot games.
+ *
+ * Each game gets its own seeds from the simulation seed and its index,
+ * so games can be played in any order. With the `parallel` feature games
+ * are spread over all cores, giving the same results as a single thread.
+ */
+
+use std::fmt::{self, Display};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    ai::{
+        eval::{EvalAI, EvalModel},
+        moska::Action,
+        BotStrategy,
+    },
+    game::moska::State,
+    Moska,
+};
+
+// Games taking more actions than this are given up
+const MAX_STEPS: usize = 10_000;
+
+// Bot playing a seat of the simulation
+#[derive(Clone, Debug, PartialEq)]
+pub enum SeatBot {
+    Strategy(BotStrategy),
+    // Evaluation bot with trained weights
+    Eval(EvalModel),
+}
+
+impl SeatBot {
+    fn get_actions(&self, game: &Moska, seed: u64) -> Vec<Action> {
+        match self {
+            SeatBot::Strategy(strategy) => strategy.get_actions_with_seed(game, seed),
+            SeatBot::Eval(model) => {
+                EvalAI::new(game.table.player_index, model.clone()).get_actions(game)
+            }
+        }
+    }
+}
+
+impl From<BotStrategy> for SeatBot {
+    fn from(strategy: BotStrategy) -> Self {
+        SeatBot::Strategy(strategy)
+    }
+}
+
+impl Display for SeatBot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SeatBot::Strategy(strategy) => write!(f, "{strategy}"),
+            SeatBot::Eval(_) => write!(f, "eval"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulationConfig {
+    pub players: u8,
+    pub games: usize,
+    // Bots of the seats, the last one repeats
+    pub bots: Vec<SeatBot>,
+    pub seed: u64,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            players: 2,
+            games: 1_000,
+            bots: vec![BotStrategy::Heuristic.into()],
+            seed: 0,
+        }
+    }
+}
+
+impl SimulationConfig {
+    // Bot of the seat, heuristic when none are given
+    pub fn bot(&self, player_index: usize) -> SeatBot {
+        self.bots
+            .get(player_index)
+            .or(self.bots.last())
+            .cloned()
+            .unwrap_or(BotStrategy::Heuristic.into())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameResult {
+    // Game ended before it was given up
+    pub finished: bool,
+    // Seat left holding the cards
+    pub loser: Option<usize>,
+    pub turns: usize,
+    // Times each seat took the attacking cards instead of defending
+    pub withdrawals: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SimulationStats {
+    pub games: usize,
+    // Games given up before anyone lost
+    pub unfinished: usize,
+    // Finished games each seat got rid of its cards in
+    pub wins: Vec<usize>,
+    pub turns: usize,
+    pub withdrawals: Vec<usize>,
+}
+
+impl SimulationStats {
+    fn new(players: u8) -> Self {
+        Self {
+            wins: vec![0; players as usize],
+            withdrawals: vec![0; players as usize],
+            ..Self::default()
+        }
+    }
+
+    fn add(&mut self, result: &GameResult) {
+        self.games += 1;
+        self.turns += result.turns;
+        match result.finished {
+            true => {
+                for (index, wins) in self.wins.iter_mut().enumerate() {
+                    *wins += (Some(index) != result.loser) as usize;
+                }
+            }
+            false => self.unfinished += 1,
+        }
+        for (total, count) in self.withdrawals.iter_mut().zip(&result.withdrawals) {
+            *total += count;
+        }
+    }
+
+    // Share of the finished games the seat won
+    pub fn win_rate(&self, player_index: usize) -> f32 {
+        let finished = self.games - self.unfinished;
+        match finished {
+            0 => 0.0,
+            _ => self.wins[player_index] as f32 / finished as f32,
+        }
+    }
+
+    pub fn mean_turns(&self) -> f32 {
+        match self.games {
+            0 => 0.0,
+            games => self.turns as f32 / games as f32,
+        }
+    }
+
+    // Mean withdrawals of the seat per game
+    pub fn mean_withdrawals(&self, player_index: usize) -> f32 {
+        match self.games {
+            0 => 0.0,
+            games => self.withdrawals[player_index] as f32 / games as f32,
+        }
+    }
+}
+
+// Plays the game with the given index of the simulation
+pub fn play_game(config: &SimulationConfig, game_index: usize) -> GameResult {
+    let mut rng = StdRng::seed_from_u64(
+        config.seed ^ (game_index as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15),
+    );
+    let mut game = Moska::new(config.players);
+    game.new_round_with_seed(rng.gen());
+
+    let mut withdrawals = vec![0; config.players as usize];
+    for _ in 0..MAX_STEPS {
+        if game.state == State::GameOver {
+            break;
+        }
+
+        let player_index = game.table.player_index;
+        for action in config.bot(player_index).get_actions(&game, rng.gen()) {
+            game.player_action(action.action, action.card_index);
+        }
+        if game.state == State::PlayerDefending && game.defender_cards.is_empty() {
+            withdrawals[player_index] += 1;
+        }
+        game.player_action(3, 0);
+    }
+
+    GameResult {
+        finished: game.state == State::GameOver,
+        loser: game.loser(),
+        turns: game.table.turn,
+        withdrawals,
+    }
+}
+
+// Plays the games of the simulation one after another
+pub fn simulate_sequential(config: &SimulationConfig) -> SimulationStats {
+    let results = (0..config.games).map(|index| play_game(config, index));
+    aggregate(config, results)
+}
+
+// Plays the games of the simulation on all cores
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+pub fn simulate(config: &SimulationConfig) -> SimulationStats {
+    use rayon::prelude::*;
+
+    let results: Vec<GameResult> = (0..config.games)
+        .into_par_iter()
+        .map(|index| play_game(config, index))
+        .collect();
+    aggregate(config, results)
+}
+
+// Plays the games of the simulation, on a single thread without
+// the `parallel` feature
+#[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+pub fn simulate(config: &SimulationConfig) -> SimulationStats {
+    simulate_sequential(config)
+}
+
+fn aggregate(
+    config: &SimulationConfig,
+    results: impl IntoIterator<Item = GameResult>,
+) -> SimulationStats {
+    let mut stats = SimulationStats::new(config.players);
+    for result in results {
+        stats.add(&result);
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(seed: u64) -> SimulationConfig {
+        SimulationConfig {
+            players: 3,
+            games: 40,
+            bots: vec![BotStrategy::Heuristic.into(), BotStrategy::Random.into()],
+            seed,
+        }
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let stats = simulate(&config(1));
+        assert_eq!(stats, simulate_sequential(&config(1)));
+        assert_eq!(stats, simulate(&config(1)));
+        assert_ne!(stats, simulate(&config(2)));
+
+        // games do not depend on each other
+        let config = config(1);
+        assert_eq!(play_game(&config, 7), play_game(&config, 7));
+    }
+
+    #[test]
+    fn test_stats() {
+        let stats = simulate(&config(3));
+        assert_eq!(stats.games, 40);
+        assert_eq!(stats.wins.len(), 3);
+
+        // finished games have a single loser
+        let finished = stats.games - stats.unfinished;
+        assert!(finished > 0);
+        assert_eq!(stats.wins.iter().sum::<usize>(), 2 * finished);
+        let win_rates: f32 = (0..3).map(|index| stats.win_rate(index)).sum();
+        assert!((win_rates - 2.0).abs() < 1e-4);
+
+        // the heuristic bot beats random players
+        assert!(stats.win_rate(0) > stats.win_rate(1));
+        assert!(stats.mean_turns() > 1.0);
+        assert!(stats.withdrawals.iter().sum::<usize>() > 0);
+
+        assert_eq!(SimulationStats::new(2).win_rate(0), 0.0);
+        assert_eq!(SimulationStats::new(2).mean_turns(), 0.0);
+    }
+
+    #[test]
+    fn test_bots() {
+        let eval = SeatBot::Eval(EvalModel::new());
+        let config = SimulationConfig {
+            games: 4,
+            bots: vec![eval.clone(), BotStrategy::Random.into()],
+            ..config(4)
+        };
+        assert_eq!(config.bot(0), eval);
+        assert_eq!(config.bot(2), BotStrategy::Random.into());
+        assert_eq!(simulate(&config).games, 4);
+
+        let config = SimulationConfig {
+            bots: vec![],
+            ..config
+        };
+        assert_eq!(config.bot(1), BotStrategy::Heuristic.into());
+
+        for strategy in [
+            BotStrategy::Heuristic,
+            BotStrategy::Random,
+            BotStrategy::MonteCarlo,
+            BotStrategy::Mcts,
+        ] {
+            assert_eq!(strategy.to_string().parse(), Ok(strategy));
+        }
+        assert!("eval".parse::<BotStrategy>().is_err());
+    }
+}
@@ -0,0 +1,19 @@
+// Hosts Moska rooms over WebSocket.
+//
+// Usage: moska-server [address]
+
+use std::env;
+
+use moska::server::serve;
+use tokio::net::TcpListener;
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let addr = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:9001".to_string());
+
+    let listener = TcpListener::bind(&addr).await?;
+    println!("Listening on ws://{}", listener.local_addr()?);
+    serve(listener, None).await
+}
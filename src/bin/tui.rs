@@ -0,0 +1,544 @@
+/*
+ * Full-screen terminal Moska against bots
+ */
+
+use std::{
+    collections::{BTreeSet, VecDeque},
+    env, io,
+    time::{Duration, Instant},
+};
+
+use itertools::Itertools;
+use moska::{game::moska::State, Action, Card, Moska, MoskaAI, Suit};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph, Wrap},
+    DefaultTerminal, Frame,
+};
+
+const USAGE: &str = "usage: moska-tui [players] [seed]";
+
+const KEYS: &str = "←→ select  space mark  enter play  backspace take back  s submit  w swap  h hint  ↑↓ log  q quit";
+
+// Seat played by the human
+const HUMAN: usize = 0;
+
+// Pause between bot actions
+const BOT_DELAY: Duration = Duration::from_millis(600);
+
+struct App {
+    game: Moska,
+    bots: Vec<MoskaAI>,
+
+    // Hand cursor and cards marked for playing
+    cursor: usize,
+    marked: BTreeSet<usize>,
+
+    log: Vec<String>,
+    // Lines scrolled up from the end of the log
+    log_scroll: usize,
+    message: String,
+
+    // Remaining actions of the animated bot turn
+    bot_actions: VecDeque<Action>,
+    bot_played: Vec<Card>,
+    next_step: Instant,
+
+    quit: bool,
+}
+
+impl App {
+    fn new(players: u8, seed: Option<u64>) -> Self {
+        let mut game = Moska::new(players);
+        match seed {
+            Some(seed) => game.new_round_with_seed(seed),
+            None => game.new_round(),
+        }
+
+        Self {
+            game,
+            bots: (0..players as usize).map(MoskaAI::new).collect(),
+            cursor: 0,
+            marked: BTreeSet::new(),
+            log: vec![format!("Moska with {players} players, you start")],
+            log_scroll: 0,
+            message: String::new(),
+            bot_actions: VecDeque::new(),
+            bot_played: vec![],
+            next_step: Instant::now(),
+            quit: false,
+        }
+    }
+
+    fn hand(&self) -> Vec<Card> {
+        self.game.player_cards(HUMAN)
+    }
+
+    fn human_turn(&self) -> bool {
+        self.game.state != State::GameOver && self.game.table.player_index == HUMAN
+    }
+
+    fn push_log(&mut self, line: String) {
+        self.log.push(line);
+        self.log_scroll = 0;
+    }
+
+    // Keeps the cursor and marks within the hand
+    fn clamp_hand(&mut self) {
+        let len = self.hand().len();
+        self.cursor = self.cursor.min(len.saturating_sub(1));
+        self.marked.retain(|index| *index < len);
+    }
+
+    fn handle_key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Up => self.log_scroll = (self.log_scroll + 1).min(self.log.len()),
+            KeyCode::Down => self.log_scroll = self.log_scroll.saturating_sub(1),
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => {
+                self.cursor = (self.cursor + 1).min(self.hand().len().saturating_sub(1))
+            }
+            _ if !self.human_turn() => self.message = "Wait for your turn".to_string(),
+            KeyCode::Char(' ') => self.toggle_mark(),
+            KeyCode::Enter => self.play(),
+            KeyCode::Backspace => self.take_back(),
+            KeyCode::Char('s') => self.submit(),
+            KeyCode::Char('w') => self.swap(),
+            KeyCode::Char('h') => self.hint(),
+            _ => {}
+        }
+    }
+
+    fn toggle_mark(&mut self) {
+        if !self.marked.remove(&self.cursor) && self.cursor < self.hand().len() {
+            self.marked.insert(self.cursor);
+        }
+    }
+
+    // Puts the marked cards, or the card under cursor, on the table
+    fn play(&mut self) {
+        let mut indexes: Vec<usize> = self.marked.iter().copied().collect();
+        if indexes.is_empty() && self.cursor < self.hand().len() {
+            indexes.push(self.cursor);
+        }
+
+        let hand = self.hand();
+        // Remove from the back so the other indexes stay put
+        for index in indexes.iter().rev() {
+            self.game.player_action(1, *index);
+        }
+        self.message = format!(
+            "Put {} on the table",
+            indexes.iter().map(|index| hand[*index]).join(" ")
+        );
+
+        self.marked.clear();
+        self.clamp_hand();
+    }
+
+    // Takes the last placed card back to hand
+    fn take_back(&mut self) {
+        let table = match self.game.state {
+            State::PlayerDefending => &self.game.defender_cards,
+            _ => &self.game.attacker_cards,
+        };
+        match table.len().checked_sub(1) {
+            Some(index) => {
+                self.message = format!("Took back {}", table[index]);
+                self.game.player_action(2, index);
+            }
+            None => self.message = "No cards of yours on the table".to_string(),
+        }
+    }
+
+    fn submit(&mut self) {
+        let defending = self.game.state == State::PlayerDefending;
+        let took = defending && self.game.defender_cards.is_empty();
+
+        if !took && !self.game.valid() {
+            self.message = if defending {
+                "Defending cards must beat every attacking card".to_string()
+            } else if self.game.attacker_cards.is_empty() {
+                "Play at least one card to attack".to_string()
+            } else {
+                "Attack with a single card or pairs, at most as many as the defender holds"
+                    .to_string()
+            };
+            return;
+        }
+
+        let line = if took {
+            format!("You take {}", self.game.attacker_cards.iter().join(" "))
+        } else if defending {
+            format!(
+                "You defend with {}",
+                self.game.defender_cards.iter().join(" ")
+            )
+        } else {
+            format!(
+                "You attack with {}",
+                self.game.attacker_cards.iter().join(" ")
+            )
+        };
+        self.game.player_action(3, 0);
+        self.push_log(line);
+        self.message.clear();
+        self.marked.clear();
+        self.clamp_hand();
+        self.next_step = Instant::now() + BOT_DELAY;
+    }
+
+    fn swap(&mut self) {
+        let trump_card = self.game.trump_card;
+        if self.game.player_action(4, 0) {
+            self.push_log(format!("You swap the two of trumps for {trump_card}"));
+            self.message.clear();
+        } else {
+            self.message = format!(
+                "You need the two of {} and cards in the deck",
+                trump_card.suit
+            );
+        }
+    }
+
+    // Marks the suggested cards
+    fn hint(&mut self) {
+        let hint = self.game.hint(HUMAN);
+        let swap = hint.actions.iter().any(|action| action.action == 4);
+        self.marked = hint
+            .actions
+            .iter()
+            .filter(|action| action.action == 1)
+            .map(|action| action.card_index)
+            .collect();
+
+        self.message = match (swap, self.marked.is_empty()) {
+            (true, _) => "Hint: swap the trump card first".to_string(),
+            (false, true) => "Hint: submit without cards".to_string(),
+            (false, false) => "Hint: play the marked cards".to_string(),
+        };
+    }
+
+    // Advances the bot in turn by one action when its delay has passed
+    fn tick(&mut self, now: Instant) {
+        if self.game.state == State::GameOver || self.human_turn() || now < self.next_step {
+            return;
+        }
+        self.next_step = now + BOT_DELAY;
+
+        let player_index = self.game.table.player_index;
+        let name = format!("Bot {player_index}");
+
+        if self.bot_actions.is_empty() {
+            self.bot_actions = self.bots[player_index].get_actions(&self.game).into();
+            self.bot_actions.push_back(Action {
+                action: 3,
+                card_index: 0,
+            });
+            self.bot_played.clear();
+        }
+
+        let Some(action) = self.bot_actions.pop_front() else {
+            return;
+        };
+        match action.action {
+            1 => self.bot_played.extend(
+                self.game
+                    .player_cards(player_index)
+                    .get(action.card_index)
+                    .copied(),
+            ),
+            3 => {
+                let line = match self.game.state {
+                    State::PlayerDefending if self.bot_played.is_empty() => {
+                        format!("{name} takes {}", self.game.attacker_cards.iter().join(" "))
+                    }
+                    State::PlayerDefending => {
+                        format!("{name} defends with {}", self.bot_played.iter().join(" "))
+                    }
+                    _ => format!("{name} attacks with {}", self.bot_played.iter().join(" ")),
+                };
+                self.push_log(line);
+            }
+            4 => self.push_log(format!(
+                "{name} swaps the two of trumps for {}",
+                self.game.trump_card
+            )),
+            _ => {}
+        }
+        self.game.player_action(action.action, action.card_index);
+
+        if self.game.state == State::GameOver {
+            self.push_log(match self.game.loser() {
+                Some(HUMAN) => "You are left holding the cards. Moska!".to_string(),
+                Some(index) => format!("Bot {index} is left holding the cards. You win!"),
+                None => "Nobody is left holding cards".to_string(),
+            });
+        }
+        self.clamp_hand();
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [top, middle, bottom, status] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(6),
+            Constraint::Length(3),
+            Constraint::Length(2),
+        ])
+        .areas(frame.area());
+        let [table, log] =
+            Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .areas(middle);
+
+        self.draw_opponents(frame, top);
+        self.draw_table(frame, table);
+        self.draw_log(frame, log);
+        self.draw_hand(frame, bottom);
+
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::from(self.message.as_str()).style(Style::new().fg(Color::Yellow)),
+                Line::from(KEYS).style(Style::new().fg(Color::DarkGray)),
+            ]),
+            status,
+        );
+    }
+
+    fn draw_opponents(&self, frame: &mut Frame, area: Rect) {
+        let mut spans = vec![];
+        for (index, player) in self.game.table.players.iter().enumerate() {
+            if index == HUMAN {
+                continue;
+            }
+            let mut style = Style::new();
+            if index == self.game.table.player_index {
+                style = style.add_modifier(Modifier::BOLD | Modifier::REVERSED);
+            }
+            spans.push(Span::styled(
+                format!(" Bot {index}: {} cards ", player.cards.len()),
+                style,
+            ));
+            spans.push(Span::raw("  "));
+        }
+
+        spans.push(Span::raw(format!(
+            "Deck {}  Trump ",
+            self.game.table.deck.count()
+        )));
+        spans.push(card_span(&self.game.trump_card, Style::new()));
+
+        frame.render_widget(
+            Paragraph::new(Line::from(spans)).block(Block::bordered().title("Players")),
+            area,
+        );
+    }
+
+    // Attacking cards with their defending cards below
+    fn draw_table(&self, frame: &mut Frame, area: Rect) {
+        let title = match self.game.state {
+            State::PlayerDefending => format!("Bot {} defends", self.game.table.player_index),
+            State::PlayerAttacking => format!("Bot {} attacks", self.game.table.player_index),
+            _ => "Game over".to_string(),
+        }
+        .replace(&format!("Bot {HUMAN} "), "You ");
+
+        let attacking = self
+            .game
+            .attacker_cards
+            .iter()
+            .flat_map(|card| [card_span(card, Style::new()), Span::raw("  ")]);
+        let defending = (0..self.game.attacker_cards.len()).flat_map(|index| {
+            [
+                match self.game.defender_cards.get(index) {
+                    Some(card) => card_span(card, Style::new()),
+                    None => Span::raw(" ? "),
+                },
+                Span::raw("  "),
+            ]
+        });
+
+        let mut lines = vec![
+            Line::from(attacking.collect::<Vec<_>>()),
+            Line::from(defending.collect::<Vec<_>>()),
+        ];
+        if self.game.state != State::PlayerDefending {
+            lines.pop();
+        }
+
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+
+    fn draw_log(&self, frame: &mut Frame, area: Rect) {
+        let height = area.height.saturating_sub(2) as usize;
+        let end = self.log.len() - self.log_scroll.min(self.log.len());
+        let lines: Vec<Line> = self.log[end.saturating_sub(height)..end]
+            .iter()
+            .map(|line| Line::from(line.as_str()))
+            .collect();
+
+        frame.render_widget(
+            Paragraph::new(lines)
+                .wrap(Wrap { trim: false })
+                .block(Block::bordered().title("Log")),
+            area,
+        );
+    }
+
+    fn draw_hand(&self, frame: &mut Frame, area: Rect) {
+        let spans: Vec<Span> = self
+            .hand()
+            .iter()
+            .enumerate()
+            .flat_map(|(index, card)| {
+                let mut style = Style::new();
+                if self.marked.contains(&index) {
+                    style = style.bg(Color::Blue);
+                }
+                if index == self.cursor && self.human_turn() {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                [card_span(card, style), Span::raw(" ")]
+            })
+            .collect();
+
+        frame.render_widget(
+            Paragraph::new(Line::from(spans)).block(Block::bordered().title("Your hand")),
+            area,
+        );
+    }
+}
+
+fn card_span(card: &Card, style: Style) -> Span<'static> {
+    let color = match card.suit {
+        Suit::Hearts | Suit::Diamonds => Color::Red,
+        Suit::Clubs | Suit::Spades => Color::White,
+    };
+    Span::styled(format!(" {card} "), style.fg(color))
+}
+
+fn run(mut terminal: DefaultTerminal, mut app: App) -> io::Result<()> {
+    while !app.quit {
+        terminal.draw(|frame| app.draw(frame))?;
+
+        if event::poll(Duration::from_millis(50))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    app.handle_key(key.code);
+                }
+            }
+        }
+        app.tick(Instant::now());
+    }
+    Ok(())
+}
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let parse = |index: usize| -> Option<u64> {
+        args.get(index).map(|value| {
+            value.parse().unwrap_or_else(|_| {
+                eprintln!("{USAGE}");
+                std::process::exit(1);
+            })
+        })
+    };
+
+    let players = parse(0).unwrap_or(2).clamp(2, 8) as u8;
+    let app = App::new(players, parse(1));
+
+    let terminal = ratatui::init();
+    let result = run(terminal, app);
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::{backend::TestBackend, Terminal};
+
+    // Runs bot turns until it's the human's turn
+    fn wait_bots(app: &mut App) {
+        let mut now = Instant::now();
+        while !app.human_turn() && app.game.state != State::GameOver {
+            now += BOT_DELAY;
+            app.tick(now);
+        }
+    }
+
+    #[test]
+    fn test_attack_and_bots() {
+        let mut app = App::new(3, Some(3));
+        assert!(app.human_turn());
+
+        // submitting without cards is rejected
+        app.handle_key(KeyCode::Char('s'));
+        assert!(app.human_turn());
+        assert!(!app.message.is_empty());
+
+        // single card attack
+        app.handle_key(KeyCode::Right);
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.game.attacker_cards.len(), 1);
+        app.handle_key(KeyCode::Backspace);
+        assert!(app.game.attacker_cards.is_empty());
+        app.handle_key(KeyCode::Enter);
+        app.handle_key(KeyCode::Char('s'));
+        assert!(!app.human_turn());
+
+        // bots act one step at a time
+        let log_len = app.log.len();
+        app.tick(Instant::now());
+        wait_bots(&mut app);
+        assert!(app.log.len() > log_len);
+    }
+
+    #[test]
+    fn test_play_with_hints() {
+        let mut app = App::new(2, Some(11));
+
+        for _ in 0..1000 {
+            if app.game.state == State::GameOver {
+                break;
+            }
+            app.handle_key(KeyCode::Char('h'));
+            if app.message.contains("swap") {
+                app.handle_key(KeyCode::Char('w'));
+                app.handle_key(KeyCode::Char('h'));
+            }
+            if !app.marked.is_empty() {
+                app.handle_key(KeyCode::Enter);
+            }
+            app.handle_key(KeyCode::Char('s'));
+            wait_bots(&mut app);
+        }
+        assert_eq!(app.game.state, State::GameOver);
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut app = App::new(3, Some(5));
+        app.handle_key(KeyCode::Enter);
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("Bot 1: 6 cards"));
+        assert!(screen.contains("Your hand"));
+        assert!(screen.contains(&app.game.attacker_cards[0].to_string()));
+    }
+}
@@ -0,0 +1,498 @@
+/*
+ * Text protocol for bots running as separate programs.
+ *
+ * The host runs the game and talks to each bot over the bot's
+ * stdin and stdout, one command per line:
+ *
+ *   host: moska                      bot: id name <name>
+ *                                    bot: moskaok
+ *   host: newgame <players> <seat>
+ *   host: position
+ *         seat 1
+ *         state defend
+ *         trump AH
+ *         deck 30
+ *         counts 6 7 5
+ *         hand 7H TS KD
+ *         attack 5S
+ *         defend
+ *         discarded
+ *         go
+ *                                    bot: move play TS
+ *   host: error <reason>
+ *   host: quit
+ *
+ * Cards are written as rank (2-9, T, J, Q, K, A, or D for a joker)
+ * and suit (C, D, H, S). Bots may use any notation `Card` parses.
+ * A move is `take` or `play` with cards, optionally after `swap`
+ * to swap the two of trumps for the trump card first.
+ * Bots may answer with `info` lines, the host ignores them.
+ */
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod process;
+
+use std::io::{self, BufRead, Write};
+
+use itertools::Itertools;
+
+use crate::{
+    deck::{rank_fmt, Card, Deck, ParseCardError, Rank, Suit},
+    game::moska::State,
+    Action, Moska,
+};
+
+// Stands in for cards the bot cannot see
+const HIDDEN: Card = Card {
+    suit: Suit::Clubs,
+    rank: Rank::Joker,
+};
+
+// Card in the notation parsed by `Card`'s `FromStr`, with `T` for ten
+pub fn card_code(card: &Card) -> String {
+    let rank = match card.rank {
+        Rank::Ten => "T".to_string(),
+        rank => rank_fmt(rank),
+    };
+    let suit = match card.suit {
+        Suit::Clubs => 'C',
+        Suit::Diamonds => 'D',
+        Suit::Hearts => 'H',
+        Suit::Spades => 'S',
+    };
+    format!("{rank}{suit}")
+}
+
+pub fn parse_card_code(code: &str) -> Result<Card, String> {
+    code.parse()
+        .map_err(|err: ParseCardError| format!("Invalid card '{code}': {err}"))
+}
+
+fn parse_cards<'a>(codes: impl Iterator<Item = &'a str>) -> Result<Vec<Card>, String> {
+    codes.map(parse_card_code).collect()
+}
+
+fn parse_number(value: Option<&str>, key: &str) -> Result<usize, String> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("Expected a number after '{key}'"))
+}
+
+// Game as seen by the player in turn
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observation {
+    pub seat: usize,
+    // Attacking or defending
+    pub state: State,
+    pub trump_card: Card,
+    pub deck_count: usize,
+    // Number of cards in each player's hand
+    pub hand_counts: Vec<usize>,
+    pub hand: Vec<Card>,
+    pub attacker_cards: Vec<Card>,
+    pub defender_cards: Vec<Card>,
+    pub discarded: Vec<Card>,
+}
+
+impl Observation {
+    pub fn new(game: &Moska, seat: usize) -> Self {
+        Self {
+            seat,
+            state: game.state,
+            trump_card: game.trump_card,
+            deck_count: game.table.deck.count(),
+            hand_counts: game.table.players.iter().map(|p| p.cards.len()).collect(),
+            hand: game.player_cards(seat),
+            attacker_cards: game.attacker_cards.clone(),
+            defender_cards: game.defender_cards.clone(),
+            discarded: game.discarded.clone(),
+        }
+    }
+
+    // Lines from `position` to `go`
+    pub fn to_lines(&self) -> Vec<String> {
+        let cards = |key: &str, cards: &[Card]| {
+            std::iter::once(key.to_string())
+                .chain(cards.iter().map(card_code))
+                .join(" ")
+        };
+        let state = match self.state {
+            State::PlayerDefending => "defend",
+            _ => "attack",
+        };
+
+        vec![
+            "position".to_string(),
+            format!("seat {}", self.seat),
+            format!("state {state}"),
+            format!("trump {}", card_code(&self.trump_card)),
+            format!("deck {}", self.deck_count),
+            format!("counts {}", self.hand_counts.iter().join(" ")),
+            cards("hand", &self.hand),
+            cards("attack", &self.attacker_cards),
+            cards("defend", &self.defender_cards),
+            cards("discarded", &self.discarded),
+            "go".to_string(),
+        ]
+    }
+
+    // Parses the lines between `position` and `go`
+    pub fn parse<'a>(lines: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let mut seat = None;
+        let mut state = None;
+        let mut trump_card = None;
+        let mut observation = Observation {
+            seat: 0,
+            state: State::PlayerAttacking,
+            trump_card: HIDDEN,
+            deck_count: 0,
+            hand_counts: vec![],
+            hand: vec![],
+            attacker_cards: vec![],
+            defender_cards: vec![],
+            discarded: vec![],
+        };
+
+        for line in lines {
+            let mut words = line.split_whitespace();
+            let Some(key) = words.next() else {
+                continue;
+            };
+            match key {
+                "seat" => seat = Some(parse_number(words.next(), key)?),
+                "state" => {
+                    state = Some(match words.next() {
+                        Some("attack") => State::PlayerAttacking,
+                        Some("defend") => State::PlayerDefending,
+                        _ => return Err("Expected 'attack' or 'defend' after 'state'".to_string()),
+                    })
+                }
+                "trump" => {
+                    trump_card = Some(parse_card_code(words.next().unwrap_or_default())?);
+                }
+                "deck" => observation.deck_count = parse_number(words.next(), key)?,
+                "counts" => {
+                    observation.hand_counts = words
+                        .map(|word| parse_number(Some(word), key))
+                        .collect::<Result<_, _>>()?;
+                }
+                "hand" => observation.hand = parse_cards(words)?,
+                "attack" => observation.attacker_cards = parse_cards(words)?,
+                "defend" => observation.defender_cards = parse_cards(words)?,
+                "discarded" => observation.discarded = parse_cards(words)?,
+                // Room for fields of later versions
+                _ => {}
+            }
+        }
+
+        observation.seat = seat.ok_or("Missing 'seat'")?;
+        observation.state = state.ok_or("Missing 'state'")?;
+        observation.trump_card = trump_card.ok_or("Missing 'trump'")?;
+        if observation.seat >= observation.hand_counts.len() {
+            return Err("Seat is not among 'counts'".to_string());
+        }
+        Ok(observation)
+    }
+
+    // Game with the hidden cards filled with placeholders.
+    // Enough for bots that only look at their own hand.
+    pub fn to_game(&self) -> Moska {
+        let mut game = Moska::new(self.hand_counts.len() as u8);
+
+        for (index, player) in game.table.players.iter_mut().enumerate() {
+            player.cards = if index == self.seat {
+                self.hand.clone()
+            } else {
+                vec![HIDDEN; self.hand_counts[index]]
+            };
+        }
+
+        // Trump card lies at the bottom of the deck
+        let mut deck = vec![HIDDEN; self.deck_count];
+        if let Some(bottom) = deck.first_mut() {
+            *bottom = self.trump_card;
+        }
+        game.table.deck = Deck::from_cards(deck);
+        game.table.player_index = self.seat;
+
+        game.trump_card = self.trump_card;
+        game.attacker_cards = self.attacker_cards.clone();
+        game.defender_cards = self.defender_cards.clone();
+        game.discarded = self.discarded.clone();
+        game.state = self.state;
+        game.rehash();
+        game
+    }
+}
+
+// Bot's answer to a position
+#[derive(Clone, Debug, PartialEq)]
+pub struct BotMove {
+    // Swap the two of trumps before playing
+    pub swap: bool,
+    // Cards put on the table, none to take when defending
+    pub cards: Vec<Card>,
+}
+
+impl BotMove {
+    // Move made by the actions of the player in turn
+    pub fn from_actions(game: &Moska, actions: &[Action]) -> Self {
+        let mut game = game.clone();
+        let player_index = game.table.player_index;
+        let mut bot_move = BotMove {
+            swap: false,
+            cards: vec![],
+        };
+
+        for action in actions {
+            match action.action {
+                1 => bot_move.cards.extend(
+                    game.player_cards(player_index)
+                        .get(action.card_index)
+                        .copied(),
+                ),
+                4 => bot_move.swap = true,
+                _ => {}
+            }
+            game.player_action(action.action, action.card_index);
+        }
+        bot_move
+    }
+
+    pub fn to_line(&self) -> String {
+        let mut words = vec!["move".to_string()];
+        if self.swap {
+            words.push("swap".to_string());
+        }
+        if self.cards.is_empty() {
+            words.push("take".to_string());
+        } else {
+            words.push("play".to_string());
+            words.extend(self.cards.iter().map(card_code));
+        }
+        words.join(" ")
+    }
+
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace().peekable();
+        if words.next() != Some("move") {
+            return Err(format!("Expected a move, got '{line}'"));
+        }
+
+        let swap = words.next_if_eq(&"swap").is_some();
+        let cards = match words.next() {
+            Some("take") => vec![],
+            Some("play") => parse_cards(words.by_ref())?,
+            _ => return Err(format!("Expected 'take' or 'play' in '{line}'")),
+        };
+        if words.next().is_some() {
+            return Err(format!("Unexpected words after the move in '{line}'"));
+        }
+
+        Ok(BotMove { swap, cards })
+    }
+
+    // Engine actions for the player in turn, before submitting.
+    // Fails if the move breaks the rules.
+    pub fn to_actions(&self, game: &Moska) -> Result<Vec<Action>, String> {
+        let mut game = game.clone();
+        let player_index = game.table.player_index;
+        let mut actions = vec![];
+
+        if self.swap {
+            if !game.player_action(4, 0) {
+                return Err("Cannot swap the trump card".to_string());
+            }
+            actions.push(Action {
+                action: 4,
+                card_index: 0,
+            });
+        }
+
+        for card in &self.cards {
+            let card_index = game.table.players[player_index]
+                .card_index(card)
+                .ok_or_else(|| format!("{} is not in hand", card_code(card)))?;
+            game.player_action(1, card_index);
+            actions.push(Action {
+                action: 1,
+                card_index,
+            });
+        }
+
+        let took = game.state == State::PlayerDefending && self.cards.is_empty();
+        if !took && !game.valid() {
+            return Err(match game.state {
+                State::PlayerDefending => "Defending cards do not beat the attack",
+                _ => "Attack must be a single card or pairs the defender can answer",
+            }
+            .to_string());
+        }
+        Ok(actions)
+    }
+}
+
+// Runs a bot over the protocol until `quit` or the end of input.
+// `choose` gives the actions of the player in turn.
+pub fn run_bot<R: BufRead, W: Write>(
+    name: &str,
+    input: R,
+    mut output: W,
+    mut choose: impl FnMut(&Moska) -> Vec<Action>,
+) -> io::Result<()> {
+    let mut position: Option<Vec<String>> = None;
+
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+
+        match (line, position.as_mut()) {
+            ("quit", _) => break,
+            ("moska", _) => {
+                writeln!(output, "id name {name}")?;
+                writeln!(output, "moskaok")?;
+            }
+            ("position", _) => position = Some(vec![]),
+            ("go", Some(lines)) => {
+                match Observation::parse(lines.iter().map(String::as_str)) {
+                    Ok(observation) => {
+                        let game = observation.to_game();
+                        let actions = choose(&game);
+                        writeln!(
+                            output,
+                            "{}",
+                            BotMove::from_actions(&game, &actions).to_line()
+                        )?;
+                    }
+                    Err(err) => writeln!(output, "info {err}")?,
+                }
+                position = None;
+            }
+            (_, Some(lines)) => lines.push(line.to_string()),
+            // newgame, error and unknown commands
+            _ => {}
+        }
+        output.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MoskaAI;
+
+    #[test]
+    fn test_card_codes() {
+        for suit in [Suit::Clubs, Suit::Hearts, Suit::Diamonds, Suit::Spades] {
+            for rank in 0..=13 {
+                let card = Card::new(suit, Rank::try_from(rank).unwrap());
+                assert_eq!(parse_card_code(&card_code(&card)), Ok(card));
+            }
+        }
+        assert_eq!(card_code(&Card::new(Suit::Hearts, Rank::Ten)), "TH");
+        assert_eq!(card_code(&HIDDEN), "DC");
+        assert_eq!(
+            parse_card_code("10h"),
+            Ok(Card::new(Suit::Hearts, Rank::Ten))
+        );
+
+        for code in ["", "A", "1H", "AX", "AHH", "XC"] {
+            assert!(parse_card_code(code).is_err(), "{code}");
+        }
+    }
+
+    #[test]
+    fn test_observation() {
+        let mut game = Moska::new(3);
+        game.new_round_with_seed(12);
+        game.player_action(1, 0);
+        game.player_action(3, 0);
+
+        let seat = game.table.player_index;
+        let observation = Observation::new(&game, seat);
+        let lines = observation.to_lines();
+        assert_eq!(lines.first().map(String::as_str), Some("position"));
+        assert_eq!(lines.last().map(String::as_str), Some("go"));
+
+        let parsed = Observation::parse(lines[1..lines.len() - 1].iter().map(String::as_str));
+        assert_eq!(parsed, Ok(observation.clone()));
+
+        // placeholders keep the bot's view of the game
+        let view = observation.to_game();
+        assert_eq!(Observation::new(&view, seat), observation);
+        assert_eq!(
+            MoskaAI::new(seat).get_actions(&view),
+            MoskaAI::new(seat).get_actions(&game)
+        );
+
+        assert!(Observation::parse(["seat 0", "state attack"]).is_err());
+        assert!(Observation::parse(["seat x"]).is_err());
+    }
+
+    #[test]
+    fn test_moves() {
+        let mut game = Moska::new(2);
+        game.new_round_with_seed(3);
+        let hand = game.player_cards(0);
+
+        let bot_move = BotMove {
+            swap: false,
+            cards: vec![hand[2]],
+        };
+        assert_eq!(BotMove::parse(&bot_move.to_line()), Ok(bot_move.clone()));
+        assert_eq!(
+            bot_move.to_actions(&game),
+            Ok(vec![Action {
+                action: 1,
+                card_index: 2
+            }])
+        );
+
+        // taking is not an attack
+        let take = BotMove::parse("move take").unwrap();
+        assert!(take.to_actions(&game).is_err());
+
+        assert!(BotMove::parse("move").is_err());
+        assert!(BotMove::parse("move play AH x").is_err());
+        assert!(BotMove::parse("move take AH").is_err());
+        assert!(BotMove::parse("play AH").is_err());
+
+        // opponent's card
+        let missing = BotMove {
+            swap: false,
+            cards: vec![game.player_cards(1)[0]],
+        };
+        assert!(missing.to_actions(&game).is_err());
+    }
+
+    #[test]
+    fn test_run_bot() {
+        let mut game = Moska::new(2);
+        game.new_round_with_seed(5);
+
+        let mut input = vec!["moska".to_string(), "newgame 2 0".to_string()];
+        input.extend(Observation::new(&game, 0).to_lines());
+        input.push("quit".to_string());
+        input.push("moska".to_string());
+
+        let mut output = vec![];
+        run_bot(
+            "reference",
+            input.join("\n").as_bytes(),
+            &mut output,
+            |game| MoskaAI::new(game.table.player_index).get_actions(game),
+        )
+        .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[..2], ["id name reference", "moskaok"]);
+        assert_eq!(lines.len(), 3);
+
+        let bot_move = BotMove::parse(lines[2]).unwrap();
+        let actions = MoskaAI::new(0).get_actions(&game);
+        assert_eq!(bot_move, BotMove::from_actions(&game, &actions));
+        assert!(bot_move.to_actions(&game).is_ok());
+    }
+}
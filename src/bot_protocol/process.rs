@@ -0,0 +1,275 @@
+/*
+ * Bots running as child processes
+ */
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    bot_protocol::{BotMove, Observation},
+    Action, Moska, MoskaAI,
+};
+
+pub struct ExternalBot {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    // Time allowed for each answer
+    timeout: Duration,
+}
+
+impl ExternalBot {
+    // Starts the program and waits for its handshake
+    pub fn spawn(program: &str, args: &[String], timeout: Duration) -> Result<Self, String> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("Could not start '{program}': {err}"))?;
+
+        let stdin = child.stdin.take().expect("Child stdin is piped");
+        let stdout = child.stdout.take().expect("Child stdout is piped");
+
+        // Lines are read on their own thread to wait for them with a timeout
+        let (sender, lines) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut bot = Self {
+            name: program.to_string(),
+            child,
+            stdin,
+            lines,
+            timeout,
+        };
+
+        bot.send("moska")?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let line = bot.read_line(deadline)?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                bot.name = name.trim().to_string();
+            } else if line.trim() == "moskaok" {
+                return Ok(bot);
+            }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{line}")
+            .and_then(|_| self.stdin.flush())
+            .map_err(|err| format!("Could not write to {}: {err}", self.name))
+    }
+
+    fn read_line(&mut self, deadline: Instant) -> Result<String, String> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        self.lines.recv_timeout(timeout).map_err(|err| match err {
+            RecvTimeoutError::Timeout => format!("{} timed out", self.name),
+            RecvTimeoutError::Disconnected => format!("{} exited", self.name),
+        })
+    }
+
+    pub fn new_game(&mut self, players: usize, seat: usize) -> Result<(), String> {
+        self.send(&format!("newgame {players} {seat}"))
+    }
+
+    // Sends the seat's observation and waits for the move
+    pub fn request_move(&mut self, game: &Moska, seat: usize) -> Result<BotMove, String> {
+        for line in Observation::new(game, seat).to_lines() {
+            self.send(&line)?;
+        }
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let line = self.read_line(deadline)?;
+            if line.starts_with("move") {
+                return BotMove::parse(&line);
+            }
+            // info and unknown lines
+        }
+    }
+
+    // Tells the bot its move was not played
+    pub fn reject(&mut self, reason: &str) -> Result<(), String> {
+        self.send(&format!("error {reason}"))
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// Seat played by an external bot.
+//
+// Rejected moves are replaced by `MoskaAI` moves. The bot is let go
+// after too many rejected moves, or at once if it times out or exits,
+// as a late answer could be taken for the next move.
+pub struct ExternalSeat {
+    bot: Option<ExternalBot>,
+    fallback: MoskaAI,
+    seat: usize,
+    max_errors: usize,
+    // Reasons of rejected moves
+    pub errors: Vec<String>,
+}
+
+impl ExternalSeat {
+    pub fn new(mut bot: ExternalBot, players: usize, seat: usize, max_errors: usize) -> Self {
+        let mut errors = vec![];
+        let bot = match bot.new_game(players, seat) {
+            Ok(()) => Some(bot),
+            Err(err) => {
+                errors.push(err);
+                None
+            }
+        };
+
+        Self {
+            bot,
+            fallback: MoskaAI::new(seat),
+            seat,
+            max_errors,
+            errors,
+        }
+    }
+
+    // Bot still plays its moves
+    pub fn connected(&self) -> bool {
+        self.bot.is_some()
+    }
+
+    pub fn get_actions(&mut self, game: &Moska) -> Vec<Action> {
+        if let Some(bot) = &mut self.bot {
+            match bot.request_move(game, self.seat) {
+                Ok(bot_move) => match bot_move.to_actions(game) {
+                    Ok(actions) => return actions,
+                    Err(err) => {
+                        let _ = bot.reject(&err);
+                        self.errors.push(err);
+                        if self.errors.len() >= self.max_errors {
+                            self.bot = None;
+                        }
+                    }
+                },
+                Err(err) => {
+                    self.errors.push(err);
+                    self.bot = None;
+                }
+            }
+        }
+
+        self.fallback.get_actions(game)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::game::{
+        moska::State,
+        testing::{play_out, play_turn},
+    };
+
+    // Shell bot attacking with its first card and always taking
+    const FIRST_CARD_BOT: &str = r#"
+        echo "id name first"
+        while read line; do
+            set -- $line
+            case "$1" in
+                moska) echo moskaok ;;
+                state) state=$2 ;;
+                hand) card=$2 ;;
+                go) if [ "$state" = defend ]; then echo "move take"; else echo "info thinking"; echo "move play $card"; fi ;;
+                quit) exit ;;
+            esac
+        done
+    "#;
+
+    fn shell_bot(script: &str, timeout_ms: u64) -> Result<ExternalBot, String> {
+        ExternalBot::spawn(
+            "sh",
+            &["-c".to_string(), script.to_string()],
+            Duration::from_millis(timeout_ms),
+        )
+    }
+
+    // Plays the game with the external bot in seat 0
+    fn play(seat: &mut ExternalSeat, seed: u64) -> Moska {
+        let mut game = Moska::new(2);
+        game.new_round_with_seed(seed);
+
+        play_out(&mut game, |game| {
+            let player = game.table.player_index;
+            let actions = if player == 0 {
+                seat.get_actions(game)
+            } else {
+                MoskaAI::new(player).get_actions(game)
+            };
+            play_turn(game, actions);
+        });
+        game
+    }
+
+    #[test]
+    fn test_external_bot() {
+        let bot = shell_bot(FIRST_CARD_BOT, 5_000).unwrap();
+        assert_eq!(bot.name(), "first");
+
+        let mut seat = ExternalSeat::new(bot, 2, 0, 3);
+        let game = play(&mut seat, 4);
+        assert_eq!(game.state, State::GameOver);
+        assert!(seat.connected());
+        assert_eq!(seat.errors, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_bad_moves() {
+        let script = "while read line; do case $line in moska) echo moskaok ;; go) echo 'move play AH AH' ;; esac; done";
+        let mut seat = ExternalSeat::new(shell_bot(script, 5_000).unwrap(), 2, 0, 3);
+
+        let game = play(&mut seat, 4);
+        assert_eq!(game.state, State::GameOver);
+        assert!(!seat.connected());
+        assert_eq!(seat.errors.len(), 3);
+    }
+
+    #[test]
+    fn test_timeouts() {
+        // never answers the handshake
+        assert!(shell_bot("sleep 5", 100).is_err());
+        // exits at once
+        assert!(shell_bot("true", 1_000).is_err());
+        assert!(ExternalBot::spawn("/nonexistent/bot", &[], Duration::from_secs(1)).is_err());
+
+        let script =
+            "while read line; do case $line in moska) echo moskaok ;; go) sleep 5 ;; esac; done";
+        let mut seat = ExternalSeat::new(shell_bot(script, 100).unwrap(), 2, 0, 3);
+        let game = play(&mut seat, 4);
+        assert_eq!(game.state, State::GameOver);
+        assert!(!seat.connected());
+        assert_eq!(seat.errors.len(), 1);
+    }
+}
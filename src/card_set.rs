@@ -0,0 +1,433 @@
+/*
+ * Set of cards in a 64-bit mask.
+ *
+ * Each card has an ordinal: the 52 suited cards are numbered by suit and
+ * Moska rank order, so the cards of a suit run from two to ace, and the
+ * jokers follow at 52.. in suit order. Set operations, membership and
+ * counting are single bit operations.
+ */
+
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Sub, SubAssign};
+
+use crate::{
+    deck::{Card, Rank, Suit},
+    game::moska::card_rank_order,
+};
+
+// Number of card ordinals, jokers included
+pub const NUM_ORDINALS: usize = 56;
+
+// Suits in the order of their discriminants
+const SUITS: [Suit; 4] = [Suit::Clubs, Suit::Hearts, Suit::Diamonds, Suit::Spades];
+
+// Ranks in Moska rank order, joker last
+const RANKS: [Rank; 14] = [
+    Rank::Two,
+    Rank::Three,
+    Rank::Four,
+    Rank::Five,
+    Rank::Six,
+    Rank::Seven,
+    Rank::Eight,
+    Rank::Nine,
+    Rank::Ten,
+    Rank::Jack,
+    Rank::Queen,
+    Rank::King,
+    Rank::Ace,
+    Rank::Joker,
+];
+
+const SUITED_MASK: u64 = (1 << 52) - 1;
+const JOKER_MASK: u64 = 0xF << 52;
+
+pub fn card_ordinal(card: &Card) -> u8 {
+    match card.rank {
+        Rank::Joker => 52 + card.suit as u8,
+        rank => card.suit as u8 * 13 + card_rank_order(rank) as u8,
+    }
+}
+
+// Panics when the ordinal is out of range
+pub fn ordinal_card(ordinal: u8) -> Card {
+    let ordinal = ordinal as usize;
+    assert!(ordinal < NUM_ORDINALS, "invalid card ordinal {}", ordinal);
+    match ordinal {
+        52.. => Card::new(SUITS[ordinal - 52], Rank::Joker),
+        _ => Card::new(SUITS[ordinal / 13], RANKS[ordinal % 13]),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CardSet {
+    mask: u64,
+}
+
+impl CardSet {
+    pub const EMPTY: Self = Self { mask: 0 };
+
+    // The 52 cards of a deck without jokers
+    pub const FULL: Self = Self { mask: SUITED_MASK };
+
+    pub const JOKERS: Self = Self { mask: JOKER_MASK };
+
+    pub fn new() -> Self {
+        Self::EMPTY
+    }
+
+    // Bits above the card ordinals are dropped
+    pub fn from_mask(mask: u64) -> Self {
+        Self {
+            mask: mask & (SUITED_MASK | JOKER_MASK),
+        }
+    }
+
+    pub fn mask(&self) -> u64 {
+        self.mask
+    }
+
+    // All cards of the suit, its joker included
+    pub fn suit(suit: Suit) -> Self {
+        let suit = suit as u64;
+        Self {
+            mask: 0x1FFF << (suit * 13) | 1 << (52 + suit),
+        }
+    }
+
+    pub fn rank(rank: Rank) -> Self {
+        match rank {
+            Rank::Joker => Self::JOKERS,
+            rank => Self {
+                mask: (0..4).fold(0, |mask, suit| {
+                    mask | 1 << (suit * 13 + card_rank_order(rank))
+                }),
+            },
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.mask.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mask == 0
+    }
+
+    pub fn contains(&self, card: &Card) -> bool {
+        self.mask & 1 << card_ordinal(card) != 0
+    }
+
+    // Returns false when the card was already in the set
+    pub fn insert(&mut self, card: Card) -> bool {
+        let bit = 1 << card_ordinal(&card);
+        let added = self.mask & bit == 0;
+        self.mask |= bit;
+        added
+    }
+
+    // Returns false when the card was not in the set
+    pub fn remove(&mut self, card: &Card) -> bool {
+        let bit = 1 << card_ordinal(card);
+        let removed = self.mask & bit != 0;
+        self.mask &= !bit;
+        removed
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            mask: self.mask | other.mask,
+        }
+    }
+
+    pub fn intersection(self, other: Self) -> Self {
+        Self {
+            mask: self.mask & other.mask,
+        }
+    }
+
+    pub fn difference(self, other: Self) -> Self {
+        Self {
+            mask: self.mask & !other.mask,
+        }
+    }
+
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.mask & !other.mask == 0
+    }
+
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        self.mask & other.mask == 0
+    }
+
+    // Cards of the set in the suit
+    pub fn of_suit(&self, suit: Suit) -> Self {
+        self.intersection(Self::suit(suit))
+    }
+
+    // Cards of the set with the rank
+    pub fn of_rank(&self, rank: Rank) -> Self {
+        self.intersection(Self::rank(rank))
+    }
+
+    // Distinct ranks of the cards, in rank order
+    pub fn ranks(&self) -> impl Iterator<Item = Rank> {
+        let set = *self;
+        RANKS
+            .into_iter()
+            .filter(move |rank| !set.of_rank(*rank).is_empty())
+    }
+
+    // Cards by ordinal: clubs, hearts, diamonds, spades from two to ace,
+    // then the jokers
+    pub fn iter(&self) -> impl Iterator<Item = Card> {
+        let mut mask = self.mask;
+        std::iter::from_fn(move || {
+            (mask != 0).then(|| {
+                let ordinal = mask.trailing_zeros() as u8;
+                mask &= mask - 1;
+                ordinal_card(ordinal)
+            })
+        })
+    }
+
+    // Cards in `card_cmp` order: non-trumps before trumps,
+    // then by rank and suit
+    pub fn iter_sorted(&self, trump_suit: Suit) -> impl Iterator<Item = Card> {
+        let trumps = Self::suit(trump_suit);
+        let groups = [self.difference(trumps), self.intersection(trumps)];
+        groups.into_iter().flat_map(|group| {
+            RANKS
+                .iter()
+                .flat_map(move |rank| group.of_rank(*rank).iter())
+        })
+    }
+
+    // Cards in `card_cmp` order
+    pub fn to_sorted_vec(&self, trump_suit: Suit) -> Vec<Card> {
+        self.iter_sorted(trump_suit).collect()
+    }
+}
+
+impl FromIterator<Card> for CardSet {
+    fn from_iter<I: IntoIterator<Item = Card>>(iter: I) -> Self {
+        let mut set = Self::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<'a> FromIterator<&'a Card> for CardSet {
+    fn from_iter<I: IntoIterator<Item = &'a Card>>(iter: I) -> Self {
+        iter.into_iter().copied().collect()
+    }
+}
+
+impl Extend<Card> for CardSet {
+    fn extend<I: IntoIterator<Item = Card>>(&mut self, iter: I) {
+        for card in iter {
+            self.insert(card);
+        }
+    }
+}
+
+impl From<&[Card]> for CardSet {
+    fn from(cards: &[Card]) -> Self {
+        cards.iter().collect()
+    }
+}
+
+impl BitOr for CardSet {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        self.union(other)
+    }
+}
+
+impl BitAnd for CardSet {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self {
+        self.intersection(other)
+    }
+}
+
+impl Sub for CardSet {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.difference(other)
+    }
+}
+
+impl BitOrAssign for CardSet {
+    fn bitor_assign(&mut self, other: Self) {
+        *self = self.union(other);
+    }
+}
+
+impl BitAndAssign for CardSet {
+    fn bitand_assign(&mut self, other: Self) {
+        *self = self.intersection(other);
+    }
+}
+
+impl SubAssign for CardSet {
+    fn sub_assign(&mut self, other: Self) {
+        *self = self.difference(other);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deck::Deck, game::moska::card_cmp, table::Player};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn all_cards() -> Vec<Card> {
+        Deck::new_with_jokers().cards()
+    }
+
+    fn random_set(rng: &mut StdRng) -> CardSet {
+        CardSet::from_mask(rng.gen())
+    }
+
+    #[test]
+    fn test_ordinals() {
+        let mut seen = [false; NUM_ORDINALS];
+        for card in all_cards() {
+            let ordinal = card_ordinal(&card);
+            assert!(!seen[ordinal as usize]);
+            seen[ordinal as usize] = true;
+            assert_eq!(ordinal_card(ordinal), card);
+        }
+        assert!(seen.iter().all(|seen| *seen));
+
+        assert_eq!(ordinal_card(0), Card::new(Suit::Clubs, Rank::Two));
+        assert_eq!(ordinal_card(12), Card::new(Suit::Clubs, Rank::Ace));
+        assert_eq!(ordinal_card(51), Card::new(Suit::Spades, Rank::Ace));
+        assert_eq!(ordinal_card(53), Card::new(Suit::Hearts, Rank::Joker));
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let mut set = CardSet::new();
+        for (count, card) in all_cards().into_iter().enumerate() {
+            assert_eq!(set.len(), count);
+            assert!(!set.contains(&card));
+            assert!(set.insert(card));
+            assert!(!set.insert(card));
+            assert!(set.contains(&card));
+        }
+        assert_eq!(set, CardSet::FULL | CardSet::JOKERS);
+
+        for card in all_cards() {
+            assert!(set.remove(&card));
+            assert!(!set.remove(&card));
+            assert!(!set.contains(&card));
+        }
+        assert!(set.is_empty());
+        assert_eq!(CardSet::from_mask(u64::MAX).len(), NUM_ORDINALS);
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..200 {
+            let a = random_set(&mut rng);
+            let b = random_set(&mut rng);
+
+            for card in all_cards() {
+                let (in_a, in_b) = (a.contains(&card), b.contains(&card));
+                assert_eq!((a | b).contains(&card), in_a || in_b);
+                assert_eq!((a & b).contains(&card), in_a && in_b);
+                assert_eq!((a - b).contains(&card), in_a && !in_b);
+            }
+            assert_eq!(a.union(b), b.union(a));
+            assert_eq!((a | b).len() + (a & b).len(), a.len() + b.len());
+            assert!((a & b).is_subset(&a));
+            assert!(a.is_subset(&(a | b)));
+            assert!((a - b).is_disjoint(&b));
+            assert_eq!(a.is_subset(&b), a - b == CardSet::EMPTY);
+
+            let mut c = a;
+            c |= b;
+            c -= b;
+            assert_eq!(c, a - b);
+            c &= a;
+            assert_eq!(c, a - b);
+        }
+    }
+
+    #[test]
+    fn test_suit_and_rank() {
+        for suit in SUITS {
+            let cards = CardSet::suit(suit);
+            assert_eq!(cards.len(), 14);
+            assert!(cards.iter().all(|card| card.suit == suit));
+        }
+        for rank in RANKS {
+            let cards = CardSet::rank(rank);
+            assert_eq!(cards.len(), 4);
+            assert!(cards.iter().all(|card| card.rank == rank));
+        }
+
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..50 {
+            let set = random_set(&mut rng);
+            let by_suit = SUITS
+                .iter()
+                .fold(CardSet::EMPTY, |acc, suit| acc | set.of_suit(*suit));
+            let by_rank = RANKS
+                .iter()
+                .fold(CardSet::EMPTY, |acc, rank| acc | set.of_rank(*rank));
+            assert_eq!(by_suit, set);
+            assert_eq!(by_rank, set);
+            for suit in SUITS {
+                let expected = set.iter().filter(|card| card.suit == suit).count();
+                assert_eq!(set.of_suit(suit).len(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_iteration() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let sets = (0..50).map(|_| random_set(&mut rng));
+        for set in sets.chain([CardSet::FULL | CardSet::JOKERS, CardSet::EMPTY]) {
+            let ordinals: Vec<u8> = set.iter().map(|card| card_ordinal(&card)).collect();
+            assert!(ordinals.windows(2).all(|pair| pair[0] < pair[1]));
+            assert_eq!(set.iter().count(), set.len());
+            assert_eq!(set.iter().collect::<CardSet>(), set);
+
+            for trump_suit in SUITS {
+                let mut expected: Vec<Card> = set.iter().collect();
+                expected.sort_by(|a, b| card_cmp(a, b, trump_suit));
+                assert_eq!(set.to_sorted_vec(trump_suit), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_player_and_deck() {
+        let deck = Deck::new();
+        assert_eq!(deck.card_set(), CardSet::FULL);
+        assert_eq!(Deck::new_with_jokers().card_set().len(), NUM_ORDINALS);
+        assert_eq!(
+            Deck::new_short().card_set(),
+            CardSet::FULL
+                - CardSet::rank(Rank::Two)
+                - CardSet::rank(Rank::Three)
+                - CardSet::rank(Rank::Four)
+                - CardSet::rank(Rank::Five)
+        );
+
+        let mut player = Player::new(0);
+        player.cards = deck.cards()[..6].to_vec();
+        let hand = player.card_set();
+        assert_eq!(hand.len(), 6);
+        assert!(hand.is_subset(&deck.card_set()));
+        assert!(hand.contains(&deck.cards()[5]));
+        assert!(!hand.contains(&deck.cards()[6]));
+    }
+}
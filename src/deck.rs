@@ -1,7 +1,10 @@
-use rand::seq::SliceRandom;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
 use wasm_bindgen::prelude::*;
 
+use crate::card_set::CardSet;
+
 #[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[wasm_bindgen]
 pub enum Suit {
     Clubs,
@@ -11,6 +14,7 @@ pub enum Suit {
 }
 
 #[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[wasm_bindgen]
 pub enum Rank {
     Joker = 0,
@@ -30,6 +34,7 @@ pub enum Rank {
 }
 
 #[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[wasm_bindgen]
 pub struct Card {
     #[wasm_bindgen(skip)]
@@ -46,6 +51,16 @@ impl Card {
         Self { suit, rank }
     }
 
+    // Parses card notation such as `QS`, `10h`, `7♣` or a playing card symbol
+    pub fn parse(text: &str) -> Result<Card, String> {
+        text.parse().map_err(|err: ParseCardError| err.to_string())
+    }
+
+    // Parses cards separated by spaces or commas, such as `7♣ 7♦ K♠`
+    pub fn parse_hand(text: &str) -> Result<Vec<Card>, String> {
+        parse_hand(text).map_err(|err| err.to_string())
+    }
+
     #[wasm_bindgen(getter)]
     pub fn suit(&self) -> String {
         format!("{:?}", self.suit)
@@ -159,14 +174,33 @@ impl Deck {
         Self { deck }
     }
 
+    // 36 card deck from sixes to aces
+    pub fn new_short() -> Self {
+        let mut deck = Self::new();
+        deck.deck
+            .retain(|card| card.rank == Rank::Ace || card.rank >= Rank::Six);
+        deck
+    }
+
+    // Deck with the cards from bottom to top
+    pub(crate) fn from_cards(cards: Vec<Card>) -> Self {
+        Self { deck: cards }
+    }
+
     pub fn shuffle(&mut self) {
         self.deck.shuffle(&mut rand::thread_rng());
     }
 
+    // Shuffles the deck in a reproducible order
+    pub fn shuffle_with_seed(&mut self, seed: u64) {
+        self.deck.shuffle(&mut StdRng::seed_from_u64(seed));
+    }
+
     pub(crate) fn pop(&mut self) -> Option<Card> {
         self.deck.pop()
     }
 
+    #[allow(dead_code)]
     pub(crate) fn pop_last(&mut self) -> Option<Card> {
         if self.deck.is_empty() {
             None
@@ -185,16 +219,42 @@ impl Deck {
         self.deck.first()
     }
 
+    // Replaces last card from the bottom of the deck.
+    // Returns the replaced card.
+    pub(crate) fn replace_last(&mut self, card: Card) -> Option<Card> {
+        self.deck
+            .first_mut()
+            .map(|last| std::mem::replace(last, card))
+    }
+
     pub fn push(&mut self, card: Card) {
         self.deck.push(card);
     }
 
+    // Copy of the cards from bottom to top
+    pub fn cards(&self) -> Vec<Card> {
+        self.deck.clone()
+    }
+
     #[wasm_bindgen(getter)]
     pub fn count(&self) -> usize {
         self.deck.len()
     }
 }
 
+impl Deck {
+    // Cards left in the deck as a set
+    pub fn card_set(&self) -> CardSet {
+        self.deck.iter().collect()
+    }
+}
+
+impl Default for Deck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TryFrom<u8> for Rank {
     type Error = &'static str;
 
@@ -219,7 +279,10 @@ impl TryFrom<u8> for Rank {
     }
 }
 
-use std::fmt::{self, Display};
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
 
 fn suit_symbol(suit: Suit) -> String {
     match suit {
@@ -263,3 +326,263 @@ impl Display for Card {
         write!(f, "{}{}", self.rank, self.suit)
     }
 }
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseCardError {
+    Empty,
+    InvalidRank(String),
+    InvalidSuit(String),
+    // Rank without a suit
+    MissingSuit(String),
+    // Playing card symbol outside the deck, like the knights
+    InvalidSymbol(char),
+}
+
+impl Display for ParseCardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseCardError::Empty => write!(f, "empty card"),
+            ParseCardError::InvalidRank(rank) => write!(f, "invalid rank '{rank}'"),
+            ParseCardError::InvalidSuit(suit) => write!(f, "invalid suit '{suit}'"),
+            ParseCardError::MissingSuit(card) => write!(f, "card '{card}' has no suit"),
+            ParseCardError::InvalidSymbol(symbol) => {
+                write!(f, "no card for symbol U+{:X}", *symbol as u32)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseCardError {}
+
+// Card of a hand that could not be parsed
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseHandError {
+    // Position of the card in the hand, from 0
+    pub index: usize,
+    // Column the card starts at, from 1
+    pub column: usize,
+    pub error: ParseCardError,
+}
+
+impl Display for ParseHandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "card {} at column {}: {}",
+            self.index + 1,
+            self.column,
+            self.error
+        )
+    }
+}
+
+impl std::error::Error for ParseHandError {}
+
+// Suit letter, name or symbol, black or white
+impl FromStr for Suit {
+    type Err = ParseCardError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.to_ascii_lowercase().as_str() {
+            "c" | "clubs" | "\u{2663}" | "\u{2667}" => Ok(Suit::Clubs),
+            "h" | "hearts" | "\u{2665}" | "\u{2661}" => Ok(Suit::Hearts),
+            "d" | "diamonds" | "\u{2666}" | "\u{2662}" => Ok(Suit::Diamonds),
+            "s" | "spades" | "\u{2660}" | "\u{2664}" => Ok(Suit::Spades),
+            _ => Err(ParseCardError::InvalidSuit(text.to_string())),
+        }
+    }
+}
+
+// Rank as shown by `rank_fmt`, or `T` for ten
+impl FromStr for Rank {
+    type Err = ParseCardError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.to_ascii_uppercase().as_str() {
+            "A" => Ok(Rank::Ace),
+            "K" => Ok(Rank::King),
+            "Q" => Ok(Rank::Queen),
+            "J" => Ok(Rank::Jack),
+            "T" | "10" => Ok(Rank::Ten),
+            "D" => Ok(Rank::Joker),
+            number @ ("2" | "3" | "4" | "5" | "6" | "7" | "8" | "9") => {
+                Ok(Rank::try_from(number.as_bytes()[0] - b'0').unwrap())
+            }
+            _ => Err(ParseCardError::InvalidRank(text.to_string())),
+        }
+    }
+}
+
+// Card from a playing card symbol, see `Card::unicode`.
+// Jokers take the suit of their row, the white joker is of clubs.
+fn symbol_card(symbol: char) -> Result<Card, ParseCardError> {
+    let code = symbol as u32;
+    let suit = match code & !0xF {
+        0x1F0A0 => Suit::Spades,
+        0x1F0B0 => Suit::Hearts,
+        0x1F0C0 => Suit::Diamonds,
+        0x1F0D0 => Suit::Clubs,
+        _ => return Err(ParseCardError::InvalidSymbol(symbol)),
+    };
+    let rank = match code & 0xF {
+        0x1 => Rank::Ace,
+        value @ 0x2..=0xA => Rank::try_from(value as u8).unwrap(),
+        0xB => Rank::Jack,
+        0xD => Rank::Queen,
+        0xE => Rank::King,
+        0xF if suit != Suit::Spades => Rank::Joker,
+        _ => return Err(ParseCardError::InvalidSymbol(symbol)),
+    };
+    Ok(Card::new(suit, rank))
+}
+
+// Rank followed by suit, like `QS`, `10h`, `Td` or `7♣`,
+// or a single playing card symbol
+impl FromStr for Card {
+    type Err = ParseCardError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        let mut chars = text.chars();
+        let Some(last) = chars.next_back() else {
+            return Err(ParseCardError::Empty);
+        };
+        if chars.as_str().is_empty() && ('\u{1F0A0}'..='\u{1F0FF}').contains(&last) {
+            return symbol_card(last);
+        }
+
+        let rank = chars.as_str();
+        let suit = match last.to_string().parse::<Suit>() {
+            Ok(suit) if !rank.is_empty() => suit,
+            result => {
+                return Err(match text.parse::<Rank>() {
+                    Ok(_) => ParseCardError::MissingSuit(text.to_string()),
+                    Err(_) if result.is_ok() => ParseCardError::InvalidRank(text.to_string()),
+                    Err(err) => result.err().unwrap_or(err),
+                })
+            }
+        };
+        Ok(Card::new(suit, rank.parse()?))
+    }
+}
+
+// Parses cards separated by whitespace or commas
+pub fn parse_hand(text: &str) -> Result<Vec<Card>, ParseHandError> {
+    let mut cards = vec![];
+    let mut start = None;
+    // Separator closes the card, the extra one ends the text
+    let chars = text.char_indices().map(Some).chain([None]);
+
+    for item in chars {
+        let separator = match item {
+            Some((_, c)) => c.is_whitespace() || c == ',',
+            None => true,
+        };
+        match (start, item) {
+            (None, Some((offset, _))) if !separator => start = Some(offset),
+            (Some(offset), _) if separator => {
+                let end = item.map_or(text.len(), |(end, _)| end);
+                let card = text[offset..end].parse().map_err(|error| ParseHandError {
+                    index: cards.len(),
+                    column: text[..offset].chars().count() + 1,
+                    error,
+                })?;
+                cards.push(card);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    Ok(cards)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUITS: [Suit; 4] = [Suit::Clubs, Suit::Hearts, Suit::Diamonds, Suit::Spades];
+
+    fn all_cards() -> Vec<Card> {
+        let ranks = (0..=13).map(|value| Rank::try_from(value).unwrap());
+        ranks
+            .flat_map(|rank| SUITS.map(|suit| Card::new(suit, rank)))
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for card in all_cards() {
+            assert_eq!(card.to_string().parse(), Ok(card));
+            assert_eq!(card.rank.to_string().parse(), Ok(card.rank));
+            assert_eq!(card.suit.to_string().parse(), Ok(card.suit));
+            if card.rank != Rank::Joker || card.suit == Suit::Clubs {
+                assert_eq!(card.unicode().parse(), Ok(card));
+            }
+        }
+
+        let hand = all_cards();
+        let text: Vec<String> = hand.iter().map(|card| card.to_string()).collect();
+        assert_eq!(parse_hand(&text.join(" ")), Ok(hand));
+    }
+
+    #[test]
+    fn test_notation() {
+        let queen = Card::new(Suit::Spades, Rank::Queen);
+        let ten = Card::new(Suit::Hearts, Rank::Ten);
+        assert_eq!("QS".parse(), Ok(queen));
+        assert_eq!(" q\u{2664} ".parse(), Ok(queen));
+        assert_eq!("10h".parse(), Ok(ten));
+        assert_eq!("T\u{2661}".parse(), Ok(ten));
+        assert_eq!("td".parse(), Ok(Card::new(Suit::Diamonds, Rank::Ten)));
+        assert_eq!("DD".parse(), Ok(Card::new(Suit::Diamonds, Rank::Joker)));
+        assert_eq!(
+            "\u{1F0CF}".parse(),
+            Ok(Card::new(Suit::Diamonds, Rank::Joker))
+        );
+        assert_eq!("spades".parse(), Ok(Suit::Spades));
+        assert_eq!("t".parse(), Ok(Rank::Ten));
+
+        assert_eq!(
+            parse_hand(" 7\u{2663}, 7d,K\u{2660} "),
+            Ok(vec![
+                Card::new(Suit::Clubs, Rank::Seven),
+                Card::new(Suit::Diamonds, Rank::Seven),
+                Card::new(Suit::Spades, Rank::King),
+            ])
+        );
+        assert_eq!(parse_hand(" , "), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_errors() {
+        use ParseCardError::*;
+
+        let parse = |text: &str| text.parse::<Card>().unwrap_err();
+        assert_eq!(parse(" "), Empty);
+        assert_eq!(parse("Q"), MissingSuit("Q".to_string()));
+        assert_eq!(parse("10"), MissingSuit("10".to_string()));
+        assert_eq!(parse("1S"), InvalidRank("1".to_string()));
+        assert_eq!(parse("11h"), InvalidRank("11".to_string()));
+        assert_eq!(parse("Qx"), InvalidSuit("x".to_string()));
+        assert_eq!(parse("\u{1F0AC}"), InvalidSymbol('\u{1F0AC}'));
+        assert_eq!(parse("\u{1F0A0}"), InvalidSymbol('\u{1F0A0}'));
+        assert_eq!(parse("\u{1F0AF}"), InvalidSymbol('\u{1F0AF}'));
+        assert_eq!(parse("1S").to_string(), "invalid rank '1'");
+
+        let error = parse_hand("7\u{2663} 8\u{2663},  Kx").unwrap_err();
+        assert_eq!(
+            error,
+            ParseHandError {
+                index: 2,
+                column: 9,
+                error: InvalidSuit("x".to_string()),
+            }
+        );
+        assert_eq!(error.to_string(), "card 3 at column 9: invalid suit 'x'");
+        assert_eq!(Card::parse("Kx"), Err("invalid suit 'x'".to_string()));
+        assert_eq!(
+            Card::parse_hand("A\u{2660} 2"),
+            Err("card 2 at column 4: card '2' has no suit".to_string())
+        );
+    }
+}
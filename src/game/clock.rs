@@ -0,0 +1,228 @@
+/*
+ * Turn clocks.
+ *
+ * A move lasts from the moment a player comes in turn, or their
+ * attack or defense starts, until the turn passes on. Time is read from
+ * a `Clock` so games can be timed by hand in tests and replays.
+ */
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use wasm_bindgen::prelude::*;
+
+use crate::game::moska::State;
+
+pub trait Clock: Send + Sync {
+    // Milliseconds since a fixed point in time
+    fn now_ms(&self) -> u64;
+}
+
+// Wall clock of the host
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[cfg(target_arch = "wasm32")]
+    fn now_ms(&self) -> u64 {
+        js_sys::Date::now() as u64
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn now_ms(&self) -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_millis() as u64)
+            .unwrap_or_default()
+    }
+}
+
+// Clock that only moves when told to
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now_ms: AtomicU64,
+}
+
+impl ManualClock {
+    pub fn new(now_ms: u64) -> Self {
+        Self {
+            now_ms: AtomicU64::new(now_ms),
+        }
+    }
+
+    pub fn advance(&self, ms: u64) {
+        self.now_ms.fetch_add(ms, Ordering::Relaxed);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_ms(&self) -> u64 {
+        self.now_ms.load(Ordering::Relaxed)
+    }
+}
+
+// What happens to a player who runs out of time
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[wasm_bindgen]
+pub enum TimeoutPolicy {
+    // Submits the table if it holds, otherwise makes the simplest legal move:
+    // attacking with the lowest card or taking the attack
+    AutoSubmit,
+    // Defender takes the attack, attackers are auto-submitted
+    AutoTake,
+    // Player forfeits, see `ForfeitRules`
+    Forfeit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[wasm_bindgen]
+pub struct TimeControl {
+    // Time for each move in milliseconds, 0 for no limit
+    pub move_ms: u64,
+    // Time for all moves of a player in a round, 0 for no limit
+    pub game_ms: u64,
+    pub policy: TimeoutPolicy,
+}
+
+#[wasm_bindgen]
+impl TimeControl {
+    #[wasm_bindgen(constructor)]
+    pub fn new(move_ms: u64, game_ms: u64, policy: TimeoutPolicy) -> Self {
+        Self {
+            move_ms,
+            game_ms,
+            policy,
+        }
+    }
+}
+
+// Player who ran out of time and what was done about it
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[wasm_bindgen]
+pub struct Timeout {
+    pub player_index: usize,
+    pub policy: TimeoutPolicy,
+    pub turn: usize,
+}
+
+#[derive(Clone)]
+pub struct TurnClock {
+    clock: Arc<dyn Clock>,
+    pub control: TimeControl,
+    // Time each player has used in finished moves
+    used_ms: Vec<u64>,
+    move_start_ms: u64,
+    // Player in turn and their state when the move started
+    mover: Option<(usize, State)>,
+}
+
+impl TurnClock {
+    pub fn new(control: TimeControl, clock: Arc<dyn Clock>, players: usize) -> Self {
+        Self {
+            clock,
+            control,
+            used_ms: vec![0; players],
+            move_start_ms: 0,
+            mover: None,
+        }
+    }
+
+    // Clears the used time for a new round
+    pub(crate) fn reset(&mut self) {
+        self.used_ms.fill(0);
+        self.mover = None;
+    }
+
+    // Starts a new move when the player in turn or their state changes
+    pub(crate) fn update(&mut self, player_index: usize, state: State) {
+        let playing = matches!(state, State::PlayerAttacking | State::PlayerDefending);
+        let mover = playing.then_some((player_index, state));
+        if mover == self.mover {
+            return;
+        }
+
+        let now = self.clock.now_ms();
+        if let Some((player_index, _)) = self.mover {
+            self.used_ms[player_index] += now.saturating_sub(self.move_start_ms);
+        }
+        self.move_start_ms = now;
+        self.mover = mover;
+    }
+
+    // Time the player has used in the round, including the current move
+    pub fn used_ms(&self, player_index: usize) -> u64 {
+        let current = match self.mover {
+            Some((mover, _)) if mover == player_index => {
+                self.clock.now_ms().saturating_sub(self.move_start_ms)
+            }
+            _ => 0,
+        };
+        self.used_ms.get(player_index).copied().unwrap_or_default() + current
+    }
+
+    // Time left for the current move under both limits.
+    // Nothing when no move is timed.
+    pub fn time_left_ms(&self) -> Option<u64> {
+        let (player_index, _) = self.mover?;
+        let move_left = (self.control.move_ms > 0).then(|| {
+            let elapsed = self.clock.now_ms().saturating_sub(self.move_start_ms);
+            self.control.move_ms.saturating_sub(elapsed)
+        });
+        let game_left = (self.control.game_ms > 0).then(|| {
+            self.control
+                .game_ms
+                .saturating_sub(self.used_ms(player_index))
+        });
+
+        match (move_left, game_left) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (left, None) | (None, left) => left,
+        }
+    }
+
+    pub fn expired(&self) -> bool {
+        self.time_left_ms() == Some(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_turn_clock() {
+        let clock = Arc::new(ManualClock::new(1_000));
+        let control = TimeControl::new(500, 600, TimeoutPolicy::AutoSubmit);
+        let mut turn_clock = TurnClock::new(control, clock.clone(), 2);
+        assert_eq!(turn_clock.time_left_ms(), None);
+
+        turn_clock.update(0, State::PlayerAttacking);
+        clock.advance(300);
+        assert_eq!(turn_clock.time_left_ms(), Some(200));
+        assert_eq!(turn_clock.used_ms(0), 300);
+
+        // same move goes on
+        turn_clock.update(0, State::PlayerAttacking);
+        assert_eq!(turn_clock.time_left_ms(), Some(200));
+
+        turn_clock.update(1, State::PlayerDefending);
+        assert_eq!(turn_clock.time_left_ms(), Some(500));
+        clock.advance(100);
+
+        // round time runs out before the move time
+        turn_clock.update(0, State::PlayerAttacking);
+        clock.advance(200);
+        assert_eq!(turn_clock.used_ms(0), 500);
+        assert_eq!(turn_clock.time_left_ms(), Some(100));
+        clock.advance(150);
+        assert!(turn_clock.expired());
+        assert_eq!(turn_clock.used_ms(1), 100);
+
+        turn_clock.update(0, State::GameOver);
+        assert_eq!(turn_clock.time_left_ms(), None);
+        turn_clock.reset();
+        assert_eq!(turn_clock.used_ms(0), 0);
+    }
+}
@@ -0,0 +1,642 @@
+/*
+ * Compact Moska state for fast simulation.
+ *
+ * Cards are coded by their `CardSet` ordinal, so that a card beats the
+ * lower codes of its suit. Hands and table piles are `CardSet` masks and
+ * the deck is a fixed array, making the whole game a plain `Copy` value.
+ * Rules follow `Moska` without turn clocks.
+ */
+
+use rand::{seq::SliceRandom, RngCore};
+
+use crate::{
+    card_set::{card_ordinal, ordinal_card, CardSet},
+    deck::{Card, Deck},
+    game::{
+        engine::{loser_outcome, Engine},
+        moska::State,
+    },
+    Moska,
+};
+
+// Most players the 52 card deck can be dealt to
+pub const MAX_PLAYERS: usize = 8;
+
+const NUM_CARDS: usize = 52;
+const NO_CARD: u8 = u8::MAX;
+
+fn bit(code: u8) -> u64 {
+    1 << code
+}
+
+fn suit_mask(suit: u8) -> u64 {
+    0x1FFF << (suit * 13)
+}
+
+fn rank_mask(rank: u8) -> u64 {
+    (0..4).fold(0, |mask, suit| mask | bit(suit * 13 + rank))
+}
+
+// Codes of the cards in the mask, lowest first
+fn codes(mut mask: u64) -> impl Iterator<Item = u8> {
+    std::iter::from_fn(move || {
+        (mask != 0).then(|| {
+            let code = mask.trailing_zeros() as u8;
+            mask &= mask - 1;
+            code
+        })
+    })
+}
+
+fn count(mask: u64) -> usize {
+    mask.count_ones() as usize
+}
+
+fn mask_cards(mask: u64) -> Vec<Card> {
+    CardSet::from_mask(mask).iter().collect()
+}
+
+fn cards_mask(cards: &[Card]) -> u64 {
+    CardSet::from(cards).mask()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CompactAction {
+    // Puts the card from hand to the table
+    Add(u8),
+    // Takes the card from the table back to hand
+    TakeBack(u8),
+    Submit,
+    // Swaps the trump two in hand with the trump card
+    SwapTrump,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompactMoska {
+    pub hands: [u64; MAX_PLAYERS],
+    pub attacker_cards: u64,
+    pub defender_cards: u64,
+    pub discarded: u64,
+    // Deck from bottom to top
+    deck: [u8; NUM_CARDS],
+    deck_count: u8,
+    pub trump_card: u8,
+    pub num_players: u8,
+    // Seats that have left the game
+    forfeited: u8,
+    pub player_index: u8,
+    pub state: State,
+    pub turn: usize,
+    pub round: usize,
+}
+
+impl CompactMoska {
+    // Nothing when the game has more players than the deck can be dealt to
+    pub fn from_moska(game: &Moska) -> Option<Self> {
+        let players = &game.table.players;
+        if players.len() > MAX_PLAYERS {
+            return None;
+        }
+
+        let mut hands = [0; MAX_PLAYERS];
+        let mut forfeited = 0;
+        for (index, player) in players.iter().enumerate() {
+            hands[index] = cards_mask(&player.cards);
+            if player.forfeited {
+                forfeited |= 1 << index;
+            }
+        }
+
+        let mut deck = [NO_CARD; NUM_CARDS];
+        let deck_cards = game.table.deck.cards();
+        for (slot, card) in deck.iter_mut().zip(&deck_cards) {
+            *slot = card_ordinal(card);
+        }
+
+        Some(Self {
+            hands,
+            attacker_cards: cards_mask(&game.attacker_cards),
+            defender_cards: cards_mask(&game.defender_cards),
+            discarded: cards_mask(&game.discarded),
+            deck,
+            deck_count: deck_cards.len() as u8,
+            trump_card: card_ordinal(&game.trump_card),
+            num_players: players.len() as u8,
+            forfeited,
+            player_index: game.table.player_index as u8,
+            state: game.state,
+            turn: game.table.turn,
+            round: game.table.round,
+        })
+    }
+
+    // Game with the same cards, cards in hand and on the table ordered by code.
+    // Seats are played by humans and the game is not timed.
+    pub fn to_moska(&self) -> Moska {
+        let mut game = Moska::new(self.num_players);
+        for (index, player) in game.table.players.iter_mut().enumerate() {
+            player.cards = mask_cards(self.hands[index]);
+            player.forfeited = self.forfeited & (1 << index) != 0;
+        }
+        game.table.deck =
+            Deck::from_cards(self.deck_codes().iter().map(|c| ordinal_card(*c)).collect());
+        game.table.player_index = self.player_index as usize;
+        game.table.turn = self.turn;
+        game.table.round = self.round;
+        game.trump_card = ordinal_card(self.trump_card);
+        game.attacker_cards = mask_cards(self.attacker_cards);
+        game.defender_cards = mask_cards(self.defender_cards);
+        game.discarded = mask_cards(self.discarded);
+        game.state = self.state;
+        game.rehash();
+        game
+    }
+
+    // Deck from bottom to top
+    pub fn deck_codes(&self) -> &[u8] {
+        &self.deck[..self.deck_count as usize]
+    }
+
+    fn trump_suit(&self) -> u8 {
+        self.trump_card / 13
+    }
+
+    // Finds next player with cards left, skipping forfeited seats
+    pub fn next_player(&self) -> usize {
+        let num_players = self.num_players as usize;
+        let current = self.player_index as usize;
+        let mut player_index = (current + 1) % num_players;
+
+        while self.hands[player_index] == 0 || self.forfeited & (1 << player_index) != 0 {
+            if player_index == current {
+                break;
+            }
+            player_index = (player_index + 1) % num_players;
+        }
+        player_index
+    }
+
+    // Plays the action for the player in turn without checking
+    // it against `legal_actions`. Returns false when nothing changed.
+    pub fn play(&mut self, action: CompactAction) -> bool {
+        let player_index = self.player_index as usize;
+        let playing = matches!(self.state, State::PlayerAttacking | State::PlayerDefending);
+        if !playing {
+            return false;
+        }
+
+        match action {
+            CompactAction::Add(code) if self.hands[player_index] & bit(code) != 0 => {
+                self.hands[player_index] &= !bit(code);
+                *self.table_cards() |= bit(code);
+                true
+            }
+            CompactAction::TakeBack(code) if *self.table_cards() & bit(code) != 0 => {
+                *self.table_cards() &= !bit(code);
+                self.hands[player_index] |= bit(code);
+                true
+            }
+            CompactAction::Submit => self.submit(),
+            CompactAction::SwapTrump => {
+                let two = bit(self.trump_suit() * 13);
+                if self.deck_count == 0 || self.hands[player_index] & two == 0 {
+                    return false;
+                }
+                self.hands[player_index] ^= two | bit(self.trump_card);
+                self.trump_card = self.trump_suit() * 13;
+                self.deck[0] = self.trump_card;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn submit(&mut self) -> bool {
+        let player_index = self.player_index as usize;
+        match self.state {
+            State::PlayerDefending if self.defender_cards == 0 => {
+                // Take all the attacking cards and continue to next turn
+                self.hands[player_index] |= self.attacker_cards;
+                self.attacker_cards = 0;
+                self.draw_cards();
+                self.next_turn();
+                true
+            }
+            State::PlayerDefending if self.eval_defense() => {
+                self.discarded |= self.attacker_cards | self.defender_cards;
+                self.attacker_cards = 0;
+                self.defender_cards = 0;
+                self.draw_cards();
+                self.state = State::PlayerAttacking;
+                if self.hands[player_index] == 0 {
+                    self.next_turn();
+                }
+                true
+            }
+            State::PlayerAttacking if self.eval_attack() => {
+                self.draw_cards();
+                self.next_turn();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn table_cards(&mut self) -> &mut u64 {
+        match self.state {
+            State::PlayerDefending => &mut self.defender_cards,
+            _ => &mut self.attacker_cards,
+        }
+    }
+
+    fn draw_cards(&mut self) {
+        let hand = &mut self.hands[self.player_index as usize];
+        while count(*hand) < 6 && self.deck_count > 0 {
+            self.deck_count -= 1;
+            let code = std::mem::replace(&mut self.deck[self.deck_count as usize], NO_CARD);
+            *hand |= bit(code);
+        }
+    }
+
+    fn next_turn(&mut self) {
+        let players_with_cards = self.hands[..self.num_players as usize]
+            .iter()
+            .filter(|hand| **hand != 0)
+            .count();
+        if players_with_cards == 1 {
+            self.state = State::GameOver;
+            return;
+        }
+
+        self.state = match self.attacker_cards {
+            0 => State::PlayerAttacking,
+            _ => State::PlayerDefending,
+        };
+        self.player_index = self.next_player() as u8;
+        self.turn += 1;
+    }
+
+    // Cards on the table can be submitted
+    pub fn valid(&self) -> bool {
+        match self.state {
+            State::PlayerAttacking => self.eval_attack(),
+            State::PlayerDefending => self.defender_cards == 0 || self.eval_defense(),
+            State::GameOver => true,
+            _ => false,
+        }
+    }
+
+    // Single card or paired cards, no more than the next player holds
+    fn eval_attack(&self) -> bool {
+        let attack = self.attacker_cards;
+        let cards = count(attack);
+        if cards == 0 || cards > count(self.hands[self.next_player()]) {
+            return false;
+        }
+        cards == 1 || (0..13).all(|rank| count(attack & rank_mask(rank)) != 1)
+    }
+
+    fn eval_defense(&self) -> bool {
+        count(self.attacker_cards) == count(self.defender_cards)
+            && Cover::new(self.attacker_cards, self.trump_suit()).cover(self.defender_cards, 0)
+    }
+
+    pub fn loser(&self) -> Option<usize> {
+        if self.state != State::GameOver {
+            return None;
+        }
+        let players = 0..self.num_players as usize;
+        players
+            .clone()
+            .find(|index| self.forfeited & (1 << index) != 0 && self.hands[*index] != 0)
+            .or_else(|| players.clone().find(|index| self.hands[*index] != 0))
+    }
+
+    // Legal actions as in `Moska`, written to the buffer to avoid allocating
+    pub fn legal_actions_into(&self, actions: &mut Vec<CompactAction>) {
+        actions.clear();
+        let hand = self.hands[self.player_index as usize];
+        let trump_suit = self.trump_suit();
+
+        match self.state {
+            State::PlayerAttacking => {
+                let attack = self.attacker_cards;
+                let room = count(self.hands[self.next_player()]).saturating_sub(count(attack));
+                let unpaired = (0..13).find(|rank| count(attack & rank_mask(*rank)) == 1);
+
+                let mut allowed = 0;
+                if attack == 0 {
+                    allowed = if room >= 1 { hand } else { 0 };
+                } else if let Some(rank) = unpaired {
+                    if room >= 1 {
+                        allowed = hand & rank_mask(rank);
+                    }
+                } else {
+                    for rank in 0..13 {
+                        let in_hand = hand & rank_mask(rank);
+                        let on_table = attack & rank_mask(rank) != 0;
+                        if (on_table && room >= 1) || (count(in_hand) >= 2 && room >= 2) {
+                            allowed |= in_hand;
+                        }
+                    }
+                }
+                actions.extend(codes(allowed).map(CompactAction::Add));
+            }
+            State::PlayerDefending => {
+                let cover = Cover::new(self.attacker_cards, trump_suit);
+                if count(self.defender_cards) < count(self.attacker_cards) {
+                    for code in codes(hand & cover.beaters_of_all()) {
+                        let forced = self.defender_cards | bit(code);
+                        if cover.cover(forced, hand & !bit(code)) {
+                            actions.push(CompactAction::Add(code));
+                        }
+                    }
+                }
+                // Covering can't be finished, give a card back
+                if actions.is_empty() && self.defender_cards != 0 && !self.valid() {
+                    let code = self.defender_cards.trailing_zeros() as u8;
+                    actions.push(CompactAction::TakeBack(code));
+                }
+            }
+            _ => return,
+        }
+
+        if self.valid() {
+            actions.push(CompactAction::Submit);
+        }
+
+        let table_empty = *match self.state {
+            State::PlayerAttacking => &self.attacker_cards,
+            _ => &self.defender_cards,
+        } == 0;
+        if table_empty && hand & bit(trump_suit * 13) != 0 && self.deck_count > 0 {
+            actions.push(CompactAction::SwapTrump);
+        }
+    }
+}
+
+// Matches attacking cards with cards beating them
+#[derive(Clone, Copy)]
+struct Cover {
+    attacker_cards: u64,
+    trump_suit: u8,
+    // Covering card of each attacking card and the other way round
+    covered_by: [u8; NUM_CARDS],
+    covers: [u8; NUM_CARDS],
+    visited: u64,
+}
+
+impl Cover {
+    fn new(attacker_cards: u64, trump_suit: u8) -> Self {
+        Self {
+            attacker_cards,
+            trump_suit,
+            covered_by: [NO_CARD; NUM_CARDS],
+            covers: [NO_CARD; NUM_CARDS],
+            visited: 0,
+        }
+    }
+
+    // Cards beating the attacking card
+    fn beaters(&self, code: u8) -> u64 {
+        let suit = code / 13;
+        let higher = suit_mask(suit) & (u64::MAX << (code + 1));
+        match suit == self.trump_suit {
+            true => higher,
+            false => higher | suit_mask(self.trump_suit),
+        }
+    }
+
+    // Attacking cards the card beats
+    fn beaten_by(&self, code: u8) -> u64 {
+        let suit = code / 13;
+        let lower = suit_mask(suit) & (bit(code) - 1);
+        let others = match suit == self.trump_suit {
+            true => !suit_mask(suit),
+            false => 0,
+        };
+        (lower | others) & self.attacker_cards
+    }
+
+    fn beaters_of_all(&self) -> u64 {
+        codes(self.attacker_cards).fold(0, |mask, code| mask | self.beaters(code))
+    }
+
+    // Every attacking card can be covered using all of the forced cards
+    // and any of the extra cards
+    fn cover(mut self, forced: u64, extra: u64) -> bool {
+        if count(forced) > count(self.attacker_cards) {
+            return false;
+        }
+        // Matched cards stay matched while augmenting, so the forced
+        // cards are matched first and the rest of the attack after them
+        for code in codes(forced) {
+            self.visited = 0;
+            if !self.match_defender(code) {
+                return false;
+            }
+        }
+        for code in codes(self.attacker_cards) {
+            self.visited = 0;
+            if self.covered_by[code as usize] == NO_CARD
+                && !self.match_attacker(code, forced | extra)
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn match_defender(&mut self, code: u8) -> bool {
+        for attack in codes(self.beaten_by(code) & !self.visited) {
+            self.visited |= bit(attack);
+            let previous = self.covered_by[attack as usize];
+            if previous == NO_CARD || self.match_defender(previous) {
+                self.covered_by[attack as usize] = code;
+                self.covers[code as usize] = attack;
+                return true;
+            }
+        }
+        false
+    }
+
+    fn match_attacker(&mut self, code: u8, cards: u64) -> bool {
+        for defense in codes(self.beaters(code) & cards & !self.visited) {
+            self.visited |= bit(defense);
+            let previous = self.covers[defense as usize];
+            if previous == NO_CARD || self.match_attacker(previous, cards) {
+                self.covers[defense as usize] = code;
+                self.covered_by[code as usize] = defense;
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Engine for CompactMoska {
+    type Action = CompactAction;
+
+    fn num_players(&self) -> usize {
+        self.num_players as usize
+    }
+
+    fn current_player(&self) -> usize {
+        self.player_index as usize
+    }
+
+    fn legal_actions(&self) -> Vec<CompactAction> {
+        let mut actions = vec![];
+        self.legal_actions_into(&mut actions);
+        actions
+    }
+
+    fn apply(&mut self, action: &CompactAction) -> bool {
+        self.legal_actions().contains(action) && self.play(*action)
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.state == State::GameOver
+    }
+
+    fn outcome(&self) -> Option<Vec<f32>> {
+        self.is_terminal()
+            .then(|| loser_outcome(self.num_players as usize, self.loser()))
+    }
+
+    // Trump card at the bottom of the deck is in sight
+    fn determinize(&self, player_index: usize, rng: &mut dyn RngCore) -> Self {
+        let mut game = *self;
+        let mut hidden = [NO_CARD; NUM_CARDS];
+        let mut len = 0;
+
+        let deck_count = self.deck_count as usize;
+        for code in self.deck.iter().take(deck_count).skip(1) {
+            hidden[len] = *code;
+            len += 1;
+        }
+        for index in (0..self.num_players as usize).filter(|index| *index != player_index) {
+            for code in codes(self.hands[index]) {
+                hidden[len] = code;
+                len += 1;
+            }
+        }
+        hidden[..len].shuffle(rng);
+
+        let mut next = 0;
+        if deck_count > 0 {
+            game.deck[1..deck_count].copy_from_slice(&hidden[..deck_count - 1]);
+            next = deck_count - 1;
+        }
+        for index in (0..self.num_players as usize).filter(|index| *index != player_index) {
+            let cards = count(self.hands[index]);
+            game.hands[index] = hidden[next..next + cards]
+                .iter()
+                .fold(0, |mask, code| mask | bit(*code));
+            next += cards;
+        }
+        game
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::moska::{Action, MoskaAI};
+    use crate::game::testing::play_out;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // Same action on the compact game
+    fn compact_action(game: &Moska, action: &Action) -> CompactAction {
+        let player = &game.table.players[game.table.player_index];
+        let table = match game.state {
+            State::PlayerDefending => &game.defender_cards,
+            _ => &game.attacker_cards,
+        };
+        match action.action {
+            1 => CompactAction::Add(card_ordinal(&player.cards[action.card_index])),
+            2 => CompactAction::TakeBack(card_ordinal(&table[action.card_index])),
+            3 => CompactAction::Submit,
+            _ => CompactAction::SwapTrump,
+        }
+    }
+
+    #[test]
+    fn test_conversion() {
+        let mut game = Moska::new(4);
+        game.new_round_with_seed(3);
+        game.player_action(1, 0);
+        game.player_action(3, 0);
+        game.forfeit(3);
+
+        let compact = CompactMoska::from_moska(&game).unwrap();
+        let converted = compact.to_moska();
+        assert_eq!(CompactMoska::from_moska(&converted), Some(compact));
+        assert_eq!(converted.table.deck.cards(), game.table.deck.cards());
+        assert_eq!(converted.attacker_cards, game.attacker_cards);
+        assert!(converted.table.players[3].forfeited);
+        assert_eq!(converted.next_player(), game.next_player());
+
+        assert_eq!(CompactMoska::from_moska(&Moska::new(9)), None);
+    }
+
+    #[test]
+    fn test_same_rules() {
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for seed in 0..20 {
+            let mut game = Moska::new(2 + seed as u8 % 4);
+            game.new_round_with_seed(seed);
+            let mut compact = CompactMoska::from_moska(&game).unwrap();
+
+            // Heuristic turns with random moves in between
+            assert!(play_out(&mut game, |game| {
+                let actions = match rng.gen_bool(0.5) {
+                    true => MoskaAI::new(game.table.player_index).get_actions(game),
+                    false => {
+                        let actions = game.legal_actions();
+                        vec![actions[rng.gen_range(0..actions.len())]]
+                    }
+                };
+                for action in actions.iter().chain(&[Action {
+                    action: 3,
+                    card_index: 0,
+                }]) {
+                    let compact_action = compact_action(game, action);
+                    game.player_action(action.action, action.card_index);
+                    compact.play(compact_action);
+                    assert_eq!(CompactMoska::from_moska(game), Some(compact));
+                }
+            }));
+            assert_eq!(compact.state, State::GameOver);
+            assert_eq!(compact.loser(), game.loser());
+        }
+    }
+
+    #[test]
+    fn test_determinize() {
+        let mut game = Moska::new(3);
+        game.new_round_with_seed(5);
+        let compact = CompactMoska::from_moska(&game).unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let copy = compact.determinize(1, &mut rng);
+        assert_eq!(copy.hands[1], compact.hands[1]);
+        assert_ne!(copy.hands[0], compact.hands[0]);
+        assert_eq!(copy.deck_codes()[0], compact.deck_codes()[0]);
+        assert_eq!(copy.deck_count, compact.deck_count);
+
+        let all = |game: &CompactMoska| {
+            game.hands.iter().fold(0, |mask, hand| mask | hand)
+                | game
+                    .deck_codes()
+                    .iter()
+                    .fold(0, |mask, code| mask | bit(*code))
+        };
+        assert_eq!(all(&copy), all(&compact));
+        assert_eq!(count(all(&copy)), NUM_CARDS);
+        for index in 0..3 {
+            assert_eq!(count(copy.hands[index]), count(compact.hands[index]));
+        }
+    }
+}
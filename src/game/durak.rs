@@ -0,0 +1,658 @@
+/*
+ * Game logic for Durak
+ *
+ * Podkidnoy: the other players may throw in cards of the ranks
+ * already on the table. Perevodnoy: before covering anything the
+ * defender may also pass the attack on with a card of the same rank.
+ *
+ * Players act one at a time. The defender covers the attacking cards
+ * in order, and attackers throw in or pass once the table is covered.
+ * Taking picks up the table at once.
+ */
+
+use rand::RngCore;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    deck::{Card, Deck, Rank, Suit},
+    game::{
+        engine::{loser_outcome, redeal, Engine},
+        moska::{card_beats, card_rank_order, State},
+    },
+    table::Table,
+};
+
+// Most cards attacked with in a bout
+const MAX_ATTACK: usize = 6;
+
+// The 36 card deck deals six cards to at most six players
+pub const MAX_PLAYERS: u8 = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[wasm_bindgen]
+pub enum DurakVariant {
+    Podkidnoy,
+    Perevodnoy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DurakAction {
+    // Leads or throws in the card at the hand index
+    Attack(usize),
+    // Covers the first uncovered attacking card with the card at the hand index
+    Defend(usize),
+    // Passes the attack on with the card at the hand index
+    Transfer(usize),
+    // Defender picks up the table
+    Take,
+    // Attacker throws in no more cards
+    Pass,
+}
+
+#[derive(Clone)]
+#[wasm_bindgen(getter_with_clone)]
+pub struct Durak {
+    pub table: Table,
+    pub trump_card: Card,
+    pub variant: DurakVariant,
+
+    // Cards attacked with in the bout
+    pub attack_cards: Vec<Card>,
+    // Cards covering the attacking cards in the same order
+    pub defense_cards: Vec<Card>,
+    pub discarded: Vec<Card>,
+
+    pub state: State,
+
+    // Player who led the bout
+    pub attacker_index: usize,
+    pub defender_index: usize,
+
+    // Attackers who passed since the table was last covered
+    passes: usize,
+}
+
+#[wasm_bindgen]
+impl Durak {
+    // Fails for more players than the deck can be dealt to
+    #[wasm_bindgen(constructor)]
+    pub fn new(players: u8, variant: DurakVariant) -> Result<Durak, String> {
+        if !(2..=MAX_PLAYERS).contains(&players) {
+            return Err(format!("Durak is played by 2 to {MAX_PLAYERS} players"));
+        }
+
+        let mut table = Table::new(players);
+        table.deck = Deck::new_short();
+
+        Ok(Self {
+            table,
+            trump_card: Card::new(Suit::Hearts, Rank::Six),
+            variant,
+            attack_cards: vec![],
+            defense_cards: vec![],
+            discarded: vec![],
+            state: State::Initial,
+            attacker_index: 0,
+            defender_index: 0,
+            passes: 0,
+        })
+    }
+
+    pub fn new_round(&mut self) {
+        self.start_round(None);
+    }
+
+    // Starts a new round with the deck shuffled from the seed
+    pub fn new_round_with_seed(&mut self, seed: u64) {
+        self.start_round(Some(seed));
+    }
+
+    fn start_round(&mut self, seed: Option<u64>) {
+        self.table.round += 1;
+        self.table.reset();
+        self.table.deck = Deck::new_short();
+        match seed {
+            Some(seed) => self.table.deck.shuffle_with_seed(seed),
+            None => self.table.deck.shuffle(),
+        }
+        self.attack_cards.clear();
+        self.defense_cards.clear();
+        self.discarded.clear();
+
+        // Deal 6 cards for each player
+        let mut last_dealt = None;
+        for player in &mut self.table.players {
+            for _ in 0..6 {
+                if let Some(card) = self.table.deck.pop() {
+                    player.cards.push(card);
+                    last_dealt = Some(card);
+                }
+            }
+        }
+        // With six players the whole deck is dealt and the last card
+        // dealt shows the trump
+        self.trump_card = self
+            .table
+            .deck
+            .peek_last()
+            .copied()
+            .or(last_dealt)
+            .unwrap_or(self.trump_card);
+
+        // Player with the lowest trump leads
+        let trump_suit = self.trump_card.suit;
+        let first = self
+            .table
+            .players
+            .iter()
+            .enumerate()
+            .flat_map(|(index, player)| player.cards.iter().map(move |card| (index, card)))
+            .filter(|(_, card)| card.suit == trump_suit)
+            .min_by_key(|(_, card)| card_rank_order(card.rank))
+            .map(|(index, _)| index)
+            .unwrap_or_default();
+
+        self.start_bout(first);
+    }
+
+    pub fn attack(&mut self, card_index: usize) -> bool {
+        self.apply(DurakAction::Attack(card_index))
+    }
+
+    pub fn defend(&mut self, card_index: usize) -> bool {
+        self.apply(DurakAction::Defend(card_index))
+    }
+
+    pub fn transfer(&mut self, card_index: usize) -> bool {
+        self.apply(DurakAction::Transfer(card_index))
+    }
+
+    pub fn take(&mut self) -> bool {
+        self.apply(DurakAction::Take)
+    }
+
+    pub fn pass(&mut self) -> bool {
+        self.apply(DurakAction::Pass)
+    }
+
+    // Returns copy of player cards
+    pub fn player_cards(&self, player_index: usize) -> Vec<Card> {
+        self.table
+            .players
+            .get(player_index)
+            .map(|player| player.cards.clone())
+            .unwrap_or_default()
+    }
+
+    // Player left holding cards when the game is over, nothing on a draw
+    pub fn loser(&self) -> Option<usize> {
+        if self.state != State::GameOver {
+            return None;
+        }
+        self.table
+            .players
+            .iter()
+            .position(|player| !player.cards.is_empty())
+    }
+}
+
+impl Durak {
+    // Plays the action for the player in turn.
+    // Returns false and changes nothing when it is not legal.
+    pub fn apply(&mut self, action: DurakAction) -> bool {
+        if !self.is_legal(action) {
+            return false;
+        }
+
+        let player_index = self.table.player_index;
+        match action {
+            DurakAction::Attack(card_index) => {
+                let card = self.table.players[player_index].cards.remove(card_index);
+                self.attack_cards.push(card);
+                self.state = State::PlayerDefending;
+                self.table.next_turn(self.defender_index);
+            }
+            DurakAction::Defend(card_index) => {
+                let card = self.table.players[player_index].cards.remove(card_index);
+                self.defense_cards.push(card);
+                if self.uncovered() == 0 {
+                    self.passes = 0;
+                    self.next_attacker();
+                }
+            }
+            DurakAction::Transfer(card_index) => {
+                let card = self.table.players[player_index].cards.remove(card_index);
+                self.attack_cards.push(card);
+                self.attacker_index = self.defender_index;
+                self.defender_index = self.next_with_cards(self.defender_index);
+                self.table.next_turn(self.defender_index);
+            }
+            DurakAction::Take => {
+                let defender = &mut self.table.players[self.defender_index];
+                defender.cards.append(&mut self.attack_cards);
+                defender.cards.append(&mut self.defense_cards);
+                self.end_bout(false);
+            }
+            DurakAction::Pass => {
+                self.passes += 1;
+                self.next_attacker();
+            }
+        }
+        true
+    }
+
+    pub fn is_legal(&self, action: DurakAction) -> bool {
+        let Some(player) = self.table.players.get(self.table.player_index) else {
+            return false;
+        };
+        let card = |card_index: usize| player.cards.get(card_index);
+
+        match (self.state, action) {
+            (State::PlayerAttacking, DurakAction::Attack(card_index)) => {
+                card(card_index).is_some_and(|card| self.can_throw_in(card))
+            }
+            (State::PlayerAttacking, DurakAction::Pass) => !self.attack_cards.is_empty(),
+            (State::PlayerDefending, DurakAction::Defend(card_index)) => {
+                match (
+                    self.attack_cards.get(self.defense_cards.len()),
+                    card(card_index),
+                ) {
+                    (Some(attack), Some(card)) => card_beats(attack, card, self.trump_card.suit),
+                    _ => false,
+                }
+            }
+            (State::PlayerDefending, DurakAction::Transfer(card_index)) => {
+                let next = self.next_with_cards(self.defender_index);
+                self.variant == DurakVariant::Perevodnoy
+                    && self.defense_cards.is_empty()
+                    && next != self.defender_index
+                    && self.table.players[next].cards.len() > self.attack_cards.len()
+                    && card(card_index).is_some_and(|card| {
+                        self.attack_cards
+                            .iter()
+                            .all(|attack| attack.rank == card.rank)
+                    })
+            }
+            (State::PlayerDefending, DurakAction::Take) => true,
+            _ => false,
+        }
+    }
+
+    // Legal actions of the player in turn
+    pub fn legal_actions(&self) -> Vec<DurakAction> {
+        let cards = self
+            .table
+            .players
+            .get(self.table.player_index)
+            .map(|player| player.cards.len())
+            .unwrap_or_default();
+
+        (0..cards)
+            .flat_map(|index| {
+                [
+                    DurakAction::Attack(index),
+                    DurakAction::Defend(index),
+                    DurakAction::Transfer(index),
+                ]
+            })
+            .chain([DurakAction::Take, DurakAction::Pass])
+            .filter(|action| self.is_legal(*action))
+            .collect()
+    }
+
+    fn uncovered(&self) -> usize {
+        self.attack_cards.len() - self.defense_cards.len()
+    }
+
+    // Card can lead the bout or matches a rank on the table,
+    // and the defender has a card left to cover it
+    fn can_throw_in(&self, card: &Card) -> bool {
+        let matches_table = self.attack_cards.is_empty()
+            || self
+                .attack_cards
+                .iter()
+                .chain(&self.defense_cards)
+                .any(|table_card| table_card.rank == card.rank);
+
+        matches_table
+            && self.attack_cards.len() < MAX_ATTACK
+            && self.uncovered() < self.table.players[self.defender_index].cards.len()
+    }
+
+    // Finds next player with cards left
+    fn next_with_cards(&self, player_index: usize) -> usize {
+        let num_players = self.table.players.len();
+        (1..=num_players)
+            .map(|offset| (player_index + offset) % num_players)
+            .find(|index| !self.table.players[*index].cards.is_empty())
+            .unwrap_or(player_index)
+    }
+
+    // Players who may throw in, starting from the one who led
+    fn attackers(&self) -> Vec<usize> {
+        let num_players = self.table.players.len();
+        (0..num_players)
+            .map(|offset| (self.attacker_index + offset) % num_players)
+            .filter(|index| {
+                *index != self.defender_index && !self.table.players[*index].cards.is_empty()
+            })
+            .collect()
+    }
+
+    // Gives the covered table to the attackers in turn,
+    // or ends the bout once all of them have passed
+    fn next_attacker(&mut self) {
+        let attackers = self.attackers();
+        let can_throw_in = attackers.iter().any(|index| {
+            self.table.players[*index]
+                .cards
+                .iter()
+                .any(|card| self.can_throw_in(card))
+        });
+
+        if self.passes >= attackers.len() || !can_throw_in {
+            return self.end_bout(true);
+        }
+
+        let next = attackers
+            .iter()
+            .position(|index| *index == self.table.player_index)
+            .map(|position| attackers[(position + 1) % attackers.len()])
+            .filter(|_| self.state == State::PlayerAttacking)
+            .unwrap_or(attackers[0]);
+        self.state = State::PlayerAttacking;
+        self.table.next_turn(next);
+    }
+
+    fn end_bout(&mut self, defended: bool) {
+        if defended {
+            self.discarded.append(&mut self.attack_cards);
+            self.discarded.append(&mut self.defense_cards);
+        }
+
+        // Attackers draw in order, the defender last
+        let num_players = self.table.players.len();
+        let draw_order = (0..num_players)
+            .map(|offset| (self.attacker_index + offset) % num_players)
+            .filter(|index| *index != self.defender_index)
+            .chain([self.defender_index]);
+        for index in draw_order {
+            let player = &mut self.table.players[index];
+            while player.cards.len() < 6 {
+                match self.table.deck.pop() {
+                    Some(card) => player.cards.push(card),
+                    None => break,
+                }
+            }
+        }
+
+        let players_with_cards = self
+            .table
+            .players
+            .iter()
+            .filter(|player| !player.cards.is_empty())
+            .count();
+        if players_with_cards <= 1 {
+            self.state = State::GameOver;
+            return;
+        }
+
+        // Defender leads the next bout unless they took the cards
+        let next = match defended && !self.table.players[self.defender_index].cards.is_empty() {
+            true => self.defender_index,
+            false => self.next_with_cards(self.defender_index),
+        };
+        self.start_bout(next);
+    }
+
+    fn start_bout(&mut self, attacker_index: usize) {
+        self.attacker_index = attacker_index;
+        self.defender_index = self.next_with_cards(attacker_index);
+        self.passes = 0;
+        self.state = State::PlayerAttacking;
+        self.table.next_turn(attacker_index);
+    }
+}
+
+impl Engine for Durak {
+    type Action = DurakAction;
+
+    fn num_players(&self) -> usize {
+        self.table.players.len()
+    }
+
+    fn current_player(&self) -> usize {
+        self.table.player_index
+    }
+
+    fn legal_actions(&self) -> Vec<DurakAction> {
+        Durak::legal_actions(self)
+    }
+
+    fn apply(&mut self, action: &DurakAction) -> bool {
+        Durak::apply(self, *action)
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.state == State::GameOver
+    }
+
+    fn outcome(&self) -> Option<Vec<f32>> {
+        self.is_terminal()
+            .then(|| loser_outcome(self.table.players.len(), self.loser()))
+    }
+
+    // Trump card at the bottom of the deck is in sight
+    fn determinize(&self, player_index: usize, rng: &mut dyn RngCore) -> Self {
+        let mut game = self.clone();
+        redeal(&mut game.table, player_index, true, rng);
+        game
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ai::durak_ai::DurakAI,
+        game::testing::{self, card, deal, play_out},
+    };
+
+    // Game with the given hands, trump suit and deck from bottom to top
+    fn game(variant: DurakVariant, hands: Vec<Vec<Card>>, deck: Vec<Card>) -> Durak {
+        let mut game = Durak::new(hands.len() as u8, variant).unwrap();
+        game.trump_card = deck
+            .first()
+            .copied()
+            .unwrap_or(card(Rank::Six, Suit::Spades));
+        deal(&mut game.table, hands, deck);
+        game.start_bout(0);
+        game
+    }
+
+    fn count_cards(game: &Durak) -> usize {
+        let piles = [&game.attack_cards, &game.defense_cards, &game.discarded];
+        testing::count_cards(&game.table, &piles.map(Vec::as_slice))
+    }
+
+    #[test]
+    fn test_deal() {
+        let mut game = Durak::new(3, DurakVariant::Podkidnoy).unwrap();
+        game.new_round_with_seed(1);
+
+        assert_eq!(game.table.deck.count(), 18);
+        assert_eq!(count_cards(&game), 36);
+        assert_eq!(game.state, State::PlayerAttacking);
+        assert_eq!(game.table.player_index, game.attacker_index);
+        assert_eq!(game.defender_index, (game.attacker_index + 1) % 3);
+
+        // leader holds the lowest trump
+        let lowest_trump = |index: usize| {
+            game.player_cards(index)
+                .iter()
+                .filter(|card| card.suit == game.trump_card.suit)
+                .map(|card| card_rank_order(card.rank))
+                .min()
+        };
+        let leader = lowest_trump(game.attacker_index);
+        assert!((0..3).all(|index| lowest_trump(index).is_none() || lowest_trump(index) >= leader));
+    }
+
+    #[test]
+    fn test_bout() {
+        let mut game = game(
+            DurakVariant::Podkidnoy,
+            vec![
+                vec![
+                    card(Rank::Seven, Suit::Clubs),
+                    card(Rank::Seven, Suit::Hearts),
+                ],
+                vec![
+                    card(Rank::Eight, Suit::Clubs),
+                    card(Rank::Ace, Suit::Spades),
+                ],
+                vec![
+                    card(Rank::Eight, Suit::Diamonds),
+                    card(Rank::Nine, Suit::Diamonds),
+                ],
+            ],
+            vec![
+                card(Rank::Six, Suit::Spades),
+                card(Rank::Ten, Suit::Diamonds),
+                card(Rank::Ten, Suit::Hearts),
+            ],
+        );
+
+        // nothing to pass on before leading
+        assert!(!game.pass());
+        assert!(!game.defend(0));
+        assert!(game.attack(0));
+        assert_eq!(game.table.player_index, 1);
+        assert_eq!(
+            game.legal_actions(),
+            vec![
+                DurakAction::Defend(0),
+                DurakAction::Defend(1),
+                DurakAction::Take
+            ]
+        );
+
+        assert!(game.defend(0));
+        assert_eq!(game.state, State::PlayerAttacking);
+        assert_eq!(game.table.player_index, 0);
+
+        // sevens and eights may be thrown in by anyone but the defender
+        assert!(game.pass());
+        assert_eq!(game.table.player_index, 2);
+        assert!(!game.attack(1));
+        assert!(game.attack(0));
+        assert!(game.defend(0));
+
+        // table is covered and the defender is out of cards
+        assert_eq!(game.discarded.len(), 4);
+        assert_eq!(game.table.player_index, 2);
+        assert_eq!(game.attacker_index, 2);
+        assert_eq!(game.defender_index, 0);
+
+        // leader draws first and empties the deck
+        assert_eq!(
+            game.player_cards(0),
+            vec![
+                card(Rank::Seven, Suit::Hearts),
+                card(Rank::Ten, Suit::Hearts),
+                card(Rank::Ten, Suit::Diamonds),
+                card(Rank::Six, Suit::Spades),
+            ]
+        );
+        assert_eq!(game.player_cards(2), vec![card(Rank::Nine, Suit::Diamonds)]);
+        assert!(game.player_cards(1).is_empty());
+    }
+
+    #[test]
+    fn test_take() {
+        let mut game = game(
+            DurakVariant::Podkidnoy,
+            vec![
+                vec![card(Rank::King, Suit::Clubs), card(Rank::Six, Suit::Hearts)],
+                vec![card(Rank::Eight, Suit::Clubs)],
+                vec![card(Rank::Nine, Suit::Diamonds)],
+            ],
+            vec![],
+        );
+
+        assert!(game.attack(0));
+        assert!(!game.defend(0));
+        assert!(game.take());
+        assert_eq!(game.player_cards(1).len(), 2);
+
+        // defender is skipped as the next attacker
+        assert_eq!(game.attacker_index, 2);
+        assert_eq!(game.defender_index, 0);
+        assert_eq!(game.state, State::PlayerAttacking);
+    }
+
+    #[test]
+    fn test_transfer() {
+        let hands = vec![
+            vec![card(Rank::Nine, Suit::Clubs)],
+            vec![card(Rank::Nine, Suit::Hearts), card(Rank::Six, Suit::Clubs)],
+            vec![card(Rank::Ten, Suit::Clubs), card(Rank::Jack, Suit::Hearts)],
+        ];
+
+        let mut podkidnoy = game(DurakVariant::Podkidnoy, hands.clone(), vec![]);
+        podkidnoy.attack(0);
+        assert!(!podkidnoy.transfer(0));
+
+        let mut game = game(DurakVariant::Perevodnoy, hands, vec![]);
+        game.attack(0);
+        assert!(!game.transfer(1));
+        assert!(game.transfer(0));
+        assert_eq!(game.attack_cards.len(), 2);
+        assert_eq!((game.attacker_index, game.defender_index), (1, 2));
+        assert_eq!(game.table.player_index, 2);
+
+        // the first player is out and the next has too few cards
+        assert!(game
+            .legal_actions()
+            .iter()
+            .all(|action| !matches!(action, DurakAction::Transfer(_))));
+        assert!(game.defend(0));
+        assert!(game.defend(0));
+        assert_eq!(game.state, State::GameOver);
+        assert_eq!(game.loser(), Some(1));
+    }
+
+    #[test]
+    fn test_player_counts() {
+        // six players get the whole deck, the last card dealt is the trump
+        let mut game = Durak::new(6, DurakVariant::Podkidnoy).unwrap();
+        for seed in 0..5 {
+            game.new_round_with_seed(seed);
+            assert_eq!(game.table.deck.count(), 0);
+            assert_eq!(count_cards(&game), 36);
+            assert_eq!(game.table.players[5].cards.last(), Some(&game.trump_card));
+        }
+
+        assert!(Durak::new(1, DurakVariant::Podkidnoy).is_err());
+        assert!(Durak::new(7, DurakVariant::Perevodnoy).is_err());
+    }
+
+    #[test]
+    fn test_bots_finish() {
+        for variant in [DurakVariant::Podkidnoy, DurakVariant::Perevodnoy] {
+            for seed in 0..20 {
+                let players = 2 + seed as u8 % 4;
+                let mut game = Durak::new(players, variant).unwrap();
+                game.new_round_with_seed(seed);
+                let bots: Vec<DurakAI> = (0..players as usize).map(DurakAI::new).collect();
+
+                let finished = play_out(&mut game, |game| {
+                    assert!(bots[game.table.player_index].play(game));
+                    assert_eq!(count_cards(game), 36);
+                });
+                assert!(finished);
+                assert_eq!(game.table.deck.count(), 0);
+            }
+        }
+    }
+}
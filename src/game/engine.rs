@@ -0,0 +1,190 @@
+/*
+ * Turn-based game interface for search bots.
+ *
+ * Games are played one action at a time by the player in turn.
+ * Hidden cards are handled by determinizing: the searching player
+ * redeals everything they can't see and searches the resulting
+ * game of perfect information.
+ */
+
+use std::fmt::Debug;
+
+use rand::{seq::SliceRandom, RngCore};
+
+use crate::{deck::Deck, table::Table};
+
+pub trait Engine: Clone {
+    type Action: Clone + Debug + PartialEq;
+
+    fn num_players(&self) -> usize;
+
+    // Player whose action is next
+    fn current_player(&self) -> usize;
+
+    // Actions the player in turn can take, empty once the game is over.
+    // Playing only legal actions always leads to the end of the game.
+    fn legal_actions(&self) -> Vec<Self::Action>;
+
+    // Plays the action for the player in turn.
+    // Returns false and changes nothing when the action is not legal.
+    fn apply(&mut self, action: &Self::Action) -> bool;
+
+    fn is_terminal(&self) -> bool;
+
+    // Score of each player once the game is over, from 0 for a loss to 1 for a win
+    fn outcome(&self) -> Option<Vec<f32>>;
+
+    // Copy of the game with the cards hidden from the player redealt at random
+    fn determinize(&self, player_index: usize, rng: &mut dyn RngCore) -> Self;
+}
+
+// Scores of a shedding game where a single player is left holding cards
+pub(crate) fn loser_outcome(players: usize, loser: Option<usize>) -> Vec<f32> {
+    (0..players)
+        .map(|index| if Some(index) == loser { 0.0 } else { 1.0 })
+        .collect()
+}
+
+// Shuffles the hands of the other players and the deck together
+// and deals them back in the same amounts.
+//
+// The bottom card of the deck stays in place when it is shown face up.
+// Cards seen picked up by other players are not tracked and get redealt too.
+pub(crate) fn redeal(
+    table: &mut Table,
+    player_index: usize,
+    keep_bottom: bool,
+    rng: &mut dyn RngCore,
+) {
+    let mut deck = table.deck.cards();
+    let bottom = match keep_bottom && !deck.is_empty() {
+        true => Some(deck.remove(0)),
+        false => None,
+    };
+
+    let mut hidden = deck;
+    for (index, player) in table.players.iter().enumerate() {
+        if index != player_index {
+            hidden.extend(player.cards.iter().copied());
+        }
+    }
+    hidden.shuffle(rng);
+
+    for (index, player) in table.players.iter_mut().enumerate() {
+        if index != player_index {
+            let count = player.cards.len();
+            player.cards = hidden.split_off(hidden.len() - count);
+        }
+    }
+    table.deck = Deck::from_cards(bottom.into_iter().chain(hidden).collect());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::{
+            durak::{Durak, DurakVariant},
+            paskahousu::Paskahousu,
+            testing::play_out,
+        },
+        Moska,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // Plays random legal actions to the end,
+    // checking determinized copies keep what the player sees
+    fn play_random<G: Engine>(
+        mut game: G,
+        rng: &mut StdRng,
+        same_view: impl Fn(&G, &G, usize) -> bool,
+    ) {
+        play_out(&mut game, |game| {
+            assert_eq!(game.outcome(), None);
+
+            let player_index = game.current_player();
+            let copy = game.determinize(player_index, rng);
+            assert!(same_view(game, &copy, player_index));
+            assert_eq!(copy.legal_actions(), game.legal_actions());
+
+            let actions = game.legal_actions();
+            let action = &actions[rng.gen_range(0..actions.len())];
+            assert!(game.apply(action));
+        });
+
+        assert!(game.is_terminal());
+        assert!(game.legal_actions().is_empty());
+        let outcome = game.outcome().unwrap();
+        assert_eq!(outcome.len(), game.num_players());
+        assert!(outcome.contains(&1.0));
+    }
+
+    fn same_table(a: &Table, b: &Table, player_index: usize, keep_bottom: bool) -> bool {
+        let counts = |table: &Table| {
+            table
+                .players
+                .iter()
+                .map(|player| player.cards.len())
+                .collect::<Vec<_>>()
+        };
+        let bottom = |table: &Table| table.deck.cards().first().copied();
+
+        a.players[player_index].cards == b.players[player_index].cards
+            && counts(a) == counts(b)
+            && a.deck.count() == b.deck.count()
+            && (!keep_bottom || bottom(a) == bottom(b))
+    }
+
+    #[test]
+    fn test_random_games() {
+        let mut rng = StdRng::seed_from_u64(5);
+
+        for seed in 0..5 {
+            let mut moska = Moska::new(3);
+            moska.new_round_with_seed(seed);
+            play_random(moska, &mut rng, |a, b, player| {
+                same_table(&a.table, &b.table, player, true)
+                    && a.attacker_cards == b.attacker_cards
+                    && a.defender_cards == b.defender_cards
+            });
+
+            let mut durak = Durak::new(4, DurakVariant::Perevodnoy).unwrap();
+            durak.new_round_with_seed(seed);
+            play_random(durak, &mut rng, |a, b, player| {
+                same_table(&a.table, &b.table, player, true) && a.attack_cards == b.attack_cards
+            });
+
+            let mut paskahousu = Paskahousu::new(3);
+            paskahousu.new_round_with_seed(seed);
+            play_random(paskahousu, &mut rng, |a, b, player| {
+                same_table(&a.table, &b.table, player, false) && a.pile == b.pile
+            });
+        }
+    }
+
+    #[test]
+    fn test_redeal() {
+        let mut game = Moska::new(3);
+        game.new_round_with_seed(2);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let mut table = game.table.clone();
+        redeal(&mut table, 1, true, &mut rng);
+        assert!(same_table(&game.table, &table, 1, true));
+        assert_ne!(game.table.players[0].cards, table.players[0].cards);
+
+        // the same cards are in play
+        let cards = |table: &Table| {
+            let mut cards: Vec<String> = table
+                .deck
+                .cards()
+                .iter()
+                .chain(table.players.iter().flat_map(|player| &player.cards))
+                .map(|card| card.to_string())
+                .collect();
+            cards.sort();
+            cards
+        };
+        assert_eq!(cards(&game.table), cards(&table));
+    }
+}
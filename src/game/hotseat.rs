@@ -0,0 +1,329 @@
+/*
+ * Hot-seat play: several humans and bots sharing one device.
+ *
+ * The game itself stays private. Only the public view and the hand of
+ * the human who has confirmed being at the device are handed out, so
+ * nobody sees another human's cards when the device is passed on.
+ */
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    ai::{
+        moska::{Action, Hint},
+        BotStrategy,
+    },
+    deck::Card,
+    game::{
+        moska::{ForfeitRules, State},
+        spectator::SpectatorView,
+    },
+    Moska,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[wasm_bindgen]
+pub enum HotSeatState {
+    // Hand is hidden until the human in turn confirms
+    Handoff,
+    HumanTurn,
+    BotTurn,
+    GameOver,
+}
+
+#[wasm_bindgen]
+pub struct HotSeat {
+    game: Moska,
+    // Human whose hand is shown
+    revealed: Option<usize>,
+    state: HotSeatState,
+}
+
+#[wasm_bindgen]
+impl HotSeat {
+    // Game where the given seats are played by humans and the rest by bots
+    #[wasm_bindgen(constructor)]
+    pub fn new(players: u8, human_seats: Vec<usize>) -> Self {
+        let mut game = Moska::new(players);
+        for seat in 0..players as usize {
+            match human_seats.contains(&seat) {
+                true => game.set_human(seat),
+                false => game.set_bot(seat, BotStrategy::Heuristic),
+            }
+        }
+
+        // With a single human nothing needs hiding
+        let revealed = match human_seats.len() {
+            1 => human_seats.first().copied(),
+            _ => None,
+        };
+
+        Self {
+            game,
+            revealed,
+            state: HotSeatState::GameOver,
+        }
+    }
+
+    pub fn set_forfeit_rules(&mut self, rules: ForfeitRules) {
+        self.game.forfeit_rules = rules;
+    }
+
+    pub fn new_round(&mut self) {
+        self.game.new_round();
+        self.update();
+    }
+
+    pub fn new_round_with_seed(&mut self, seed: u64) {
+        self.game.new_round_with_seed(seed);
+        self.update();
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn state(&self) -> HotSeatState {
+        self.state
+    }
+
+    // Player in turn
+    #[wasm_bindgen(getter)]
+    pub fn player_index(&self) -> usize {
+        self.game.table.player_index
+    }
+
+    pub fn is_human(&self, seat: usize) -> bool {
+        self.game
+            .table
+            .players
+            .get(seat)
+            .is_some_and(|player| player.human())
+    }
+
+    // Human takes over the seat, keeping its hand
+    pub fn join(&mut self, seat: usize) {
+        self.game.set_human(seat);
+        self.update();
+    }
+
+    // Human leaves the seat to a bot, or forfeits it
+    pub fn leave(&mut self, seat: usize) {
+        self.game.disconnect(seat);
+        if self.revealed == Some(seat) {
+            self.revealed = None;
+        }
+        self.update();
+    }
+
+    // Player left holding the cards
+    pub fn loser(&self) -> Option<usize> {
+        self.game.loser()
+    }
+
+    // Public state of the game, without any hands
+    pub fn view(&self) -> SpectatorView {
+        SpectatorView::new(&self.game, false, 0)
+    }
+
+    // Cards on the table can be submitted
+    #[wasm_bindgen(getter)]
+    pub fn valid(&self) -> bool {
+        self.game.valid()
+    }
+
+    // Human in turn confirms they are at the device
+    pub fn confirm(&mut self) -> bool {
+        if self.state != HotSeatState::Handoff {
+            return false;
+        }
+        self.revealed = Some(self.game.table.player_index);
+        self.update();
+        true
+    }
+
+    // Hand of the human in turn, empty until they confirm
+    pub fn hand(&self) -> Vec<Card> {
+        match self.state {
+            HotSeatState::HumanTurn => self.game.player_cards(self.game.table.player_index),
+            _ => vec![],
+        }
+    }
+
+    pub fn hint(&self) -> Option<Hint> {
+        (self.state == HotSeatState::HumanTurn)
+            .then(|| self.game.hint(self.game.table.player_index))
+    }
+
+    // Action of the human in turn, see `Moska::player_action`
+    pub fn player_action(&mut self, action: usize, card_index: usize) -> bool {
+        if self.state != HotSeatState::HumanTurn {
+            return false;
+        }
+        let result = self.game.player_action(action, card_index);
+        self.update();
+        result
+    }
+
+    // Plays the turn of the bot in turn.
+    // Returns the actions played before submitting.
+    pub fn play_bot(&mut self) -> Vec<Action> {
+        if self.state != HotSeatState::BotTurn {
+            return vec![];
+        }
+        let actions = self.game.controller_actions().unwrap_or_default();
+        for action in &actions {
+            self.game.player_action(action.action, action.card_index);
+        }
+        self.game.player_action(3, 0);
+        self.update();
+        actions
+    }
+
+    // Hides the hand whenever another human comes in turn
+    fn update(&mut self) {
+        let player_index = self.game.table.player_index;
+        self.state = if self.game.state == State::GameOver {
+            HotSeatState::GameOver
+        } else if !self.is_human(player_index) {
+            HotSeatState::BotTurn
+        } else if self.revealed == Some(player_index) {
+            HotSeatState::HumanTurn
+        } else {
+            HotSeatState::Handoff
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handoff() {
+        let mut hotseat = HotSeat::new(2, vec![0, 1]);
+        hotseat.new_round_with_seed(3);
+
+        // hidden until the first human confirms
+        assert_eq!(hotseat.state(), HotSeatState::Handoff);
+        assert!(hotseat.hand().is_empty());
+        assert!(hotseat.hint().is_none());
+        assert!(!hotseat.player_action(1, 0));
+        assert_eq!(hotseat.view().attacker_cards, vec![]);
+
+        assert!(hotseat.confirm());
+        assert!(!hotseat.confirm());
+        assert_eq!(hotseat.state(), HotSeatState::HumanTurn);
+        assert_eq!(hotseat.hand(), hotseat.game.player_cards(0));
+
+        let attack = hotseat.hand()[0];
+        hotseat.player_action(1, 0);
+        assert_eq!(hotseat.view().attacker_cards, vec![attack]);
+        hotseat.player_action(3, 0);
+
+        // passed on to the defender
+        assert_eq!(hotseat.state(), HotSeatState::Handoff);
+        assert_eq!(hotseat.player_index(), 1);
+        assert!(hotseat.hand().is_empty());
+        assert_eq!(hotseat.view().attacker_cards, vec![attack]);
+
+        hotseat.confirm();
+        assert_eq!(hotseat.hand(), hotseat.game.player_cards(1));
+    }
+
+    #[test]
+    fn test_humans_and_bots() {
+        let mut hotseat = HotSeat::new(4, vec![1, 3]);
+        hotseat.new_round_with_seed(9);
+        assert!(!hotseat.is_human(0) && hotseat.is_human(1) && !hotseat.is_human(4));
+
+        let mut last_human = None;
+        for _ in 0..10_000 {
+            match hotseat.state() {
+                HotSeatState::GameOver => break,
+                HotSeatState::BotTurn => {
+                    assert!(hotseat.hand().is_empty());
+                    assert!(!hotseat.player_action(3, 0));
+                    hotseat.play_bot();
+                }
+                HotSeatState::Handoff => {
+                    assert!(hotseat.hand().is_empty());
+                    assert!(hotseat.play_bot().is_empty());
+                    // only another human needs to confirm
+                    assert_ne!(last_human, Some(hotseat.player_index()));
+                    hotseat.confirm();
+                }
+                HotSeatState::HumanTurn => {
+                    last_human = Some(hotseat.player_index());
+                    for action in hotseat.hint().unwrap().actions {
+                        hotseat.player_action(action.action, action.card_index);
+                    }
+                    hotseat.player_action(3, 0);
+                }
+            }
+        }
+        assert_eq!(hotseat.state(), HotSeatState::GameOver);
+        assert!(hotseat.hand().is_empty());
+    }
+
+    #[test]
+    fn test_join_and_leave() {
+        let mut hotseat = HotSeat::new(3, vec![0, 1]);
+        hotseat.new_round_with_seed(2);
+        hotseat.confirm();
+        let hand = hotseat.hand();
+
+        // a bot plays the seat with the same hand
+        hotseat.leave(0);
+        assert_eq!(hotseat.state(), HotSeatState::BotTurn);
+        assert_eq!(hotseat.game.player_cards(0), hand);
+
+        hotseat.join(0);
+        assert_eq!(hotseat.state(), HotSeatState::Handoff);
+        hotseat.confirm();
+        assert_eq!(hotseat.hand(), hand);
+
+        // forfeiting discards the hand and skips the seat
+        hotseat.set_forfeit_rules(ForfeitRules {
+            forfeit_disconnected: true,
+            forfeit_loses: false,
+        });
+        hotseat.leave(0);
+        assert!(hotseat.game.player_cards(0).is_empty());
+        assert_eq!(hotseat.player_index(), 1);
+        assert_eq!(hotseat.state(), HotSeatState::Handoff);
+
+        // last seat with cards is left holding them
+        hotseat.leave(1);
+        assert_eq!(hotseat.state(), HotSeatState::GameOver);
+        assert_eq!(hotseat.loser(), Some(2));
+    }
+
+    #[test]
+    fn test_leave_mid_turn() {
+        let mut hotseat = HotSeat::new(3, vec![0, 1]);
+        hotseat.new_round_with_seed(2);
+        hotseat.confirm();
+        let hand = hotseat.hand();
+
+        // the bot starts over from the whole hand and ends the turn
+        hotseat.player_action(1, 0);
+        assert_eq!(hotseat.hand().len(), hand.len() - 1);
+        hotseat.leave(0);
+        assert_eq!(hotseat.state(), HotSeatState::BotTurn);
+        let cards = hotseat.game.player_cards(0);
+        assert_eq!(cards.len(), hand.len());
+        assert!(hand.iter().all(|card| cards.contains(card)));
+        assert!(!hotseat.play_bot().is_empty());
+        assert_ne!(hotseat.player_index(), 0);
+    }
+
+    #[test]
+    fn test_single_human() {
+        let mut hotseat = HotSeat::new(3, vec![2]);
+        hotseat.new_round_with_seed(1);
+
+        while hotseat.state() == HotSeatState::BotTurn {
+            hotseat.play_bot();
+        }
+        assert_eq!(hotseat.state(), HotSeatState::HumanTurn);
+        assert_eq!(hotseat.player_index(), 2);
+    }
+}
@@ -1,6 +1,17 @@
 use crate::deck::Card;
 
+pub mod clock;
+pub mod compact;
+pub mod durak;
+pub mod engine;
+pub mod hotseat;
 pub mod moska;
+pub mod paskahousu;
+pub mod record;
+pub mod spectator;
+#[cfg(test)]
+pub(crate) mod testing;
+pub mod zobrist;
 
 // Game interface
 pub trait Game {
@@ -3,12 +3,25 @@
  */
 
 use itertools::Itertools;
-use std::{cmp::Ordering, collections::HashMap};
+use rand::RngCore;
+use std::{cmp::Ordering, collections::HashMap, sync::Arc};
 use wasm_bindgen::prelude::*;
 
 use crate::{
+    ai::BotStrategy,
+    ai::{
+        endgame::EndgameSolver,
+        moska::{cheapest_defense, Action, Hint, HintReason, MoskaAI},
+    },
+    card_set::CardSet,
     deck::{Card, Rank, Suit},
-    table::Table,
+    game::{
+        clock::{Clock, SystemClock, TimeControl, Timeout, TimeoutPolicy, TurnClock},
+        engine::{loser_outcome, redeal, Engine},
+        record::{Move, MoveKind},
+        zobrist,
+    },
+    table::{Controller, Table},
 };
 
 #[derive(Debug)]
@@ -20,6 +33,7 @@ enum PlayerAction {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[wasm_bindgen]
 pub enum State {
     Initial,
@@ -28,6 +42,25 @@ pub enum State {
     GameOver,
 }
 
+// What happens to seats leaving the game
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[wasm_bindgen]
+pub struct ForfeitRules {
+    // Disconnected seats forfeit instead of being played by a bot
+    pub forfeit_disconnected: bool,
+    // Forfeiting loses the game instead of discarding the hand
+    pub forfeit_loses: bool,
+}
+
+#[wasm_bindgen]
+impl ForfeitRules {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Clone)]
 #[wasm_bindgen(getter_with_clone)]
 pub struct Moska {
     pub table: Table,
@@ -38,37 +71,74 @@ pub struct Moska {
     pub discarded: Vec<Card>,
 
     pub state: State,
+
+    pub forfeit_rules: ForfeitRules,
+
+    // Times the moves when set
+    #[wasm_bindgen(skip)]
+    pub clock: Option<TurnClock>,
+
+    // Timeout the current state was reached by, cleared by the next move.
+    // Tells pollers of `state` that a move was played for a player.
+    pub timeout: Option<Timeout>,
+
+    // Seed the round was dealt from, if any
+    #[wasm_bindgen(skip)]
+    pub seed: Option<u64>,
+
+    // Moves played this round, see `GameRecord`
+    #[wasm_bindgen(skip)]
+    pub history: Vec<Move>,
+
+    // Zobrist hash of the cards in hands and on the table, and of the deck
+    cards_hash: u64,
+    deck_hash: u64,
 }
 
 #[wasm_bindgen]
 impl Moska {
     #[wasm_bindgen(constructor)]
     pub fn new(players: u8) -> Self {
-        Self {
+        let mut game = Self {
             table: Table::new(players),
             trump_card: Card::new(Suit::Hearts, Rank::Two),
             attacker_cards: vec![],
             defender_cards: vec![],
             discarded: vec![],
             state: State::Initial,
-        }
+            forfeit_rules: ForfeitRules::default(),
+            clock: None,
+            timeout: None,
+            seed: None,
+            history: vec![],
+            cards_hash: 0,
+            deck_hash: 0,
+        };
+        // Table starts with a full deck
+        game.rehash();
+        game
     }
 
-    pub(crate) fn reset(&mut self) {
+    pub(crate) fn reset(&mut self, seed: Option<u64>) {
         self.table.reset();
         self.attacker_cards.clear();
         self.defender_cards.clear();
         self.discarded.clear();
-        self.table.deck.shuffle();
+        match seed {
+            Some(seed) => self.table.deck.shuffle_with_seed(seed),
+            None => self.table.deck.shuffle(),
+        }
         self.state = State::Initial;
     }
 
-    // Finds next player with cards left
+    // Finds next player with cards left, skipping forfeited seats
     pub fn next_player(&self) -> usize {
         let num_players = self.table.players.len();
         let mut player_index = (self.table.player_index + 1) % num_players;
 
-        while self.table.players[player_index].cards.len() == 0 {
+        while self.table.players[player_index].cards.is_empty()
+            || self.table.players[player_index].forfeited
+        {
             // check looped all players
             if player_index == self.table.player_index {
                 break;
@@ -81,13 +151,27 @@ impl Moska {
     }
 
     // Swaps trump card with the card in current player's hand.
+    // Trump card lies at the bottom of the deck,
+    // so it can be swapped only until the deck runs out.
     pub fn swap_trumpcard(&mut self, card_index: usize) -> bool {
+        if self.table.deck.count() == 0 {
+            return false;
+        }
+
+        let seat = self.table.player_index;
         if let Some(player) = self.table.current_player_mut() {
             if let Some(card) = player.cards.get_mut(card_index) {
+                self.cards_hash ^=
+                    zobrist::hand_key(seat, card) ^ zobrist::hand_key(seat, &self.trump_card);
+                self.deck_hash ^=
+                    zobrist::deck_key(0, card) ^ zobrist::deck_key(0, &self.trump_card);
+
                 // Swap cards
-                let card_copy = *card;
-                *card = self.trump_card;
-                self.trump_card = card_copy;
+                std::mem::swap(card, &mut self.trump_card);
+                let taken = *card;
+                self.table.deck.replace_last(self.trump_card);
+                self.history
+                    .push(Move::new(seat, MoveKind::Swap, vec![taken]));
                 return true;
             }
         }
@@ -96,11 +180,28 @@ impl Moska {
     }
 
     pub fn new_round(&mut self) {
+        self.start_round(None);
+    }
+
+    // Starts a new round with the deck shuffled from the seed
+    pub fn new_round_with_seed(&mut self, seed: u64) {
+        self.start_round(Some(seed));
+    }
+
+    fn start_round(&mut self, seed: Option<u64>) {
         self.table.round += 1;
 
-        self.setup();
+        self.setup(seed);
+        self.seed = seed;
+        self.history.clear();
 
         self.state = State::PlayerAttacking;
+
+        self.timeout = None;
+        if let Some(clock) = &mut self.clock {
+            clock.reset();
+        }
+        self.update_clock();
     }
 
     // Continues to next turn.
@@ -113,7 +214,7 @@ impl Moska {
             .players
             .iter()
             .enumerate()
-            .filter_map(|(index, p)| p.cards.first().and_then(|_| Some(index)))
+            .filter_map(|(index, p)| p.cards.first().map(|_| index))
             .collect();
 
         if players_with_cards.len() == 1 {
@@ -135,16 +236,131 @@ impl Moska {
     }
 
     pub fn player_action(&mut self, action: usize, card_index: usize) -> bool {
+        // Moves after the time is up are not played
+        if self.check_clock() {
+            return false;
+        }
+
+        self.timeout = None;
+        let result = self.apply_action(action, card_index);
+        self.update_clock();
+        result
+    }
+
+    // Times moves with the host's clock
+    pub fn set_time_control(&mut self, control: TimeControl) {
+        self.set_clock(control, Arc::new(SystemClock));
+    }
+
+    pub fn clear_time_control(&mut self) {
+        self.clock = None;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn time_control(&self) -> Option<TimeControl> {
+        self.clock.as_ref().map(|clock| clock.control)
+    }
+
+    // Time left for the current move, nothing when not timed
+    pub fn time_left_ms(&self) -> Option<u64> {
+        self.clock.as_ref()?.time_left_ms()
+    }
+
+    // Time the player has used in the round
+    pub fn time_used_ms(&self, player_index: usize) -> u64 {
+        self.clock
+            .as_ref()
+            .map(|clock| clock.used_ms(player_index))
+            .unwrap_or_default()
+    }
+
+    // Applies the timeout policy if the player in turn is out of time.
+    // Hosts call this periodically so that idle players time out.
+    //
+    // The move played by the policy leaves `state` as any other move would,
+    // and `timeout` tells which player ran out of time and what was done.
+    // A forfeiting seat ends the game with `State::GameOver` when
+    // `forfeit_loses` is set.
+    pub fn check_clock(&mut self) -> bool {
+        let Some(control) = self
+            .clock
+            .as_ref()
+            .filter(|c| c.expired())
+            .map(|c| c.control)
+        else {
+            return false;
+        };
+
+        let player_index = self.table.player_index;
+        let turn = self.table.turn;
+        let policy = match (control.policy, self.state) {
+            (TimeoutPolicy::AutoTake, State::PlayerAttacking) => TimeoutPolicy::AutoSubmit,
+            (policy, _) => policy,
+        };
+
+        match policy {
+            TimeoutPolicy::Forfeit => {
+                self.forfeit_seat(player_index);
+            }
+            TimeoutPolicy::AutoTake => {
+                self.return_table_cards();
+                self.apply_action(PlayerAction::Submit as usize, 0);
+            }
+            TimeoutPolicy::AutoSubmit => {
+                if !self.valid() {
+                    self.return_table_cards();
+                    if self.state == State::PlayerAttacking {
+                        let lowest =
+                            self.table
+                                .players
+                                .get(player_index)
+                                .and_then(|player| {
+                                    player.cards.iter().position_min_by(|a, b| {
+                                        card_cmp(a, b, self.trump_card.suit)
+                                    })
+                                })
+                                .unwrap_or_default();
+                        self.apply_action(PlayerAction::AddCard as usize, lowest);
+                    }
+                }
+                self.apply_action(PlayerAction::Submit as usize, 0);
+            }
+        }
+
+        self.timeout = Some(Timeout {
+            player_index,
+            policy,
+            turn,
+        });
+        self.update_clock();
+        true
+    }
+
+    // Zobrist hash of the position, with the order of the deck when asked.
+    // Positions differing only in the order of cards in hands or on the
+    // table hash the same.
+    pub fn zobrist_hash(&self, with_deck: bool) -> u64 {
+        let deck = match with_deck {
+            true => self.deck_hash,
+            false => 0,
+        };
+        self.cards_hash ^ deck ^ zobrist::turn_hash(self)
+    }
+
+    fn apply_action(&mut self, action: usize, card_index: usize) -> bool {
         // Puts the selected card to the table
         if action == PlayerAction::AddCard as usize {
-            println!("add");
+            let seat = self.table.player_index;
             if let Some(player) = self.table.current_player_mut() {
                 if let Some(card) = player.pop_card(card_index) {
+                    self.cards_hash ^= zobrist::hand_key(seat, &card);
                     match &self.state {
                         State::PlayerAttacking => {
+                            self.cards_hash ^= zobrist::attack_key(&card);
                             self.attacker_cards.push(card);
                         }
                         State::PlayerDefending => {
+                            self.cards_hash ^= zobrist::defense_key(&card);
                             self.defender_cards.push(card);
                         }
                         _ => {
@@ -159,18 +375,20 @@ impl Moska {
         // from attacking or defending cards,
         // depending on the current state.
         if action == PlayerAction::TakeCard as usize {
-            println!("take");
+            let seat = self.table.player_index;
             if let Some(player) = self.table.current_player_mut() {
                 match &self.state {
-                    State::PlayerAttacking => {
-                        if self.attacker_cards.get(card_index).is_some() {
-                            player.cards.push(self.attacker_cards.remove(card_index));
-                        }
+                    State::PlayerAttacking if card_index < self.attacker_cards.len() => {
+                        let card = self.attacker_cards.remove(card_index);
+                        self.cards_hash ^=
+                            zobrist::hand_key(seat, &card) ^ zobrist::attack_key(&card);
+                        player.cards.push(card);
                     }
-                    State::PlayerDefending => {
-                        if self.defender_cards.get(card_index).is_some() {
-                            player.cards.push(self.defender_cards.remove(card_index));
-                        }
+                    State::PlayerDefending if card_index < self.defender_cards.len() => {
+                        let card = self.defender_cards.remove(card_index);
+                        self.cards_hash ^=
+                            zobrist::hand_key(seat, &card) ^ zobrist::defense_key(&card);
+                        player.cards.push(card);
                     }
                     _ => {
                         // NOOP
@@ -190,23 +408,32 @@ impl Moska {
         // Checks attacking cards validity and starts next turn,
         // with next player defending.
         if action == PlayerAction::Submit as usize {
-            println!("submit");
             match self.state {
                 State::PlayerDefending => {
                     if self.defender_cards.is_empty() {
-                        println!("withdraw");
                         // Take all the attacking cards and continue to next turn.
+                        let seat = self.table.player_index;
+                        self.history.push(Move::new(seat, MoveKind::Take, vec![]));
+                        for card in &self.attacker_cards {
+                            self.cards_hash ^=
+                                zobrist::hand_key(seat, card) ^ zobrist::attack_key(card);
+                        }
                         self.table
                             .current_player_mut()
                             .unwrap()
                             .cards
-                            .extend(self.attacker_cards.drain(..));
+                            .append(&mut self.attacker_cards);
 
                         self.draw_cards();
                         return self.next_turn();
                     }
 
                     self.eval_defense().then(|| {
+                        self.history.push(Move::new(
+                            self.table.player_index,
+                            MoveKind::Defend,
+                            self.defender_cards.clone(),
+                        ));
                         self.discard_table();
                         self.draw_cards();
                         self.state = State::PlayerAttacking;
@@ -219,6 +446,11 @@ impl Moska {
                 }
                 State::PlayerAttacking => {
                     self.eval_attack().then(|| {
+                        self.history.push(Move::new(
+                            self.table.player_index,
+                            MoveKind::Attack,
+                            self.attacker_cards.clone(),
+                        ));
                         self.draw_cards();
                         self.next_turn();
                     });
@@ -244,18 +476,113 @@ impl Moska {
         false
     }
 
+    pub fn set_human(&mut self, player_index: usize) {
+        self.set_controller(player_index, Controller::Human);
+    }
+
+    pub fn set_bot(&mut self, player_index: usize, strategy: BotStrategy) {
+        self.set_controller(player_index, Controller::Bot(strategy));
+    }
+
+    // Marks the seat disconnected.
+    // The seat forfeits if the rules say so, otherwise a bot plays it.
+    pub fn disconnect(&mut self, player_index: usize) {
+        self.set_controller(player_index, Controller::Disconnected);
+        if self.forfeit_rules.forfeit_disconnected {
+            self.forfeit(player_index);
+        }
+    }
+
+    // Removes the seat from the game.
+    //
+    // Depending on the rules the seat either loses at once,
+    // or its hand is discarded and the game continues without it.
+    // Cards on the table are discarded when the seat is in turn.
+    pub fn forfeit(&mut self, player_index: usize) -> bool {
+        let forfeited = self.forfeit_seat(player_index);
+        self.update_clock();
+        forfeited
+    }
+
+    fn forfeit_seat(&mut self, player_index: usize) -> bool {
+        let playing = matches!(self.state, State::PlayerAttacking | State::PlayerDefending);
+        match self.table.players.get_mut(player_index) {
+            Some(player) if playing && !player.forfeited => player.forfeited = true,
+            _ => return false,
+        }
+        self.history
+            .push(Move::new(player_index, MoveKind::Forfeit, vec![]));
+
+        if self.forfeit_rules.forfeit_loses {
+            self.state = State::GameOver;
+            return true;
+        }
+
+        let mut cards = std::mem::take(&mut self.table.players[player_index].cards);
+        for card in &cards {
+            self.cards_hash ^= zobrist::hand_key(player_index, card);
+        }
+        self.discarded.append(&mut cards);
+
+        if player_index == self.table.player_index {
+            self.discard_table();
+            return self.next_turn();
+        }
+
+        let players_with_cards = self
+            .table
+            .players
+            .iter()
+            .filter(|player| !player.cards.is_empty())
+            .count();
+        if players_with_cards <= 1 {
+            self.state = State::GameOver;
+        }
+        true
+    }
+
+    // Player left holding the cards when the game is over.
+    // A seat that lost by forfeiting still holds its hand.
+    pub fn loser(&self) -> Option<usize> {
+        if self.state != State::GameOver {
+            return None;
+        }
+        let players = &self.table.players;
+        players
+            .iter()
+            .position(|player| player.forfeited && !player.cards.is_empty())
+            .or_else(|| players.iter().position(|player| !player.cards.is_empty()))
+    }
+
     // Returns copy of player cards
     pub fn player_cards(&self, player_index: usize) -> Vec<Card> {
         self.table
             .players
             .get(player_index)
-            .and_then(|player| Some(player.cards.clone()))
+            .map(|player| player.cards.clone())
             .unwrap_or_default()
     }
 
+    // Suggests actions for the player using the strongest available bot.
+    // Endgames are solved exactly when a forced win is found in time.
+    pub fn hint(&self, player_index: usize) -> Hint {
+        if player_index == self.table.player_index {
+            if let Some(solution) = EndgameSolver::with_node_limit(100_000).solve(self) {
+                if solution.win {
+                    return Hint {
+                        actions: solution.actions,
+                        reason: HintReason::PlayForcedWin,
+                    };
+                }
+            }
+        }
+
+        MoskaAI::new(player_index).hint(self)
+    }
+
     // Sets up a new game
-    fn setup(&mut self) {
-        self.reset();
+    fn setup(&mut self, seed: Option<u64>) {
+        self.reset(seed);
 
         // Deal 6 cards for each player
         for player in &mut self.table.players {
@@ -271,15 +598,18 @@ impl Moska {
 
         // Draw the trump card
         self.trump_card = self.table.deck.peek_last().cloned().unwrap();
+        self.rehash();
     }
 
     // Draws enough cards for player until deck is empty
     fn draw_cards(&mut self) {
         let player_index = self.table.player_index;
-        if let Some(_) = self.table.current_player() {
+        if self.table.current_player().is_some() {
             let player = self.table.players.get_mut(player_index).unwrap();
             while player.cards.len() < 6 && self.table.deck.peek().is_some() {
                 if let Some(card) = self.table.deck.pop() {
+                    self.cards_hash ^= zobrist::hand_key(player_index, &card);
+                    self.deck_hash ^= zobrist::deck_key(self.table.deck.count(), &card);
                     player.cards.push(card);
                 }
             }
@@ -343,20 +673,7 @@ impl Moska {
     // Resolves a pair of attacking and defending card.
     // Returns true when defending succeeds.
     pub fn resolve_pair(&self, atk: &Card, def: &Card) -> bool {
-        // If the cards are of same suit,
-        // rank determines the outcome.
-        if atk.suit == def.suit {
-            if card_rank_order(def.rank) > card_rank_order(atk.rank) {
-                return true;
-            }
-        }
-        // If suits are not equal,
-        // check if B is a trump card.
-        else if def.suit == self.trump_card.suit {
-            return true;
-        }
-
-        false
+        card_beats(atk, def, self.trump_card.suit)
     }
 
     // Resolves attacking and defending cards.
@@ -387,8 +704,239 @@ impl Moska {
 
     // Clears playing table
     fn discard_table(&mut self) {
-        self.discarded.extend(self.attacker_cards.drain(..));
-        self.discarded.extend(self.defender_cards.drain(..));
+        for card in &self.attacker_cards {
+            self.cards_hash ^= zobrist::attack_key(card);
+        }
+        for card in &self.defender_cards {
+            self.cards_hash ^= zobrist::defense_key(card);
+        }
+        self.discarded.append(&mut self.attacker_cards);
+        self.discarded.append(&mut self.defender_cards);
+    }
+}
+
+impl Moska {
+    // Recomputes the hash after the cards have been changed directly
+    pub fn rehash(&mut self) {
+        self.cards_hash = zobrist::cards_hash(self);
+        self.deck_hash = zobrist::deck_hash(self);
+    }
+
+    // Copy of the position to search on, leaving out the clock, the seed
+    // and the move history that grows with the game
+    pub(crate) fn search_copy(&self) -> Self {
+        Self {
+            table: self.table.clone(),
+            trump_card: self.trump_card,
+            attacker_cards: self.attacker_cards.clone(),
+            defender_cards: self.defender_cards.clone(),
+            discarded: self.discarded.clone(),
+            state: self.state,
+            forfeit_rules: self.forfeit_rules,
+            clock: None,
+            timeout: self.timeout,
+            seed: None,
+            history: vec![],
+            cards_hash: self.cards_hash,
+            deck_hash: self.deck_hash,
+        }
+    }
+
+    // Bot actions for the player in turn when the seat is not played by a human.
+    // Disconnected seats are played by the heuristic bot.
+    pub fn controller_actions(&self) -> Option<Vec<Action>> {
+        match self.table.players.get(self.table.player_index)?.controller {
+            Controller::Human => None,
+            Controller::Bot(strategy) => Some(strategy.get_actions(self)),
+            Controller::Disconnected => Some(BotStrategy::Heuristic.get_actions(self)),
+        }
+    }
+
+    // Times moves with the given clock
+    pub fn set_clock(&mut self, control: TimeControl, clock: Arc<dyn Clock>) {
+        let mut clock = TurnClock::new(control, clock, self.table.players.len());
+        clock.update(self.table.player_index, self.state);
+        self.clock = Some(clock);
+    }
+
+    fn update_clock(&mut self) {
+        if let Some(clock) = &mut self.clock {
+            clock.update(self.table.player_index, self.state);
+        }
+    }
+
+    // Puts the cards of the player in turn back to their hand
+    fn return_table_cards(&mut self) {
+        let cards = match self.state {
+            State::PlayerAttacking => &mut self.attacker_cards,
+            State::PlayerDefending => &mut self.defender_cards,
+            _ => return,
+        };
+        if let Some(player) = self.table.players.get_mut(self.table.player_index) {
+            for card in cards.iter() {
+                self.cards_hash ^= zobrist::hand_key(self.table.player_index, card)
+                    ^ match self.state {
+                        State::PlayerAttacking => zobrist::attack_key(card),
+                        _ => zobrist::defense_key(card),
+                    };
+            }
+            player.cards.append(cards);
+        }
+    }
+
+    // A new controller of the seat in turn plans from its whole hand,
+    // so the cards the previous one put on the table go back to it
+    fn set_controller(&mut self, player_index: usize, controller: Controller) {
+        let Some(player) = self.table.players.get_mut(player_index) else {
+            return;
+        };
+        if std::mem::replace(&mut player.controller, controller) != controller
+            && player_index == self.table.player_index
+        {
+            self.return_table_cards();
+        }
+    }
+}
+
+impl Engine for Moska {
+    type Action = Action;
+
+    fn num_players(&self) -> usize {
+        self.table.players.len()
+    }
+
+    fn current_player(&self) -> usize {
+        self.table.player_index
+    }
+
+    // Single card actions that never leave the table in a state
+    // that can't be submitted:
+    // - attacks add cards of a rank already on the table, or start
+    //   a new pair once every rank on the table is paired
+    // - defenses cover the attacking cards in order, as long as
+    //   the rest can still be covered
+    // - submitting when the table holds, which takes the attack
+    //   when defending without cards
+    // - swapping the trump two before the deck runs out
+    fn legal_actions(&self) -> Vec<Action> {
+        let Some(player) = self.table.players.get(self.table.player_index) else {
+            return vec![];
+        };
+        let action = |action: PlayerAction, card_index: usize| Action {
+            action: action as usize,
+            card_index,
+        };
+        let trump_suit = self.trump_card.suit;
+        let mut actions = vec![];
+
+        match self.state {
+            State::PlayerAttacking => {
+                let room = self.table.players[self.next_player()]
+                    .cards
+                    .len()
+                    .saturating_sub(self.attacker_cards.len());
+                let rank_count = |rank: Rank| {
+                    self.attacker_cards
+                        .iter()
+                        .filter(|card| card.rank == rank)
+                        .count()
+                };
+                let unpaired = self
+                    .attacker_cards
+                    .iter()
+                    .find(|card| rank_count(card.rank) < 2)
+                    .map(|card| card.rank);
+
+                for (card_index, card) in player.cards.iter().enumerate() {
+                    let in_hand = player.cards.iter().filter(|c| c.rank == card.rank).count();
+                    let legal = match (self.attacker_cards.is_empty(), unpaired) {
+                        (true, _) => room >= 1,
+                        // Unpaired card is paired before anything else
+                        (false, Some(rank)) => rank == card.rank && room >= 1,
+                        (false, None) if rank_count(card.rank) > 0 => room >= 1,
+                        (false, None) => room >= 2 && in_hand >= 2,
+                    };
+                    if legal {
+                        actions.push(action(PlayerAction::AddCard, card_index));
+                    }
+                }
+            }
+            State::PlayerDefending => {
+                let covered = self.defender_cards.len();
+                if let Some(atk) = self.attacker_cards.get(covered) {
+                    let cards: Vec<&Card> = player
+                        .cards
+                        .iter()
+                        .sorted_by(|a, b| card_cmp(a, b, trump_suit))
+                        .collect();
+                    for (card_index, card) in player.cards.iter().enumerate() {
+                        if !card_beats(atk, card, trump_suit) {
+                            continue;
+                        }
+                        let rest: Vec<&Card> = cards
+                            .iter()
+                            .copied()
+                            .filter(|c| !std::ptr::eq(*c, card))
+                            .collect();
+                        if cheapest_defense(&rest, &self.attacker_cards[covered + 1..], trump_suit)
+                            .is_some()
+                        {
+                            actions.push(action(PlayerAction::AddCard, card_index));
+                        }
+                    }
+                }
+                // Covering can't be finished, give the cards back
+                if actions.is_empty() && covered > 0 && !self.valid() {
+                    actions.push(action(PlayerAction::TakeCard, covered - 1));
+                }
+            }
+            _ => return vec![],
+        }
+
+        if self.valid() || (self.state == State::PlayerDefending && self.defender_cards.is_empty())
+        {
+            actions.push(action(PlayerAction::Submit, 0));
+        }
+
+        let table_empty = match self.state {
+            State::PlayerAttacking => self.attacker_cards.is_empty(),
+            _ => self.defender_cards.is_empty(),
+        };
+        let holds_two = player
+            .cards
+            .iter()
+            .any(|card| card.suit == trump_suit && card.rank == Rank::Two);
+        if table_empty && holds_two && self.table.deck.count() > 0 {
+            actions.push(action(PlayerAction::SwapTrumpCard, 0));
+        }
+
+        actions
+    }
+
+    // Bypasses the turn clock, search plays on copies of the game
+    fn apply(&mut self, action: &Action) -> bool {
+        if !self.legal_actions().contains(action) {
+            return false;
+        }
+        self.apply_action(action.action, action.card_index);
+        true
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.state == State::GameOver
+    }
+
+    fn outcome(&self) -> Option<Vec<f32>> {
+        self.is_terminal()
+            .then(|| loser_outcome(self.table.players.len(), self.loser()))
+    }
+
+    // Trump card at the bottom of the deck is in sight
+    fn determinize(&self, player_index: usize, rng: &mut dyn RngCore) -> Self {
+        let mut game = self.search_copy();
+        redeal(&mut game.table, player_index, true, rng);
+        game.rehash();
+        game
     }
 }
 
@@ -412,36 +960,46 @@ pub fn card_rank_order(rank: Rank) -> usize {
     }
 }
 
-// Card comparison function with trump suit
-pub fn card_cmp(a: &Card, b: &Card, trump_suit: Suit) -> Ordering {
-    if a.suit == b.suit {
-        if card_rank_order(a.rank) > card_rank_order(b.rank) {
-            Ordering::Greater
-        } else {
-            Ordering::Less
-        }
-    } else if b.suit == trump_suit {
-        Ordering::Greater
-    } else {
-        Ordering::Less
+// Checks if defending card beats the attacking card.
+pub fn card_beats(atk: &Card, def: &Card, trump_suit: Suit) -> bool {
+    // If the cards are of same suit,
+    // rank determines the outcome.
+    if atk.suit == def.suit {
+        card_rank_order(def.rank) > card_rank_order(atk.rank)
+    }
+    // If suits are not equal,
+    // check if defending card is a trump card.
+    else {
+        def.suit == trump_suit
     }
 }
 
-// Finds all pairs
-pub fn find_pairs<'a>(cards: &[&'a Card]) -> Vec<Vec<&'a Card>> {
-    let mut map: HashMap<u8, Vec<&Card>> = HashMap::new();
-
-    for card in cards {
-        let key = card.rank as u8;
+// Card comparison function with trump suit.
+// Trumps are greater than other suits, then cards are ordered by rank.
+// Suit breaks ties between equal ranks so the ordering is total.
+pub fn card_cmp(a: &Card, b: &Card, trump_suit: Suit) -> Ordering {
+    let is_trump = |card: &Card| card.suit == trump_suit;
 
-        if !map.contains_key(&key) {
-            map.insert(key, vec![card]);
-        } else {
-            (*map.get_mut(&key).unwrap()).push(card);
-        }
-    }
+    is_trump(a)
+        .cmp(&is_trump(b))
+        .then(card_rank_order(a.rank).cmp(&card_rank_order(b.rank)))
+        .then((a.suit as u8).cmp(&(b.suit as u8)))
+}
 
-    map.values().filter(|v| v.len() > 1).cloned().collect_vec()
+// Finds all pairs, grouped by rank in rank order
+pub fn find_pairs<'a>(cards: &[&'a Card]) -> Vec<Vec<&'a Card>> {
+    let set: CardSet = cards.iter().copied().collect();
+
+    set.ranks()
+        .filter(|rank| set.of_rank(*rank).len() > 1)
+        .map(|rank| {
+            cards
+                .iter()
+                .copied()
+                .filter(|card| card.rank == rank)
+                .collect()
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -501,37 +1059,37 @@ mod tests {
         game.new_round();
 
         // empty table, should be illegal
-        assert_eq!(game.eval_attack(), false);
+        assert!(!game.eval_attack());
 
         // add 2 of hearts, should be ok
         game.attacker_cards.push(Card::new(Suit::Hearts, Rank::Two));
-        assert_eq!(game.eval_attack(), true);
+        assert!(game.eval_attack());
 
         // add 3 of diamonds, should be illegal
         game.attacker_cards
             .push(Card::new(Suit::Diamonds, Rank::Three));
-        assert_eq!(game.eval_attack(), false);
+        assert!(!game.eval_attack());
         game.attacker_cards.pop();
 
         // make a pair, should be ok
         game.attacker_cards
             .push(Card::new(Suit::Diamonds, Rank::Two));
-        assert_eq!(game.eval_attack(), true);
+        assert!(game.eval_attack());
 
         // add a second pair, should be ok
         game.attacker_cards
             .push(Card::new(Suit::Spades, Rank::Four));
         game.attacker_cards.push(Card::new(Suit::Clubs, Rank::Four));
         assert_eq!(game.attacker_cards.len(), 4);
-        assert_eq!(game.eval_attack(), true);
+        assert!(game.eval_attack());
 
         // pop last card out, should be illegal
         game.attacker_cards.pop();
         assert_eq!(game.attacker_cards.len(), 3);
-        assert_eq!(game.eval_attack(), false);
+        assert!(!game.eval_attack());
 
         game.attacker_cards.pop();
-        assert_eq!(game.eval_attack(), true);
+        assert!(game.eval_attack());
     }
 
     #[test]
@@ -547,26 +1105,26 @@ mod tests {
         game.attacker_cards.push(Card::new(Suit::Hearts, Rank::Two));
         game.defender_cards
             .push(Card::new(Suit::Hearts, Rank::Three));
-        assert_eq!(game.eval_attack(), true);
-        assert_eq!(game.eval_defense(), true);
+        assert!(game.eval_attack());
+        assert!(game.eval_defense());
 
         // make attack pair
         game.attacker_cards
             .push(Card::new(Suit::Diamonds, Rank::Two));
-        assert_eq!(game.eval_attack(), true);
-        assert_eq!(game.eval_defense(), false);
+        assert!(game.eval_attack());
+        assert!(!game.eval_defense());
 
         // try to add wrong suit for defender
         game.defender_cards
             .push(Card::new(Suit::Clubs, Rank::Three));
-        assert_eq!(game.eval_defense(), false);
+        assert!(!game.eval_defense());
 
         // replace correct suit for defender
         println!("foo");
         game.defender_cards.pop();
         game.defender_cards
             .push(Card::new(Suit::Diamonds, Rank::Three));
-        assert_eq!(game.eval_defense(), true);
+        assert!(game.eval_defense());
 
         // add a second pair
         game.attacker_cards.push(Card::new(Suit::Spades, Rank::Ten));
@@ -575,22 +1133,22 @@ mod tests {
         game.defender_cards
             .push(Card::new(Suit::Spades, Rank::Jack));
         game.defender_cards.push(Card::new(Suit::Clubs, Rank::Jack));
-        assert_eq!(game.eval_defense(), true);
+        assert!(game.eval_defense());
 
         // should be ok in reverse order
         game.defender_cards.reverse();
-        assert_eq!(game.eval_defense(), true);
+        assert!(game.eval_defense());
 
         // pop last card and try with lesser rank
         game.defender_cards.pop();
         game.defender_cards.push(Card::new(Suit::Clubs, Rank::Five));
-        assert_eq!(game.eval_defense(), false);
+        assert!(!game.eval_defense());
 
         // try trump card
         game.defender_cards.pop();
         game.defender_cards
             .push(Card::new(Suit::Spades, Rank::Five));
-        assert_eq!(game.eval_defense(), true);
+        assert!(game.eval_defense());
 
         // clear table
         game.player_action(PlayerAction::Submit as usize, 0);
@@ -604,7 +1162,7 @@ mod tests {
             .push(Card::new(Suit::Clubs, Rank::Queen));
         game.defender_cards
             .push(Card::new(Suit::Spades, Rank::Jack));
-        assert_eq!(game.eval_defense(), false);
+        assert!(!game.eval_defense());
     }
 
     #[test]
@@ -671,7 +1229,7 @@ mod tests {
         game.table.players[2].cards = vec![Card::new(Suit::Hearts, Rank::Four)];
 
         // pop all cards from deck
-        while let Some(_) = game.table.deck.pop() {}
+        while game.table.deck.pop().is_some() {}
 
         assert_eq!(game.state, State::PlayerAttacking);
         game.player_action(PlayerAction::AddCard as usize, 0);
@@ -726,6 +1284,33 @@ mod tests {
         assert_eq!(game.state, State::GameOver);
     }
 
+    #[test]
+    fn test_card_cmp() {
+        let trump = Suit::Spades;
+        let mut cards = vec![
+            Card::new(Suit::Spades, Rank::Two),
+            Card::new(Suit::Hearts, Rank::Ace),
+            Card::new(Suit::Clubs, Rank::Three),
+            Card::new(Suit::Spades, Rank::King),
+            Card::new(Suit::Diamonds, Rank::Three),
+        ];
+        cards.sort_by(|a, b| card_cmp(a, b, trump));
+
+        assert_eq!(
+            cards,
+            vec![
+                Card::new(Suit::Clubs, Rank::Three),
+                Card::new(Suit::Diamonds, Rank::Three),
+                Card::new(Suit::Hearts, Rank::Ace),
+                Card::new(Suit::Spades, Rank::Two),
+                Card::new(Suit::Spades, Rank::King),
+            ]
+        );
+
+        let card = Card::new(Suit::Hearts, Rank::Five);
+        assert_eq!(card_cmp(&card, &card, trump), Ordering::Equal);
+    }
+
     #[test]
     fn test_swap_trumpcard() {
         let mut game = Moska::new(2);
@@ -736,7 +1321,7 @@ mod tests {
         game.table.players[0].cards = vec![Card::new(Suit::Spades, Rank::Two)];
         game.table.players[1].cards = vec![Card::new(Suit::Hearts, Rank::Three)];
 
-        assert_eq!(game.player_action(4, 0), true);
+        assert!(game.player_action(4, 0));
         assert_eq!(
             game.table.players[0].cards[0],
             Card::new(Suit::Spades, Rank::Ace)
@@ -750,10 +1335,310 @@ mod tests {
         game.table.players[0].cards = vec![Card::new(Suit::Hearts, Rank::Two)];
         game.table.players[1].cards = vec![Card::new(Suit::Hearts, Rank::Three)];
 
-        assert_eq!(game.player_action(4, 0), false);
+        assert!(!game.player_action(4, 0));
         assert_eq!(
             game.table.players[0].cards[0],
             Card::new(Suit::Hearts, Rank::Two)
         );
+
+        // swapped card is placed at the bottom of the deck
+        let mut game = Moska::new(2);
+        game.new_round();
+
+        let trump_card = game.trump_card;
+        let two = Card::new(trump_card.suit, Rank::Two);
+        game.table.players[0].cards = vec![two];
+
+        assert!(game.player_action(4, 0));
+        assert_eq!(game.table.players[0].cards[0], trump_card);
+        assert_eq!(game.trump_card, two);
+        assert_eq!(game.table.deck.peek_last(), Some(&two));
+
+        // no swapping once the deck is empty
+        let mut game = Moska::new(2);
+        game.new_round();
+
+        let two = Card::new(game.trump_card.suit, Rank::Two);
+        game.table.players[0].cards = vec![two];
+        while game.table.deck.pop().is_some() {}
+
+        assert!(!game.player_action(4, 0));
+        assert_eq!(game.table.players[0].cards[0], two);
+    }
+
+    #[test]
+    fn test_controllers() {
+        let mut game = Moska::new(3);
+        game.new_round_with_seed(4);
+        assert!(game.controller_actions().is_none());
+
+        // switching controllers keeps the hand
+        let hand = game.player_cards(0);
+        game.set_bot(0, BotStrategy::Heuristic);
+        assert_eq!(
+            game.table.players[0].bot_strategy(),
+            Some(BotStrategy::Heuristic)
+        );
+        assert!(!game.controller_actions().unwrap().is_empty());
+
+        game.disconnect(0);
+        assert!(!game.table.players[0].connected());
+        assert!(!game.table.players[0].forfeited);
+        assert!(game.controller_actions().is_some());
+
+        game.set_human(0);
+        assert!(game.table.players[0].human());
+        assert_eq!(game.player_cards(0), hand);
+    }
+
+    #[test]
+    fn test_disconnect_mid_turn() {
+        use crate::game::zobrist;
+
+        for seed in 0..10 {
+            for state in [State::PlayerAttacking, State::PlayerDefending] {
+                let mut game = Moska::new(3);
+                game.new_round_with_seed(seed);
+                if state == State::PlayerDefending {
+                    game.player_action(1, 0);
+                    game.player_action(3, 0);
+                }
+                let seat = game.table.player_index;
+                let hand = game.player_cards(seat).len();
+
+                // cards on the table go back to the hand
+                game.player_action(1, 0);
+                assert_eq!(game.player_cards(seat).len(), hand - 1);
+                game.disconnect(seat);
+                assert_eq!(game.state, state);
+                assert_eq!(game.player_cards(seat).len(), hand);
+                assert_eq!(game.zobrist_hash(true), zobrist::hash(&game, true));
+
+                // and the bot playing the seat ends the turn
+                for action in game.controller_actions().unwrap() {
+                    game.player_action(action.action, action.card_index);
+                }
+                game.player_action(3, 0);
+                assert!(game.table.player_index != seat || game.state != state);
+            }
+        }
+    }
+
+    #[test]
+    fn test_forfeit() {
+        let mut game = Moska::new(3);
+        game.new_round_with_seed(4);
+        assert!(!game.forfeit(3));
+
+        // seat not in turn is skipped from then on
+        assert!(game.forfeit(1));
+        assert!(!game.forfeit(1));
+        assert!(game.player_cards(1).is_empty());
+        assert_eq!(game.discarded.len(), 6);
+        assert_eq!(game.next_player(), 2);
+
+        game.player_action(1, 0);
+        game.player_action(3, 0);
+        assert_eq!(game.table.player_index, 2);
+        assert_eq!(game.state, State::PlayerDefending);
+
+        // defender in turn, table is discarded and the next seat attacks
+        game.disconnect(2);
+        assert_eq!(game.state, State::PlayerDefending);
+        game.forfeit_rules.forfeit_disconnected = true;
+        game.disconnect(2);
+        assert!(game.attacker_cards.is_empty());
+        assert_eq!(game.discarded.len(), 13);
+        assert_eq!(game.state, State::GameOver);
+        assert_eq!(game.loser(), Some(0));
+    }
+
+    #[test]
+    fn test_forfeit_loses() {
+        let mut game = Moska::new(3);
+        game.forfeit_rules = ForfeitRules {
+            forfeit_disconnected: false,
+            forfeit_loses: true,
+        };
+        assert!(!game.forfeit(0));
+
+        game.new_round_with_seed(4);
+        assert_eq!(game.loser(), None);
+        assert!(game.forfeit(2));
+        assert_eq!(game.state, State::GameOver);
+        assert_eq!(game.loser(), Some(2));
+        assert_eq!(game.player_cards(2).len(), 6);
+
+        // new round seats everyone again
+        game.new_round_with_seed(5);
+        assert!(!game.table.players[2].forfeited);
+    }
+
+    #[test]
+    fn test_timeouts() {
+        use crate::game::clock::ManualClock;
+
+        let clock = Arc::new(ManualClock::new(0));
+        let mut game = Moska::new(3);
+        game.set_clock(
+            TimeControl::new(1_000, 0, TimeoutPolicy::AutoTake),
+            clock.clone(),
+        );
+        game.new_round_with_seed(2);
+        assert_eq!(game.time_left_ms(), Some(1_000));
+
+        // attacker runs out of time with an invalid table
+        let lowest = game
+            .player_cards(0)
+            .into_iter()
+            .min_by(|a, b| card_cmp(a, b, game.trump_card.suit))
+            .unwrap();
+        game.player_action(1, 0);
+        game.player_action(1, 0);
+        clock.advance(1_000);
+        assert!(!game.player_action(3, 0));
+        assert_eq!(game.state, State::PlayerDefending);
+        assert_eq!(game.attacker_cards, vec![lowest]);
+        assert_eq!(
+            game.timeout,
+            Some(Timeout {
+                player_index: 0,
+                policy: TimeoutPolicy::AutoSubmit,
+                turn: 0
+            })
+        );
+        assert_eq!(game.time_used_ms(0), 1_000);
+
+        // defender takes
+        clock.advance(400);
+        assert!(!game.check_clock());
+        assert_eq!(game.time_left_ms(), Some(600));
+        clock.advance(600);
+        assert!(game.check_clock());
+        assert_eq!(
+            game.timeout.map(|t| t.policy),
+            Some(TimeoutPolicy::AutoTake)
+        );
+        assert_eq!(game.player_cards(1).len(), 7);
+        assert_eq!(game.state, State::PlayerAttacking);
+        assert_eq!(game.table.player_index, 2);
+        assert_eq!(game.time_left_ms(), Some(1_000));
+        game.player_action(1, 0);
+        assert_eq!(game.timeout, None);
+
+        // forfeiting loses the game
+        game.forfeit_rules.forfeit_loses = true;
+        game.clock.as_mut().unwrap().control = TimeControl::new(0, 500, TimeoutPolicy::Forfeit);
+        clock.advance(500);
+        assert!(game.check_clock());
+        assert_eq!(game.state, State::GameOver);
+        assert_eq!(game.loser(), Some(2));
+        assert_eq!(game.timeout.map(|t| t.policy), Some(TimeoutPolicy::Forfeit));
+        assert_eq!(game.time_left_ms(), None);
+
+        // clock starts over with the round
+        game.new_round_with_seed(3);
+        assert_eq!(game.timeout, None);
+        assert_eq!(game.time_used_ms(0), 0);
+        game.clear_time_control();
+        clock.advance(10_000);
+        assert!(!game.check_clock());
+        assert_eq!(game.time_left_ms(), None);
+    }
+
+    #[test]
+    fn test_engine_actions() {
+        let mut game = Moska::new(2);
+        game.new_round_with_seed(1);
+        game.trump_card = Card::new(Suit::Spades, Rank::Ace);
+        game.table.players[0].cards = vec![
+            Card::new(Suit::Hearts, Rank::Four),
+            Card::new(Suit::Clubs, Rank::Four),
+            Card::new(Suit::Hearts, Rank::Nine),
+        ];
+        game.table.players[1].cards = vec![
+            Card::new(Suit::Hearts, Rank::Five),
+            Card::new(Suit::Spades, Rank::Three),
+            Card::new(Suit::Clubs, Rank::Two),
+        ];
+        let add = |card_index| Action {
+            action: 1,
+            card_index,
+        };
+        let submit = Action {
+            action: 3,
+            card_index: 0,
+        };
+
+        assert_eq!(game.legal_actions(), vec![add(0), add(1), add(2)]);
+
+        // a single nine can't be paired, so only the pair goes on top of a four
+        game.apply(&add(0));
+        assert_eq!(game.legal_actions(), vec![add(0), submit]);
+        game.apply(&add(0));
+        assert_eq!(game.legal_actions(), vec![submit]);
+        assert!(!game.apply(&add(0)));
+        game.apply(&submit);
+
+        // the five of hearts must cover the four of hearts for the rest to hold
+        assert_eq!(game.current_player(), 1);
+        assert_eq!(game.legal_actions(), vec![add(0), submit]);
+        game.apply(&add(0));
+        assert_eq!(game.legal_actions(), vec![add(0)]);
+        game.apply(&add(0));
+        assert_eq!(game.legal_actions(), vec![submit]);
+    }
+
+    #[test]
+    fn test_engine_take_back() {
+        let mut game = Moska::new(2);
+        game.new_round_with_seed(1);
+        game.trump_card = Card::new(Suit::Spades, Rank::Ace);
+        game.table.players[0].cards = vec![
+            Card::new(Suit::Hearts, Rank::Four),
+            Card::new(Suit::Clubs, Rank::Four),
+        ];
+        game.table.players[1].cards = vec![
+            Card::new(Suit::Hearts, Rank::Five),
+            Card::new(Suit::Diamonds, Rank::Three),
+            Card::new(Suit::Clubs, Rank::Two),
+        ];
+        let take = |card_index| Action {
+            action: 2,
+            card_index,
+        };
+        for action in [1, 1, 3] {
+            game.player_action(action, 0);
+        }
+
+        // every attacking card has a card on it, but the twos and threes
+        // don't hold, so the defender must be able to take them back
+        game.player_action(1, 2);
+        game.player_action(1, 1);
+        assert!(!game.valid());
+        assert_eq!(game.legal_actions(), vec![take(1)]);
+        assert!(game.apply(&take(1)));
+        assert_eq!(game.defender_cards.len(), 1);
+    }
+
+    #[test]
+    fn test_find_pairs() {
+        let cards = [
+            Card::new(Suit::Clubs, Rank::Four),
+            Card::new(Suit::Hearts, Rank::King),
+            Card::new(Suit::Spades, Rank::Four),
+            Card::new(Suit::Diamonds, Rank::Two),
+            Card::new(Suit::Diamonds, Rank::King),
+            Card::new(Suit::Hearts, Rank::Four),
+        ];
+        let refs: Vec<&Card> = cards.iter().collect();
+        let pairs = find_pairs(&refs);
+        assert_eq!(
+            pairs,
+            vec![
+                vec![&cards[0], &cards[2], &cards[5]],
+                vec![&cards[1], &cards[4]],
+            ]
+        );
     }
 }
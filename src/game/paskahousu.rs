@@ -0,0 +1,581 @@
+/*
+ * Game logic for Finnish Paskahousu
+ *
+ * Players take turns playing one or more cards of a rank equal to or
+ * higher than the top of the pile, refilling their hand from the deck.
+ * A two goes on anything and anything goes on a two. A ten burns any
+ * pile below the face cards and an ace burns a pile of face cards,
+ * as does a fourth card of a rank in a row. Burning lets the player
+ * go again. A player who can't or won't play picks up the pile, or may
+ * first try their luck with the top card of the deck.
+ * The last player holding cards loses.
+ */
+
+use rand::RngCore;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    ai::moska::Action,
+    deck::{Card, Rank},
+    game::{
+        engine::{loser_outcome, redeal, Engine},
+        moska::{card_rank_order, State},
+    },
+    table::Table,
+};
+
+// Cards kept in hand while the deck lasts
+const HAND_SIZE: usize = 5;
+
+#[derive(Debug)]
+enum PlayerAction {
+    AddCard = 1,
+    TakeCard = 2,
+    Submit = 3,
+    TryDeck = 4,
+}
+
+#[derive(Clone)]
+#[wasm_bindgen(getter_with_clone)]
+pub struct Paskahousu {
+    pub table: Table,
+
+    // Played cards, top last
+    pub pile: Vec<Card>,
+    // Cards about to be played by the player in turn
+    pub played_cards: Vec<Card>,
+    pub discarded: Vec<Card>,
+
+    pub state: State,
+
+    // Players who have run out of cards, in order
+    pub finished: Vec<usize>,
+
+    // Played card was turned from the deck
+    from_deck: bool,
+}
+
+#[wasm_bindgen]
+impl Paskahousu {
+    #[wasm_bindgen(constructor)]
+    pub fn new(players: u8) -> Self {
+        Self {
+            table: Table::new(players),
+            pile: vec![],
+            played_cards: vec![],
+            discarded: vec![],
+            state: State::Initial,
+            finished: vec![],
+            from_deck: false,
+        }
+    }
+
+    pub fn new_round(&mut self) {
+        self.start_round(None);
+    }
+
+    // Starts a new round with the deck shuffled from the seed
+    pub fn new_round_with_seed(&mut self, seed: u64) {
+        self.start_round(Some(seed));
+    }
+
+    fn start_round(&mut self, seed: Option<u64>) {
+        // Loser of the last round starts
+        let first = self.loser().unwrap_or_default();
+
+        self.table.round += 1;
+        self.table.reset();
+        match seed {
+            Some(seed) => self.table.deck.shuffle_with_seed(seed),
+            None => self.table.deck.shuffle(),
+        }
+        self.pile.clear();
+        self.played_cards.clear();
+        self.discarded.clear();
+        self.finished.clear();
+        self.from_deck = false;
+
+        for player in &mut self.table.players {
+            for _ in 0..HAND_SIZE {
+                player.cards.extend(self.table.deck.pop());
+            }
+        }
+
+        self.state = State::PlayerAttacking;
+        self.table.next_turn(first);
+    }
+
+    // Finds next player with cards left
+    pub fn next_player(&self) -> usize {
+        let num_players = self.table.players.len();
+        (1..=num_players)
+            .map(|offset| (self.table.player_index + offset) % num_players)
+            .find(|index| !self.table.players[*index].cards.is_empty())
+            .unwrap_or(self.table.player_index)
+    }
+
+    // Actions:
+    // 1: puts the card at the hand index to the played cards
+    // 2: takes the played card at the index back to hand
+    // 3: plays the played cards, or picks up the pile if there are none
+    // 4: turns the top card of the deck to the played cards
+    //
+    // A card turned from the deck can't be taken back and
+    // is picked up with the pile if it doesn't fit.
+    pub fn player_action(&mut self, action: usize, card_index: usize) -> bool {
+        if self.state != State::PlayerAttacking {
+            return false;
+        }
+        let player_index = self.table.player_index;
+
+        if action == PlayerAction::AddCard as usize && !self.from_deck {
+            if let Some(card) = self.table.players[player_index].pop_card(card_index) {
+                self.played_cards.push(card);
+                return true;
+            }
+        }
+
+        if action == PlayerAction::TakeCard as usize
+            && !self.from_deck
+            && card_index < self.played_cards.len()
+        {
+            let card = self.played_cards.remove(card_index);
+            self.table.players[player_index].cards.push(card);
+            return true;
+        }
+
+        if action == PlayerAction::Submit as usize {
+            if self.valid() {
+                self.play();
+                return true;
+            }
+            if self.from_deck || (self.played_cards.is_empty() && !self.pile.is_empty()) {
+                self.pick_up();
+                return true;
+            }
+        }
+
+        if action == PlayerAction::TryDeck as usize
+            && self.played_cards.is_empty()
+            && !self.pile.is_empty()
+        {
+            if let Some(card) = self.table.deck.pop() {
+                self.played_cards.push(card);
+                self.from_deck = true;
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // Played cards are of a single rank that goes on the pile
+    #[wasm_bindgen(getter)]
+    pub fn valid(&self) -> bool {
+        match self.played_cards.first() {
+            Some(card) => {
+                self.played_cards.iter().all(|c| c.rank == card.rank)
+                    && can_play(card.rank, self.pile.last())
+            }
+            None => false,
+        }
+    }
+
+    // Returns copy of player cards
+    pub fn player_cards(&self, player_index: usize) -> Vec<Card> {
+        self.table
+            .players
+            .get(player_index)
+            .map(|player| player.cards.clone())
+            .unwrap_or_default()
+    }
+
+    // Player left holding cards when the game is over
+    pub fn loser(&self) -> Option<usize> {
+        if self.state != State::GameOver {
+            return None;
+        }
+        self.table
+            .players
+            .iter()
+            .position(|player| !player.cards.is_empty())
+    }
+}
+
+impl Paskahousu {
+    fn play(&mut self) {
+        let rank = self.played_cards[0].rank;
+        self.pile.append(&mut self.played_cards);
+        self.from_deck = false;
+        self.draw_cards();
+
+        let four_of_a_kind = self.pile.len() >= 4
+            && self.pile[self.pile.len() - 4..]
+                .iter()
+                .all(|card| card.rank == rank);
+        let burns = matches!(rank, Rank::Ten | Rank::Ace) || four_of_a_kind;
+        if burns {
+            self.discarded.append(&mut self.pile);
+        }
+
+        let player_index = self.table.player_index;
+        if self.table.players[player_index].cards.is_empty() {
+            self.finished.push(player_index);
+            self.next_turn();
+        } else if burns {
+            // Same player goes again
+            self.table.next_turn(player_index);
+        } else {
+            self.next_turn();
+        }
+    }
+
+    fn pick_up(&mut self) {
+        let player = &mut self.table.players[self.table.player_index];
+        player.cards.append(&mut self.pile);
+        player.cards.append(&mut self.played_cards);
+        self.from_deck = false;
+        self.next_turn();
+    }
+
+    // Fills the hand of the player in turn from the deck
+    fn draw_cards(&mut self) {
+        let player = &mut self.table.players[self.table.player_index];
+        while player.cards.len() < HAND_SIZE {
+            match self.table.deck.pop() {
+                Some(card) => player.cards.push(card),
+                None => break,
+            }
+        }
+    }
+
+    // Ends the game when a single player holds cards
+    fn next_turn(&mut self) {
+        let players_with_cards = self
+            .table
+            .players
+            .iter()
+            .filter(|player| !player.cards.is_empty())
+            .count();
+        if players_with_cards <= 1 {
+            self.state = State::GameOver;
+            return;
+        }
+        self.table.next_turn(self.next_player());
+    }
+}
+
+impl Engine for Paskahousu {
+    type Action = Action;
+
+    fn num_players(&self) -> usize {
+        self.table.players.len()
+    }
+
+    fn current_player(&self) -> usize {
+        self.table.player_index
+    }
+
+    // Cards of the rank being played that go on the pile, playing them,
+    // picking up the pile or trying the deck.
+    // Played cards are never taken back.
+    fn legal_actions(&self) -> Vec<Action> {
+        if self.state != State::PlayerAttacking {
+            return vec![];
+        }
+        let action = |action: PlayerAction, card_index: usize| Action {
+            action: action as usize,
+            card_index,
+        };
+        let mut actions = vec![];
+
+        if !self.from_deck {
+            let top = self.pile.last();
+            let played_rank = self.played_cards.first().map(|card| card.rank);
+            for (card_index, card) in self.table.players[self.table.player_index]
+                .cards
+                .iter()
+                .enumerate()
+            {
+                let same_rank = played_rank.is_none_or(|rank| rank == card.rank);
+                if same_rank && can_play(card.rank, top) {
+                    actions.push(action(PlayerAction::AddCard, card_index));
+                }
+            }
+        }
+
+        let picks_up = self.played_cards.is_empty() && !self.pile.is_empty();
+        if self.valid() || self.from_deck || picks_up {
+            actions.push(action(PlayerAction::Submit, 0));
+        }
+        if picks_up && self.table.deck.count() > 0 {
+            actions.push(action(PlayerAction::TryDeck, 0));
+        }
+        actions
+    }
+
+    fn apply(&mut self, action: &Action) -> bool {
+        self.legal_actions().contains(action)
+            && self.player_action(action.action, action.card_index)
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.state == State::GameOver
+    }
+
+    fn outcome(&self) -> Option<Vec<f32>> {
+        self.is_terminal()
+            .then(|| loser_outcome(self.table.players.len(), self.loser()))
+    }
+
+    fn determinize(&self, player_index: usize, rng: &mut dyn RngCore) -> Self {
+        let mut game = self.clone();
+        redeal(&mut game.table, player_index, false, rng);
+        game
+    }
+}
+
+// Rank can be played on the top card of the pile
+pub fn can_play(rank: Rank, top: Option<&Card>) -> bool {
+    let is_face = |rank: Rank| matches!(rank, Rank::Jack | Rank::Queen | Rank::King);
+
+    match (rank, top.map(|card| card.rank)) {
+        (Rank::Two, _) | (_, None) | (_, Some(Rank::Two)) => true,
+        (Rank::Ten, Some(top)) => !is_face(top) && top != Rank::Ace,
+        (Rank::Ace, Some(top)) => is_face(top),
+        (rank, Some(top)) => card_rank_order(rank) >= card_rank_order(top),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ai::paskahousu_ai::PaskahousuAI,
+        deck::Suit,
+        game::testing::{self, card, deal, play_out},
+    };
+
+    // Game of the given hands and pile, with the deck from bottom to top
+    fn game(hands: Vec<Vec<Card>>, pile: Vec<Card>, deck: Vec<Card>) -> Paskahousu {
+        let mut game = Paskahousu::new(hands.len() as u8);
+        game.state = State::PlayerAttacking;
+        game.pile = pile;
+        deal(&mut game.table, hands, deck);
+        game
+    }
+
+    fn count_cards(game: &Paskahousu) -> usize {
+        let piles = [&game.pile, &game.played_cards, &game.discarded];
+        testing::count_cards(&game.table, &piles.map(Vec::as_slice))
+    }
+
+    #[test]
+    fn test_can_play() {
+        let top = |rank: Rank| card(rank, Suit::Clubs);
+
+        assert!(can_play(Rank::Three, None));
+        assert!(can_play(Rank::Five, Some(&top(Rank::Five))));
+        assert!(!can_play(Rank::Four, Some(&top(Rank::Five))));
+        assert!(can_play(Rank::Three, Some(&top(Rank::Two))));
+        assert!(can_play(Rank::Two, Some(&top(Rank::King))));
+
+        assert!(can_play(Rank::Ten, Some(&top(Rank::Nine))));
+        assert!(!can_play(Rank::Ten, Some(&top(Rank::Jack))));
+        assert!(can_play(Rank::Jack, Some(&top(Rank::Ten))));
+
+        assert!(can_play(Rank::Ace, Some(&top(Rank::Queen))));
+        assert!(!can_play(Rank::Ace, Some(&top(Rank::Nine))));
+        assert!(!can_play(Rank::King, Some(&top(Rank::Ace))));
+    }
+
+    #[test]
+    fn test_play() {
+        let mut game = game(
+            vec![
+                vec![
+                    card(Rank::Six, Suit::Clubs),
+                    card(Rank::Six, Suit::Hearts),
+                    card(Rank::Four, Suit::Clubs),
+                ],
+                vec![card(Rank::Nine, Suit::Clubs)],
+            ],
+            vec![card(Rank::Five, Suit::Spades)],
+            vec![
+                card(Rank::Three, Suit::Spades),
+                card(Rank::Seven, Suit::Spades),
+                card(Rank::Eight, Suit::Spades),
+            ],
+        );
+
+        // mixed ranks and low cards don't go
+        game.player_action(1, 2);
+        assert!(!game.valid());
+        assert!(!game.player_action(3, 0));
+        game.player_action(2, 0);
+        game.player_action(1, 1);
+        game.player_action(1, 0);
+        assert!(game.valid());
+        assert!(game.player_action(3, 0));
+
+        assert_eq!(game.pile.len(), 3);
+        assert_eq!(game.table.player_index, 1);
+
+        // hand is refilled from the deck
+        assert_eq!(
+            game.player_cards(0),
+            vec![
+                card(Rank::Four, Suit::Clubs),
+                card(Rank::Eight, Suit::Spades),
+                card(Rank::Seven, Suit::Spades),
+                card(Rank::Three, Suit::Spades),
+            ]
+        );
+        assert_eq!(count_cards(&game), 8);
+    }
+
+    #[test]
+    fn test_burn() {
+        let mut game = game(
+            vec![
+                vec![card(Rank::Ten, Suit::Clubs), card(Rank::Ace, Suit::Clubs)],
+                vec![card(Rank::Queen, Suit::Clubs)],
+            ],
+            vec![card(Rank::Nine, Suit::Spades)],
+            vec![],
+        );
+
+        // ace doesn't go on a nine
+        game.player_action(1, 1);
+        assert!(!game.valid());
+        game.player_action(2, 0);
+
+        // ten burns the pile and the player goes again
+        game.player_action(1, 0);
+        assert!(game.player_action(3, 0));
+        assert!(game.pile.is_empty());
+        assert_eq!(game.discarded.len(), 2);
+        assert_eq!(game.table.player_index, 0);
+
+        // nothing to pick up
+        assert!(!game.player_action(3, 0));
+
+        // fourth card of a rank in a row burns the pile
+        let mut game = self::game(
+            vec![
+                vec![
+                    card(Rank::Seven, Suit::Clubs),
+                    card(Rank::Three, Suit::Clubs),
+                ],
+                vec![card(Rank::Queen, Suit::Clubs)],
+            ],
+            vec![
+                card(Rank::Seven, Suit::Spades),
+                card(Rank::Seven, Suit::Hearts),
+                card(Rank::Seven, Suit::Diamonds),
+            ],
+            vec![],
+        );
+        game.player_action(1, 0);
+        game.player_action(3, 0);
+        assert_eq!(game.discarded.len(), 4);
+        assert_eq!(game.table.player_index, 0);
+    }
+
+    #[test]
+    fn test_pick_up() {
+        let mut game = game(
+            vec![
+                vec![card(Rank::Three, Suit::Clubs)],
+                vec![
+                    card(Rank::Queen, Suit::Clubs),
+                    card(Rank::Three, Suit::Diamonds),
+                    card(Rank::Four, Suit::Diamonds),
+                    card(Rank::Five, Suit::Diamonds),
+                    card(Rank::Six, Suit::Diamonds),
+                    card(Rank::Eight, Suit::Diamonds),
+                ],
+            ],
+            vec![card(Rank::Nine, Suit::Spades)],
+            vec![
+                card(Rank::King, Suit::Spades),
+                card(Rank::Four, Suit::Spades),
+            ],
+        );
+
+        // a four off the deck doesn't go, and is picked up with the pile
+        assert!(game.player_action(4, 0));
+        assert!(!game.player_action(2, 0));
+        assert!(!game.player_action(1, 0));
+        assert!(game.player_action(3, 0));
+        assert_eq!(game.player_cards(0).len(), 3);
+        assert!(game.pile.is_empty());
+        assert_eq!(game.table.player_index, 1);
+
+        // nothing to try on an empty pile
+        assert!(!game.player_action(4, 0));
+        game.player_action(1, 0);
+        game.player_action(3, 0);
+
+        // a king off the deck goes
+        game.player_action(4, 0);
+        assert!(game.valid());
+        game.player_action(3, 0);
+        assert_eq!(game.pile.len(), 2);
+        assert_eq!(game.player_cards(0).len(), 3);
+        assert_eq!(game.table.player_index, 1);
+        assert_eq!(count_cards(&game), 10);
+    }
+
+    #[test]
+    fn test_game_over() {
+        let mut game = game(
+            vec![
+                vec![card(Rank::Five, Suit::Clubs)],
+                vec![card(Rank::Six, Suit::Clubs)],
+                vec![card(Rank::Seven, Suit::Clubs)],
+            ],
+            vec![],
+            vec![],
+        );
+
+        game.player_action(1, 0);
+        game.player_action(3, 0);
+        assert_eq!(game.finished, vec![0]);
+        assert_eq!(game.table.player_index, 1);
+        assert_eq!(game.loser(), None);
+
+        game.player_action(1, 0);
+        game.player_action(3, 0);
+        assert_eq!(game.state, State::GameOver);
+        assert_eq!(game.finished, vec![0, 1]);
+        assert_eq!(game.loser(), Some(2));
+        assert!(!game.player_action(3, 0));
+
+        // loser starts the next round
+        game.new_round_with_seed(1);
+        assert_eq!(game.table.player_index, 2);
+        assert!(game.finished.is_empty());
+        assert_eq!(game.table.deck.count(), 52 - 15);
+    }
+
+    #[test]
+    fn test_bots_finish() {
+        for seed in 0..20 {
+            let players = 2 + seed as u8 % 5;
+            let mut game = Paskahousu::new(players);
+            game.new_round_with_seed(seed);
+
+            let finished = play_out(&mut game, |game| {
+                let bot = PaskahousuAI::new(game.table.player_index);
+                for action in bot.get_actions(game) {
+                    assert!(game.player_action(action.action, action.card_index));
+                }
+                assert!(game.player_action(3, 0));
+                assert_eq!(count_cards(game), 52);
+            });
+            assert!(finished);
+            assert_eq!(game.finished.len(), players as usize - 1);
+        }
+    }
+}
@@ -0,0 +1,578 @@
+/*
+ * Text records of Moska games, in the spirit of chess PGN.
+ *
+ * A record starts with header tags and lists the moves of the round,
+ * a numbered line for each attack and the moves following it:
+ *
+ *     [Players "2"]
+ *     [Rules "standard"]
+ *     [Seed "42"]
+ *     [Trump "4♠"]
+ *     [Result "P1"]
+ *
+ *     1. P0 attack 7♣ 7♦; P1 take
+ *     2. P0 swap 4♠; P0 attack 9♥; P1 defend J♥
+ *
+ * The result names the player left holding the cards, `*` while the game
+ * is on and `-` when nobody was. Games are replayed from the seed, so only
+ * seeded rounds can be recorded.
+ */
+
+use std::fmt::{self, Display};
+
+use crate::{
+    deck::Card,
+    game::moska::{ForfeitRules, State},
+    Moska,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MoveKind {
+    // Cards were put on the table and submitted
+    Attack,
+    // Table was covered, cards hold the defending cards
+    Defend,
+    // Defender took the attacking cards
+    Take,
+    // Two of trumps was swapped, cards hold the card taken
+    Swap,
+    // Player left the game
+    Forfeit,
+}
+
+impl MoveKind {
+    fn name(self) -> &'static str {
+        match self {
+            MoveKind::Attack => "attack",
+            MoveKind::Defend => "defend",
+            MoveKind::Take => "take",
+            MoveKind::Swap => "swap",
+            MoveKind::Forfeit => "forfeit",
+        }
+    }
+}
+
+// Completed move of a player
+#[derive(Clone, Debug, PartialEq)]
+pub struct Move {
+    pub player_index: usize,
+    pub kind: MoveKind,
+    pub cards: Vec<Card>,
+}
+
+impl Move {
+    pub fn new(player_index: usize, kind: MoveKind, cards: Vec<Card>) -> Self {
+        Self {
+            player_index,
+            kind,
+            cards,
+        }
+    }
+}
+
+impl Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "P{} {}", self.player_index, self.kind.name())?;
+        for card in &self.cards {
+            write!(f, " {card}")?;
+        }
+        Ok(())
+    }
+}
+
+// Malformed or illegal input, positions start from 1
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl RecordError {
+    fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameRecord {
+    pub players: u8,
+    pub rules: ForfeitRules,
+    pub seed: u64,
+    // Trump card dealt, before any swaps
+    pub trump_card: Card,
+    pub finished: bool,
+    // Player left holding the cards
+    pub loser: Option<usize>,
+    pub moves: Vec<Move>,
+}
+
+impl GameRecord {
+    // Record of the round so far, nothing when it was not dealt from a seed
+    pub fn from_game(game: &Moska) -> Option<Self> {
+        let trump_card = game
+            .history
+            .iter()
+            .find(|mv| mv.kind == MoveKind::Swap)
+            .and_then(|mv| mv.cards.first().copied())
+            .unwrap_or(game.trump_card);
+
+        Some(Self {
+            players: game.table.players.len() as u8,
+            rules: game.forfeit_rules,
+            seed: game.seed?,
+            trump_card,
+            finished: game.state == State::GameOver,
+            loser: game.loser(),
+            moves: game.history.clone(),
+        })
+    }
+
+    pub fn to_text(&self) -> String {
+        let result = match (self.finished, self.loser) {
+            (false, _) => "*".to_string(),
+            (true, Some(loser)) => format!("P{loser}"),
+            (true, None) => "-".to_string(),
+        };
+        let mut text = format!(
+            "[Players \"{}\"]\n[Rules \"{}\"]\n[Seed \"{}\"]\n[Trump \"{}\"]\n[Result \"{}\"]\n\n",
+            self.players,
+            rules_text(&self.rules),
+            self.seed,
+            self.trump_card,
+            result
+        );
+
+        let mut number = 0;
+        for (index, mv) in self.moves.iter().enumerate() {
+            if index == 0 || mv.kind == MoveKind::Attack {
+                if number > 0 {
+                    text.push('\n');
+                }
+                number += 1;
+                text.push_str(&format!("{number}. "));
+            } else {
+                text.push_str("; ");
+            }
+            text.push_str(&mv.to_string());
+        }
+        if number > 0 {
+            text.push('\n');
+        }
+        text
+    }
+
+    // Parses the record, replaying the moves to check they are legal
+    pub fn from_text(text: &str) -> Result<Self, RecordError> {
+        let mut tags: Vec<(usize, &str, &str)> = vec![];
+        let mut move_lines: Vec<(usize, &str)> = vec![];
+
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            if trimmed.starts_with('[') {
+                if !move_lines.is_empty() {
+                    return Err(RecordError::new(
+                        number,
+                        column(line, line.trim_start()),
+                        "Header tag after the moves",
+                    ));
+                }
+                let (key, value) = parse_tag(trimmed).ok_or_else(|| {
+                    RecordError::new(number, column(line, line.trim_start()), "Malformed tag")
+                })?;
+                if tags.iter().any(|(_, other, _)| *other == key) {
+                    return Err(RecordError::new(
+                        number,
+                        column(line, line.trim_start()),
+                        format!("Duplicate tag '{key}'"),
+                    ));
+                }
+                tags.push((number, key, value));
+            } else {
+                move_lines.push((number, line));
+            }
+        }
+
+        let tag = |key: &str| tags.iter().find(|(_, other, _)| *other == key).copied();
+        let missing = |key: &str| RecordError::new(1, 1, format!("Missing tag '{key}'"));
+        let invalid = |(line, key, _): (usize, &str, &str)| {
+            RecordError::new(line, 1, format!("Invalid value for tag '{key}'"))
+        };
+
+        let players_tag = tag("Players").ok_or_else(|| missing("Players"))?;
+        let players = players_tag
+            .2
+            .parse()
+            .ok()
+            .filter(|players| (2..=8).contains(players))
+            .ok_or_else(|| invalid(players_tag))?;
+        let seed_tag = tag("Seed").ok_or_else(|| missing("Seed"))?;
+        let seed = seed_tag.2.parse().map_err(|_| invalid(seed_tag))?;
+        let rules = match tag("Rules") {
+            Some(rules_tag) => parse_rules(rules_tag.2).ok_or_else(|| invalid(rules_tag))?,
+            None => ForfeitRules::default(),
+        };
+
+        let mut game = Moska::new(players);
+        game.forfeit_rules = rules;
+        game.new_round_with_seed(seed);
+        let trump_card = game.trump_card;
+
+        if let Some(trump_tag) = tag("Trump") {
+            let card = trump_tag
+                .2
+                .parse::<Card>()
+                .map_err(|_| invalid(trump_tag))?;
+            if card != trump_card {
+                return Err(RecordError::new(
+                    trump_tag.0,
+                    1,
+                    format!("Seed deals trump {trump_card}, not {card}"),
+                ));
+            }
+        }
+
+        let mut moves = vec![];
+        for (expected, (number, line)) in (1..).zip(move_lines) {
+            let trimmed = line.trim_start();
+            let start = column(line, line.trim_start());
+            let (label, rest) = trimmed.split_once(' ').unwrap_or((trimmed, ""));
+            if label != format!("{expected}.") {
+                return Err(RecordError::new(
+                    number,
+                    start,
+                    format!("Expected move number '{expected}.'"),
+                ));
+            }
+
+            let mut offset = line.len() - rest.len();
+            for part in rest.split(';') {
+                let position = offset + part.len() - part.trim_start().len();
+                offset += part.len() + 1;
+                let column = line[..position].chars().count() + 1;
+
+                let mv = parse_move(part).map_err(|err| RecordError::new(number, column, err))?;
+                replay_move(&mut game, &mv).map_err(|err| RecordError::new(number, column, err))?;
+                moves.push(mv);
+            }
+        }
+
+        if let Some(result_tag) = tag("Result") {
+            let result = match result_tag.2 {
+                "*" => Some((false, None)),
+                "-" => Some((true, None)),
+                value => value
+                    .strip_prefix('P')
+                    .and_then(|seat| seat.parse().ok())
+                    .map(|seat| (true, Some(seat))),
+            };
+            let played = (game.state == State::GameOver, game.loser());
+            match result {
+                None => return Err(invalid(result_tag)),
+                Some(result) if result != played => {
+                    return Err(RecordError::new(
+                        result_tag.0,
+                        1,
+                        "Result does not match the moves",
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            players,
+            rules,
+            seed,
+            trump_card,
+            finished: game.state == State::GameOver,
+            loser: game.loser(),
+            moves,
+        })
+    }
+
+    // Plays the moves from the deal
+    pub fn replay(&self) -> Result<Moska, String> {
+        let mut game = Moska::new(self.players);
+        game.forfeit_rules = self.rules;
+        game.new_round_with_seed(self.seed);
+
+        for (index, mv) in self.moves.iter().enumerate() {
+            replay_move(&mut game, mv).map_err(|err| format!("Move {}: {err}", index + 1))?;
+        }
+        Ok(game)
+    }
+}
+
+impl Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_text())
+    }
+}
+
+// Column of the part within the line
+fn column(line: &str, part: &str) -> usize {
+    line[..line.len() - part.len()].chars().count() + 1
+}
+
+fn rules_text(rules: &ForfeitRules) -> String {
+    let mut flags = vec![];
+    if rules.forfeit_disconnected {
+        flags.push("forfeit-disconnected");
+    }
+    if rules.forfeit_loses {
+        flags.push("forfeit-loses");
+    }
+    match flags.is_empty() {
+        true => "standard".to_string(),
+        false => flags.join(" "),
+    }
+}
+
+fn parse_rules(text: &str) -> Option<ForfeitRules> {
+    let mut rules = ForfeitRules::default();
+    if text == "standard" {
+        return Some(rules);
+    }
+    for flag in text.split_whitespace() {
+        match flag {
+            "forfeit-disconnected" => rules.forfeit_disconnected = true,
+            "forfeit-loses" => rules.forfeit_loses = true,
+            _ => return None,
+        }
+    }
+    Some(rules)
+}
+
+// Tag of the form `[Key "value"]`
+fn parse_tag(line: &str) -> Option<(&str, &str)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (key, value) = inner.split_once(' ')?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    (!key.is_empty() && !value.contains('"')).then_some((key, value))
+}
+
+fn parse_move(text: &str) -> Result<Move, String> {
+    let mut words = text.split_whitespace();
+    let player = words.next().ok_or("Expected a move")?;
+    let player_index = player
+        .strip_prefix('P')
+        .and_then(|seat| seat.parse().ok())
+        .ok_or_else(|| format!("Expected a player like 'P0', found '{player}'"))?;
+
+    let kind = match words.next() {
+        Some("attack") => MoveKind::Attack,
+        Some("defend") => MoveKind::Defend,
+        Some("take") => MoveKind::Take,
+        Some("swap") => MoveKind::Swap,
+        Some("forfeit") => MoveKind::Forfeit,
+        Some(word) => return Err(format!("Unknown move '{word}'")),
+        None => return Err("Expected a move after the player".to_string()),
+    };
+
+    let cards = words
+        .map(|word| {
+            word.parse::<Card>()
+                .map_err(|err| format!("Invalid card '{word}': {err}"))
+        })
+        .collect::<Result<Vec<Card>, String>>()?;
+    let expected = match kind {
+        MoveKind::Attack | MoveKind::Defend => !cards.is_empty(),
+        MoveKind::Take | MoveKind::Forfeit => cards.is_empty(),
+        MoveKind::Swap => cards.len() == 1,
+    };
+    if !expected {
+        return Err(format!("Wrong number of cards to {}", kind.name()));
+    }
+
+    Ok(Move::new(player_index, kind, cards))
+}
+
+// Plays the move, checking the game records it as it was given
+fn replay_move(game: &mut Moska, mv: &Move) -> Result<(), String> {
+    if mv.kind == MoveKind::Forfeit {
+        return match game.forfeit(mv.player_index) {
+            true => Ok(()),
+            false => Err(format!("P{} cannot forfeit", mv.player_index)),
+        };
+    }
+    if game.state == State::GameOver {
+        return Err("Game is over".to_string());
+    }
+    if mv.player_index != game.table.player_index {
+        return Err(format!("P{} is not in turn", mv.player_index));
+    }
+
+    let played = game.history.len();
+    match mv.kind {
+        MoveKind::Swap => {
+            game.player_action(4, 0);
+        }
+        _ => {
+            for card in &mv.cards {
+                let card_index = game.table.players[mv.player_index]
+                    .card_index(card)
+                    .ok_or_else(|| format!("P{} does not hold {card}", mv.player_index))?;
+                game.player_action(1, card_index);
+            }
+            game.player_action(3, 0);
+        }
+    }
+
+    match game.history.get(played) {
+        Some(recorded) if recorded == mv => Ok(()),
+        _ => Err(format!("Illegal move '{mv}'")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::{heuristic_turn, play_out};
+
+    fn played_game(seed: u64, players: u8) -> Moska {
+        let mut game = Moska::new(players);
+        game.new_round_with_seed(seed);
+        assert!(play_out(&mut game, heuristic_turn));
+        game
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for seed in 0..10 {
+            let mut game = played_game(seed, 2 + seed as u8 % 3);
+            if seed == 3 {
+                game = Moska::new(3);
+                game.new_round_with_seed(seed);
+                game.player_action(1, 0);
+                game.player_action(3, 0);
+                game.forfeit(2);
+            }
+
+            let record = GameRecord::from_game(&game).unwrap();
+            let text = record.to_text();
+            assert_eq!(GameRecord::from_text(&text), Ok(record.clone()));
+
+            let replayed = record.replay().unwrap();
+            assert_eq!(replayed.history, game.history);
+            assert_eq!(replayed.state, game.state);
+            assert_eq!(replayed.loser(), game.loser());
+            assert_eq!(replayed.zobrist_hash(true), game.zobrist_hash(true));
+        }
+
+        let mut game = Moska::new(2);
+        game.new_round();
+        assert_eq!(GameRecord::from_game(&game), None);
+    }
+
+    #[test]
+    fn test_search_copies() {
+        use crate::game::engine::Engine;
+        use rand::{rngs::StdRng, SeedableRng};
+
+        // searching bots don't copy the record
+        let game = played_game(4, 3);
+        assert!(!game.history.is_empty());
+        let copy = game.determinize(1, &mut StdRng::seed_from_u64(0));
+        assert!(copy.history.is_empty());
+        assert_eq!(copy.seed, None);
+    }
+
+    #[test]
+    fn test_text() {
+        let mut game = Moska::new(2);
+        game.new_round_with_seed(1);
+        let trump = game.trump_card;
+        let hand = game.player_cards(0);
+
+        game.player_action(1, 0);
+        game.player_action(3, 0);
+        game.player_action(3, 0);
+
+        let text = GameRecord::from_game(&game).unwrap().to_text();
+        assert_eq!(
+            text,
+            format!(
+                "[Players \"2\"]\n[Rules \"standard\"]\n[Seed \"1\"]\n[Trump \"{trump}\"]\n\
+                 [Result \"*\"]\n\n1. P0 attack {}; P1 take\n",
+                hand[0]
+            )
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut game = Moska::new(2);
+        game.new_round_with_seed(1);
+        let trump = game.trump_card;
+        let card = game.player_cards(0)[0];
+        let other = game.player_cards(1)[0];
+        let header = format!("[Players \"2\"]\n[Seed \"1\"]\n[Trump \"{trump}\"]\n\n");
+
+        let error = |text: &str| GameRecord::from_text(text).unwrap_err();
+        let at = |error: RecordError| (error.line, error.column);
+
+        // tags
+        assert_eq!(at(error("[Seed \"1\"]\n")), (1, 1));
+        assert_eq!(at(error("[Players \"2\"]\n  [Seed 1]\n")), (2, 3));
+        assert_eq!(at(error("[Players \"9\"]\n[Seed \"1\"]\n")), (1, 1));
+        assert_eq!(
+            at(error("[Players \"2\"]\n[Seed \"2\"]\n[Trump \"A♠\"]\n")),
+            (3, 1)
+        );
+        assert_eq!(at(error(&format!("{header}[Result \"P1\"]\n"))), (5, 1));
+        assert_eq!(
+            at(error(&format!("{header}1. P0 take\n[Seed \"1\"]\n"))),
+            (6, 1)
+        );
+
+        // moves
+        assert_eq!(at(error(&format!("{header}2. P0 attack {card}\n"))), (5, 1));
+        let width = card.to_string().chars().count();
+        assert_eq!(
+            at(error(&format!("{header}1. P0 attack {card}; X1 take\n"))),
+            (5, 16 + width)
+        );
+        assert_eq!(at(error(&format!("{header}1. P0 attack 1♣\n"))), (5, 4));
+        assert_eq!(at(error(&format!("{header}1. P0 dance\n"))), (5, 4));
+        assert_eq!(
+            at(error(&format!("{header}1. P1 attack {other}\n"))),
+            (5, 4)
+        );
+        assert_eq!(
+            at(error(&format!("{header}1. P0 attack {other}\n"))),
+            (5, 4)
+        );
+        assert_eq!(
+            error(&format!("{header}1. P0 attack {card};  P1 defend {card}\n")),
+            RecordError::new(5, 17 + width, format!("P1 does not hold {card}"))
+        );
+        assert_eq!(
+            error(&format!("{header}1. P0 take\n")).to_string(),
+            "line 5, column 4: Illegal move 'P0 take'"
+        );
+
+        let text = format!("{header}1. P0 attack {card}; P1 take\n");
+        assert_eq!(GameRecord::from_text(&text).unwrap().moves.len(), 2);
+    }
+}
@@ -0,0 +1,481 @@
+/*
+ * Spectator views of a game.
+ *
+ * Public spectators see what every player sees: the table, trump card
+ * and card counts. Omniscient spectators also see every hand and the
+ * deck order, but only once the game has moved a given number of turns
+ * past what they are shown.
+ */
+
+use std::collections::VecDeque;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{deck::Card, game::moska::State, table::Player, Moska};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[wasm_bindgen]
+pub enum SpectatorMode {
+    // Hands and deck stay hidden
+    Public,
+    // Hands and deck are shown after a delay
+    Omniscient,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[wasm_bindgen]
+pub enum EventKind {
+    // Cards were dealt, cards hold the trump card
+    NewRound,
+    // Player swapped the two of trumps, cards hold the card taken
+    SwapTrump,
+    Attack,
+    // Table was cleared, cards hold the defending cards
+    Defend,
+    // Defender took the attacking cards
+    Take,
+    // Player is out of cards
+    Finish,
+    // Player left the game
+    Forfeit,
+    // Player is left holding the cards
+    GameOver,
+}
+
+// Something that happened in the game
+#[derive(Clone, Debug, PartialEq)]
+#[wasm_bindgen(getter_with_clone)]
+pub struct GameEvent {
+    pub kind: EventKind,
+    pub player_index: usize,
+    pub cards: Vec<Card>,
+    pub round: usize,
+    pub turn: usize,
+}
+
+#[derive(Clone)]
+#[wasm_bindgen(getter_with_clone)]
+pub struct SpectatorView {
+    pub round: usize,
+    pub turn: usize,
+    pub state: State,
+    // Player in turn
+    pub player_index: usize,
+    pub trump_card: Card,
+    pub deck_count: usize,
+    pub discarded_count: usize,
+    // Number of cards in each player's hand
+    pub hand_counts: Vec<usize>,
+    pub attacker_cards: Vec<Card>,
+    pub defender_cards: Vec<Card>,
+    // Hands and deck are included
+    pub revealed: bool,
+    // Every player's hand, empty when not revealed
+    pub hands: Vec<Player>,
+    // Deck from top to bottom, empty when not revealed
+    pub deck: Vec<Card>,
+    // Turns the game has gone past the view
+    pub behind: usize,
+}
+
+impl SpectatorView {
+    pub(crate) fn new(game: &Moska, revealed: bool, behind: usize) -> Self {
+        Self {
+            round: game.table.round,
+            turn: game.table.turn,
+            state: game.state,
+            player_index: game.table.player_index,
+            trump_card: game.trump_card,
+            deck_count: game.table.deck.count(),
+            discarded_count: game.discarded.len(),
+            hand_counts: game.table.players.iter().map(|p| p.cards.len()).collect(),
+            attacker_cards: game.attacker_cards.clone(),
+            defender_cards: game.defender_cards.clone(),
+            revealed,
+            hands: match revealed {
+                true => game.table.players.clone(),
+                false => vec![],
+            },
+            deck: match revealed {
+                true => game.table.deck.cards().into_iter().rev().collect(),
+                false => vec![],
+            },
+            behind,
+        }
+    }
+}
+
+// Game as observed at some point
+struct Snapshot {
+    game: Moska,
+    // Events known at the time
+    events: usize,
+}
+
+#[wasm_bindgen]
+pub struct Spectator {
+    mode: SpectatorMode,
+    // Turns the omniscient view lags behind
+    delay: usize,
+    // Latest snapshot last, the first one is shown
+    snapshots: VecDeque<Snapshot>,
+    events: Vec<GameEvent>,
+}
+
+#[wasm_bindgen]
+impl Spectator {
+    // Starts watching the game from its current position
+    #[wasm_bindgen(constructor)]
+    pub fn new(game: &Moska, mode: SpectatorMode, delay: usize) -> Self {
+        Self {
+            mode,
+            delay,
+            snapshots: VecDeque::from([Snapshot {
+                game: game.clone(),
+                events: 0,
+            }]),
+            events: vec![],
+        }
+    }
+
+    // Records what happened since the last observation.
+    // Must be called at least after every submitted move.
+    pub fn observe(&mut self, game: &Moska) {
+        let latest = &self
+            .snapshots
+            .back()
+            .expect("Spectator has a snapshot")
+            .game;
+        self.events.extend(events_between(latest, game));
+
+        let snapshot = Snapshot {
+            game: game.clone(),
+            events: self.events.len(),
+        };
+        if self.mode == SpectatorMode::Public {
+            self.snapshots = VecDeque::from([snapshot]);
+            return;
+        }
+        self.snapshots.push_back(snapshot);
+
+        // Keep the latest snapshot that is old enough to show
+        let turn = game.table.turn;
+        while self
+            .snapshots
+            .get(1)
+            .is_some_and(|next| next.game.table.turn + self.delay <= turn)
+        {
+            self.snapshots.pop_front();
+        }
+    }
+
+    // Snapshot the spectator is shown and whether it is revealed
+    fn shown(&self) -> (&Snapshot, bool) {
+        let latest = self.snapshots.back().expect("Spectator has a snapshot");
+        match self.mode {
+            SpectatorMode::Public => (latest, false),
+            // Nothing is left to hide after the game
+            SpectatorMode::Omniscient if latest.game.state == State::GameOver => (latest, true),
+            SpectatorMode::Omniscient => {
+                let first = &self.snapshots[0];
+                (
+                    first,
+                    first.game.table.turn + self.delay <= latest.game.table.turn,
+                )
+            }
+        }
+    }
+
+    pub fn view(&self) -> SpectatorView {
+        let (snapshot, revealed) = self.shown();
+        let latest = &self
+            .snapshots
+            .back()
+            .expect("Spectator has a snapshot")
+            .game;
+        SpectatorView::new(
+            &snapshot.game,
+            revealed,
+            latest.table.turn - snapshot.game.table.turn,
+        )
+    }
+
+    // Events since joining, up to the position shown
+    pub fn events(&self) -> Vec<GameEvent> {
+        self.events_since(0)
+    }
+
+    // Events after the first `index` events, for polling the feed
+    pub fn events_since(&self, index: usize) -> Vec<GameEvent> {
+        let (snapshot, _) = self.shown();
+        self.events
+            .get(index..snapshot.events)
+            .unwrap_or_default()
+            .to_vec()
+    }
+}
+
+// Player has no cards left to play
+fn is_out(game: &Moska, player_index: usize) -> bool {
+    let on_table = match game.state {
+        State::PlayerAttacking => !game.attacker_cards.is_empty(),
+        State::PlayerDefending => !game.defender_cards.is_empty(),
+        _ => false,
+    };
+
+    let player = &game.table.players[player_index];
+    let played_out = player.cards.is_empty()
+        && game.table.deck.count() == 0
+        && !(player_index == game.table.player_index && on_table);
+    player.forfeited || played_out
+}
+
+// Public events that take the game from one position to the other
+fn events_between(prev: &Moska, game: &Moska) -> Vec<GameEvent> {
+    let event = |kind, player_index, cards| GameEvent {
+        kind,
+        player_index,
+        cards,
+        round: game.table.round,
+        turn: game.table.turn,
+    };
+
+    if game.table.round != prev.table.round {
+        return vec![event(
+            EventKind::NewRound,
+            game.table.player_index,
+            vec![game.trump_card],
+        )];
+    }
+
+    let mut events = vec![];
+    let player_index = prev.table.player_index;
+
+    if game.trump_card != prev.trump_card {
+        events.push(event(
+            EventKind::SwapTrump,
+            player_index,
+            vec![prev.trump_card],
+        ));
+    }
+
+    match prev.state {
+        State::PlayerAttacking
+            if game.state != State::PlayerAttacking && !game.attacker_cards.is_empty() =>
+        {
+            events.push(event(
+                EventKind::Attack,
+                player_index,
+                game.attacker_cards.clone(),
+            ));
+        }
+        State::PlayerDefending if game.discarded.len() > prev.discarded.len() => {
+            // Attacking cards are discarded first
+            let defended = prev.discarded.len() + prev.attacker_cards.len();
+            events.push(event(
+                EventKind::Defend,
+                player_index,
+                game.discarded.get(defended..).unwrap_or_default().to_vec(),
+            ));
+        }
+        State::PlayerDefending
+            if game.attacker_cards.is_empty() && !prev.attacker_cards.is_empty() =>
+        {
+            events.push(event(
+                EventKind::Take,
+                player_index,
+                prev.attacker_cards.clone(),
+            ));
+        }
+        _ => {}
+    }
+
+    for index in 0..game.table.players.len() {
+        if game.table.players[index].forfeited && !prev.table.players[index].forfeited {
+            events.push(event(EventKind::Forfeit, index, vec![]));
+        } else if is_out(game, index) && !is_out(prev, index) {
+            events.push(event(EventKind::Finish, index, vec![]));
+        }
+    }
+
+    if game.state == State::GameOver && prev.state != State::GameOver {
+        events.push(event(
+            EventKind::GameOver,
+            game.loser().unwrap_or(game.table.player_index),
+            vec![],
+        ));
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{game::testing::play_out, MoskaAI};
+
+    // Plays a bot turn, observing after every action.
+    // Returns the observed positions.
+    fn play_turn(game: &mut Moska, spectators: &mut [&mut Spectator]) -> Vec<Moska> {
+        let mut observed = vec![];
+        let actions = MoskaAI::new(game.table.player_index).get_actions(game);
+        for (action, card_index) in actions
+            .iter()
+            .map(|action| (action.action, action.card_index))
+            .chain([(3, 0)])
+        {
+            game.player_action(action, card_index);
+            spectators.iter_mut().for_each(|s| s.observe(game));
+            observed.push(game.clone());
+        }
+        observed
+    }
+
+    #[test]
+    fn test_events() {
+        let mut game = Moska::new(2);
+        let mut spectator = Spectator::new(&game, SpectatorMode::Public, 0);
+        game.new_round_with_seed(3);
+        spectator.observe(&game);
+
+        let attack = game.player_cards(0)[0];
+        game.player_action(1, 0);
+        game.player_action(3, 0);
+        spectator.observe(&game);
+
+        // defender takes
+        game.player_action(3, 0);
+        spectator.observe(&game);
+
+        let kinds: Vec<EventKind> = spectator.events().iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![EventKind::NewRound, EventKind::Attack, EventKind::Take]
+        );
+        assert_eq!(spectator.events()[0].cards, vec![game.trump_card]);
+        assert_eq!(spectator.events()[1].cards, vec![attack]);
+        assert_eq!(spectator.events()[2].player_index, 1);
+        assert_eq!(spectator.events()[2].cards, vec![attack]);
+        assert_eq!(spectator.events_since(2).len(), 1);
+        assert_eq!(spectator.events_since(5).len(), 0);
+    }
+
+    #[test]
+    fn test_forfeit_events() {
+        let mut game = Moska::new(3);
+        game.new_round_with_seed(3);
+        let mut spectator = Spectator::new(&game, SpectatorMode::Public, 0);
+
+        game.forfeit(1);
+        spectator.observe(&game);
+        game.forfeit(0);
+        spectator.observe(&game);
+
+        let events: Vec<(EventKind, usize)> = spectator
+            .events()
+            .iter()
+            .map(|e| (e.kind, e.player_index))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (EventKind::Forfeit, 1),
+                (EventKind::Forfeit, 0),
+                (EventKind::GameOver, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_public_view() {
+        let mut game = Moska::new(3);
+        game.new_round_with_seed(5);
+        for _ in 0..4 {
+            play_turn(&mut game, &mut []);
+        }
+
+        // joins mid-game
+        let mut spectator = Spectator::new(&game, SpectatorMode::Public, 0);
+        assert!(spectator.events().is_empty());
+
+        assert!(play_out(&mut game, |game| {
+            play_turn(game, &mut [&mut spectator]);
+
+            let view = spectator.view();
+            assert!(!view.revealed && view.hands.is_empty() && view.deck.is_empty());
+            assert_eq!(view.turn, game.table.turn);
+            assert_eq!(view.attacker_cards, game.attacker_cards);
+            assert_eq!(view.hand_counts[0], game.player_cards(0).len());
+        }));
+
+        let events = spectator.events();
+        assert!(events.iter().any(|e| e.kind == EventKind::Defend));
+        assert_eq!(events.last().map(|e| e.kind), Some(EventKind::GameOver));
+        assert_eq!(
+            events
+                .iter()
+                .filter(|e| e.kind == EventKind::Finish)
+                .count(),
+            2
+        );
+
+        // every defending card covers a discarded attacking card
+        let defended: usize = events
+            .iter()
+            .filter(|e| e.kind == EventKind::Defend)
+            .map(|e| e.cards.len())
+            .sum();
+        assert!(defended > 0 && defended * 2 <= game.discarded.len());
+    }
+
+    #[test]
+    fn test_delayed_reveal() {
+        let mut game = Moska::new(2);
+        game.new_round_with_seed(7);
+        let mut spectator = Spectator::new(&game, SpectatorMode::Omniscient, 2);
+        let mut history = vec![game.clone()];
+
+        // hidden until the game is two turns ahead
+        history.extend(play_turn(&mut game, &mut [&mut spectator]));
+        let view = spectator.view();
+        assert!(!view.revealed && view.hands.is_empty());
+        assert_eq!((view.turn, view.behind), (0, 1));
+        assert!(spectator.events().is_empty());
+
+        for _ in 0..6 {
+            history.extend(play_turn(&mut game, &mut [&mut spectator]));
+
+            let view = spectator.view();
+            assert!(view.revealed);
+            assert!(view.behind >= 2);
+            assert_eq!(view.turn + view.behind, game.table.turn);
+
+            // hands and deck as last observed at the shown turn
+            let shown = history
+                .iter()
+                .rev()
+                .find(|past| past.table.turn == view.turn)
+                .unwrap();
+            assert_eq!(view.hands[0].cards, shown.player_cards(0));
+            assert_eq!(view.hands[1].cards, shown.player_cards(1));
+            assert_eq!(view.deck.len(), view.deck_count);
+            assert_eq!(view.deck.last(), Some(&shown.trump_card));
+            assert!(spectator
+                .events()
+                .iter()
+                .all(|event| event.turn <= view.turn));
+        }
+
+        // everything is shown after the game
+        assert!(play_out(&mut game, |game| {
+            play_turn(game, &mut [&mut spectator]);
+        }));
+        let view = spectator.view();
+        assert!(view.revealed);
+        assert_eq!(view.behind, 0);
+        assert_eq!(
+            spectator.events().last().map(|e| e.kind),
+            Some(EventKind::GameOver)
+        );
+    }
+}
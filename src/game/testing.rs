@@ -0,0 +1,58 @@
+/*
+ * Helpers shared by the tests of the games.
+ */
+
+use crate::{
+    ai::{moska::Action, BotStrategy},
+    deck::{Card, Deck, Rank, Suit},
+    game::engine::Engine,
+    table::Table,
+    Moska,
+};
+
+// Turns after which a game is taken to be stuck
+pub const MAX_TURNS: usize = 10_000;
+
+pub fn card(rank: Rank, suit: Suit) -> Card {
+    Card::new(suit, rank)
+}
+
+// Seats the hands and sets the deck from bottom to top
+pub fn deal(table: &mut Table, hands: Vec<Vec<Card>>, deck: Vec<Card>) {
+    table.deck = Deck::from_cards(deck);
+    for (player, hand) in table.players.iter_mut().zip(hands) {
+        player.cards = hand;
+    }
+}
+
+// Cards in hands and in the deck along with the given piles,
+// which stays the same while playing
+pub fn count_cards(table: &Table, piles: &[&[Card]]) -> usize {
+    let hands: usize = table.players.iter().map(|player| player.cards.len()).sum();
+    let piles: usize = piles.iter().map(|pile| pile.len()).sum();
+    hands + table.deck.count() + piles
+}
+
+// Plays turns until the game is over.
+// Returns false if the game didn't end in `MAX_TURNS` turns.
+pub fn play_out<G: Engine>(game: &mut G, mut turn: impl FnMut(&mut G)) -> bool {
+    for _ in 0..MAX_TURNS {
+        if game.is_terminal() {
+            return true;
+        }
+        turn(game);
+    }
+    game.is_terminal()
+}
+
+// Plays the actions for the player in turn and submits
+pub fn play_turn(game: &mut Moska, actions: Vec<Action>) {
+    for action in actions {
+        game.player_action(action.action, action.card_index);
+    }
+    game.player_action(3, 0);
+}
+
+pub fn heuristic_turn(game: &mut Moska) {
+    play_turn(game, BotStrategy::Heuristic.get_actions(game));
+}
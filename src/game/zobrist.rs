@@ -0,0 +1,229 @@
+/*
+ * Zobrist hashing of Moska positions.
+ *
+ * A position hashes to the xor of a key for each of its features:
+ * every card in each hand and table pile, the trump card, the player in
+ * turn and the state, and optionally every card of the deck by its place.
+ * Moving a card toggles two keys, so `Moska` keeps the hash up to date
+ * while playing instead of recomputing it.
+ *
+ * Keys are mixed from the feature on demand rather than kept in tables.
+ * The order of cards in hands and on the table does not change the hash.
+ */
+
+use crate::{card_set::card_ordinal, deck::Card, game::moska::State, Moska};
+
+const HAND: u64 = 0;
+const ATTACK: u64 = 1;
+const DEFENSE: u64 = 2;
+const TRUMP: u64 = 3;
+const DECK: u64 = 4;
+const PLAYER: u64 = 5;
+const STATE: u64 = 6;
+
+// SplitMix64 finalizer, distinct inputs give distinct keys
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn key(kind: u64, index: usize, value: u64) -> u64 {
+    mix(kind << 56 | (index as u64) << 8 | value)
+}
+
+fn card_key(kind: u64, index: usize, card: &Card) -> u64 {
+    key(kind, index, card_ordinal(card) as u64)
+}
+
+pub(crate) fn hand_key(player_index: usize, card: &Card) -> u64 {
+    card_key(HAND, player_index, card)
+}
+
+pub(crate) fn attack_key(card: &Card) -> u64 {
+    card_key(ATTACK, 0, card)
+}
+
+pub(crate) fn defense_key(card: &Card) -> u64 {
+    card_key(DEFENSE, 0, card)
+}
+
+// Card at the position counted from the bottom of the deck
+pub(crate) fn deck_key(position: usize, card: &Card) -> u64 {
+    card_key(DECK, position, card)
+}
+
+// Features that are cheap to hash on demand
+pub(crate) fn turn_hash(game: &Moska) -> u64 {
+    let state = match game.state {
+        State::Initial => 0,
+        State::PlayerAttacking => 1,
+        State::PlayerDefending => 2,
+        State::GameOver => 3,
+    };
+    card_key(TRUMP, 0, &game.trump_card)
+        ^ key(PLAYER, game.table.player_index, 0)
+        ^ key(STATE, 0, state)
+}
+
+// Cards in hands and on the table
+pub(crate) fn cards_hash(game: &Moska) -> u64 {
+    let hands = game
+        .table
+        .players
+        .iter()
+        .enumerate()
+        .flat_map(|(index, player)| player.cards.iter().map(move |card| hand_key(index, card)));
+    let attack = game.attacker_cards.iter().map(attack_key);
+    let defense = game.defender_cards.iter().map(defense_key);
+
+    hands
+        .chain(attack)
+        .chain(defense)
+        .fold(0, |hash, key| hash ^ key)
+}
+
+pub(crate) fn deck_hash(game: &Moska) -> u64 {
+    game.table
+        .deck
+        .cards()
+        .iter()
+        .enumerate()
+        .fold(0, |hash, (position, card)| hash ^ deck_key(position, card))
+}
+
+// Hash of the position computed from scratch, see `Moska::zobrist_hash`
+pub fn hash(game: &Moska, with_deck: bool) -> u64 {
+    let deck = match with_deck {
+        true => deck_hash(game),
+        false => 0,
+    };
+    cards_hash(game) ^ deck ^ turn_hash(game)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ai::BotStrategy,
+        deck::{Rank, Suit},
+        game::{engine::Engine, testing::play_out},
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn assert_hashes(game: &Moska) {
+        assert_eq!(game.zobrist_hash(false), hash(game, false));
+        assert_eq!(game.zobrist_hash(true), hash(game, true));
+    }
+
+    #[test]
+    fn test_incremental() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for seed in 0..20 {
+            let mut game = Moska::new(2 + seed as u8 % 4);
+            game.new_round_with_seed(seed);
+            assert_hashes(&game);
+
+            // Heuristic turns and random, partly invalid moves
+            play_out(&mut game, |game| {
+                let actions = match rng.gen_bool(0.5) {
+                    true => BotStrategy::Heuristic.get_actions(game),
+                    false => game.legal_actions().into_iter().take(1).collect(),
+                };
+                for action in actions {
+                    game.player_action(action.action, action.card_index);
+                    assert_hashes(game);
+                }
+                game.player_action(rng.gen_range(1..=4), rng.gen_range(0..8));
+                assert_hashes(game);
+                if rng.gen_bool(0.02) {
+                    game.forfeit(game.table.player_index);
+                    assert_hashes(game);
+                }
+                game.player_action(3, 0);
+                assert_hashes(game);
+            });
+        }
+    }
+
+    #[test]
+    fn test_timeouts() {
+        use crate::game::clock::{ManualClock, TimeControl, TimeoutPolicy};
+        use std::sync::Arc;
+
+        for policy in [TimeoutPolicy::AutoTake, TimeoutPolicy::AutoSubmit] {
+            let clock = Arc::new(ManualClock::new(0));
+            let mut game = Moska::new(3);
+            game.set_clock(TimeControl::new(1_000, 0, policy), clock.clone());
+            game.new_round_with_seed(2);
+
+            // out of time with cards on the table
+            game.player_action(1, 0);
+            game.player_action(1, 0);
+            clock.advance(1_000);
+            assert!(game.check_clock());
+            assert_hashes(&game);
+            game.player_action(1, 0);
+            clock.advance(1_000);
+            assert!(game.check_clock());
+            assert_hashes(&game);
+        }
+    }
+
+    #[test]
+    fn test_positions() {
+        let mut game = Moska::new(3);
+        assert_hashes(&game);
+        game.new_round_with_seed(7);
+        let start = game.zobrist_hash(true);
+
+        // the same position by another order of play
+        game.player_action(1, 0);
+        game.player_action(1, 0);
+        let added = game.zobrist_hash(true);
+        game.player_action(2, 1);
+        game.player_action(2, 0);
+        assert_eq!(game.zobrist_hash(true), start);
+        game.player_action(1, 4);
+        game.player_action(1, 4);
+        assert_eq!(game.zobrist_hash(true), added);
+        assert_ne!(added, start);
+
+        // deck order only counts when asked for
+        let mut other = game.clone();
+        let mut cards = other.table.deck.cards();
+        cards.swap(1, 2);
+        other.table.deck = crate::Deck::from_cards(cards);
+        other.rehash();
+        assert_eq!(other.zobrist_hash(false), game.zobrist_hash(false));
+        assert_ne!(other.zobrist_hash(true), game.zobrist_hash(true));
+
+        // as does the player in turn
+        other.table.player_index = 2;
+        assert_ne!(other.zobrist_hash(false), game.zobrist_hash(false));
+
+        // and the trump
+        let mut other = game.clone();
+        other.trump_card = crate::Card::new(Suit::Spades, Rank::Ace);
+        assert_ne!(other.zobrist_hash(false), game.zobrist_hash(false));
+    }
+
+    #[test]
+    fn test_swap_and_determinize() {
+        let mut game = Moska::new(2);
+        game.new_round_with_seed(1);
+        let trump_card = game.trump_card;
+        let trump_two = crate::Card::new(trump_card.suit, Rank::Two);
+        game.table.players[0].cards[0] = trump_two;
+        game.rehash();
+
+        assert!(game.player_action(4, 0));
+        assert_eq!(game.table.players[0].cards[0], trump_card);
+        assert_eq!(game.trump_card, trump_two);
+        assert_hashes(&game);
+
+        let mut rng = StdRng::seed_from_u64(2);
+        assert_hashes(&game.determinize(0, &mut rng));
+    }
+}
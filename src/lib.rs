@@ -1,8 +1,16 @@
 pub mod ai;
+pub mod bot_protocol;
+pub mod card_set;
 pub mod deck;
 pub mod game;
 pub mod table;
 
+#[cfg(feature = "serde")]
+pub mod protocol;
+#[cfg(feature = "server")]
+pub mod server;
+
 pub use ai::moska::*;
+pub use card_set::CardSet;
 pub use deck::{Card, Deck, Rank, Suit};
 pub use game::moska::Moska;
@@ -1,4 +1,781 @@
+/*
+ * Terminal Moska against bots
+ */
+
+use std::{
+    env, fs,
+    io::{self, BufRead, Write},
+    time::Duration,
+};
+
+use itertools::Itertools;
+use moska::{
+    ai::{
+        eval::{EvalAI, EvalModel},
+        simulate::{simulate, SeatBot, SimulationConfig},
+        BotStrategy,
+    },
+    bot_protocol::process::{ExternalBot, ExternalSeat},
+    game::moska::{card_beats, State},
+    Action, Card, HintReason, Moska,
+};
+
+const USAGE: &str = "\
+usage: moska [--players N] [--bot BOT]... [--seed N]
+       moska simulate [--players N] [--games N] [--bot BOT]... [--seed N]
+bots: heuristic, random, montecarlo, mcts, eval=WEIGHTS, external=COMMAND (not simulated)";
+
+// Time an external bot has for each move
+const EXTERNAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Rejected moves before an external bot is replaced
+const EXTERNAL_MAX_ERRORS: usize = 3;
+
+const HELP: &str = "\
+commands:
+  play 3 5   put cards from your hand on the table
+  take 1     take a card back from the table
+  submit     submit the table, or take the attacking cards when not defending
+  swap       swap the two of trumps for the trump card
+  hint       suggest a move
+  help       show this help
+  quit       leave the game";
+
+// Seat played by the human
+const HUMAN: usize = 0;
+
+enum Bot {
+    Strategy(BotStrategy),
+    Eval(EvalAI),
+    External(ExternalSeat),
+}
+
+impl Bot {
+    fn get_actions(&mut self, game: &Moska) -> Vec<Action> {
+        match self {
+            Bot::Strategy(strategy) => strategy.get_actions(game),
+            Bot::Eval(bot) => bot.get_actions(game),
+            Bot::External(bot) => bot.get_actions(game),
+        }
+    }
+}
+
+// Bot strategy of a seat
+#[derive(Clone, Debug, PartialEq)]
+enum Strategy {
+    Bot(BotStrategy),
+    Eval(String),
+    // Program and its arguments
+    External(Vec<String>),
+}
+
+impl Strategy {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value.split_once('=') {
+            None => value.parse().map(Strategy::Bot),
+            Some(("eval", path)) if !path.is_empty() => Ok(Strategy::Eval(path.to_string())),
+            Some(("external", command)) if !command.trim().is_empty() => Ok(Strategy::External(
+                command.split_whitespace().map(String::from).collect(),
+            )),
+            _ => Err(format!("Unknown bot strategy '{value}'")),
+        }
+    }
+
+    fn bot(&self, player_index: usize, players: u8) -> Result<Bot, String> {
+        match self {
+            Strategy::Bot(strategy) => Ok(Bot::Strategy(*strategy)),
+            Strategy::Eval(path) => Ok(Bot::Eval(EvalAI::new(player_index, load_model(path)?))),
+            Strategy::External(command) => {
+                let bot = ExternalBot::spawn(&command[0], &command[1..], EXTERNAL_TIMEOUT)?;
+                Ok(Bot::External(ExternalSeat::new(
+                    bot,
+                    players as usize,
+                    player_index,
+                    EXTERNAL_MAX_ERRORS,
+                )))
+            }
+        }
+    }
+
+    // Bot of a simulated seat, external programs are not simulated
+    fn seat_bot(&self) -> Result<SeatBot, String> {
+        match self {
+            Strategy::Bot(strategy) => Ok(SeatBot::Strategy(*strategy)),
+            Strategy::Eval(path) => Ok(SeatBot::Eval(load_model(path)?)),
+            Strategy::External(_) => Err("External bots can't be simulated".to_string()),
+        }
+    }
+}
+
+fn load_model(path: &str) -> Result<EvalModel, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| format!("Could not read weights '{path}': {err}"))?;
+    EvalModel::from_text(&text)
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    players: u8,
+    // Strategies of bot seats, the last one repeats
+    bots: Vec<Strategy>,
+    seed: Option<u64>,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    parse_args(args, &[], |_, _| Ok(()))
+}
+
+// Parses the options shared by the commands.
+// Values of the command's own flags go to `extra`.
+fn parse_args(
+    args: &[String],
+    extra_flags: &[&str],
+    mut extra: impl FnMut(&str, &str) -> Result<(), String>,
+) -> Result<Options, String> {
+    let mut options = Options {
+        players: 2,
+        bots: vec![],
+        seed: None,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--players" => {
+                options.players = value()?
+                    .parse()
+                    .ok()
+                    .filter(|players| (2..=8).contains(players))
+                    .ok_or("Players must be a number from 2 to 8")?;
+            }
+            "--bot" => options.bots.push(Strategy::parse(value()?)?),
+            "--seed" => {
+                options.seed = Some(value()?.parse().map_err(|_| "Seed must be a number")?);
+            }
+            flag if extra_flags.contains(&flag) => extra(flag, value()?)?,
+            _ => return Err(format!("Unknown argument '{arg}'")),
+        }
+    }
+
+    Ok(options)
+}
+
+// Options of the simulate subcommand
+#[derive(Debug, PartialEq)]
+struct SimulationOptions {
+    // Bots take every seat, the last strategy repeats
+    options: Options,
+    games: usize,
+}
+
+impl SimulationOptions {
+    // Loads the bots of the seats
+    fn config(&self) -> Result<SimulationConfig, String> {
+        Ok(SimulationConfig {
+            players: self.options.players,
+            games: self.games,
+            bots: self
+                .options
+                .bots
+                .iter()
+                .map(Strategy::seat_bot)
+                .collect::<Result<_, String>>()?,
+            seed: self
+                .options
+                .seed
+                .unwrap_or(SimulationConfig::default().seed),
+        })
+    }
+}
+
+fn parse_simulation(args: &[String]) -> Result<SimulationOptions, String> {
+    let mut games = SimulationConfig::default().games;
+    let options = parse_args(args, &["--games"], |_, value| {
+        games = value.parse().map_err(|_| "Games must be a number")?;
+        Ok(())
+    })?;
+    Ok(SimulationOptions { options, games })
+}
+
+fn run_simulation(config: &SimulationConfig) {
+    let stats = simulate(config);
+    println!(
+        "{} games, {} given up, {:.1} turns on average",
+        stats.games,
+        stats.unfinished,
+        stats.mean_turns()
+    );
+    for index in 0..config.players as usize {
+        println!(
+            "Seat {index} ({}): won {:.1}%, took the attack {:.2} times a game",
+            config.bot(index),
+            100.0 * stats.win_rate(index),
+            stats.mean_withdrawals(index)
+        );
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Play(Vec<usize>),
+    Take(Vec<usize>),
+    Submit,
+    Swap,
+    Hint,
+    Help,
+    Quit,
+}
+
+// Parses a command line.
+// Card numbers are shown starting from 1 and returned as indexes.
+fn parse_command(line: &str) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+    let Some(command) = words.next() else {
+        return Err("Type a command, or 'help' to list them".to_string());
+    };
+    let args: Vec<&str> = words.collect();
+
+    let numbers = || -> Result<Vec<usize>, String> {
+        let numbers = args
+            .iter()
+            .map(|word| match word.parse::<usize>() {
+                Ok(number) if number > 0 => Ok(number - 1),
+                _ => Err(format!("'{word}' is not a card number")),
+            })
+            .collect::<Result<Vec<usize>, String>>()?;
+
+        if numbers.is_empty() {
+            return Err(format!(
+                "'{command}' needs card numbers, like '{command} 1 2'"
+            ));
+        }
+        if !numbers.iter().all_unique() {
+            return Err("Each card can be chosen only once".to_string());
+        }
+        Ok(numbers)
+    };
+
+    let command = match command {
+        "play" | "p" => Command::Play(numbers()?),
+        "take" | "t" => Command::Take(numbers()?),
+        "submit" | "s" => Command::Submit,
+        "swap" => Command::Swap,
+        "hint" | "h" => Command::Hint,
+        "help" | "?" => Command::Help,
+        "quit" | "q" | "exit" => Command::Quit,
+        _ => {
+            return Err(format!(
+                "Unknown command '{command}', type 'help' to list commands"
+            ))
+        }
+    };
+
+    if matches!(
+        command,
+        Command::Submit | Command::Swap | Command::Hint | Command::Help | Command::Quit
+    ) && !args.is_empty()
+    {
+        return Err("Command takes no arguments".to_string());
+    }
+
+    Ok(command)
+}
+
+fn cards_text(cards: &[Card]) -> String {
+    if cards.is_empty() {
+        "-".to_string()
+    } else {
+        cards.iter().join(" ")
+    }
+}
+
+fn numbered_text(cards: &[Card]) -> String {
+    if cards.is_empty() {
+        return "-".to_string();
+    }
+    cards
+        .iter()
+        .enumerate()
+        .map(|(index, card)| format!("[{}] {card}", index + 1))
+        .join("  ")
+}
+
+fn player_name(player_index: usize) -> String {
+    if player_index == HUMAN {
+        "You".to_string()
+    } else {
+        format!("Bot {player_index}")
+    }
+}
+
+fn render(game: &Moska) {
+    println!();
+    println!(
+        "Trump {}  Deck {}  Discarded {}",
+        game.trump_card,
+        game.table.deck.count(),
+        game.discarded.len()
+    );
+
+    for (index, player) in game.table.players.iter().enumerate() {
+        if index != HUMAN {
+            let marker = if index == game.table.player_index {
+                ">"
+            } else {
+                " "
+            };
+            println!(
+                "{marker} {}: {} cards",
+                player_name(index),
+                player.cards.len()
+            );
+        }
+    }
+
+    println!("Attacking: {}", numbered_text(&game.attacker_cards));
+    if game.state == State::PlayerDefending {
+        println!("Defending: {}", numbered_text(&game.defender_cards));
+    }
+    println!("Your hand: {}", numbered_text(&game.player_cards(HUMAN)));
+}
+
+// Explains why submitting the table is not accepted
+fn rejection(game: &Moska) -> String {
+    match game.state {
+        State::PlayerAttacking => {
+            let max_cards = game.table.players[game.next_player()].cards.len();
+            if game.attacker_cards.is_empty() {
+                "Play at least one card to attack".to_string()
+            } else if game.attacker_cards.len() > max_cards {
+                format!(
+                    "{} holds only {max_cards} cards, attack with fewer",
+                    player_name(game.next_player())
+                )
+            } else {
+                "Attack with a single card or with pairs of the same rank".to_string()
+            }
+        }
+        State::PlayerDefending => {
+            let needed = game.attacker_cards.len();
+            if game.defender_cards.len() != needed {
+                format!(
+                    "Defend with {needed} cards, one for each attacking card, or take them back and submit to take"
+                )
+            } else {
+                let trump_suit = game.trump_card.suit;
+                let uncovered = game
+                    .attacker_cards
+                    .iter()
+                    .filter(|atk| {
+                        !game
+                            .defender_cards
+                            .iter()
+                            .any(|def| card_beats(atk, def, trump_suit))
+                    })
+                    .collect_vec();
+
+                if uncovered.is_empty() {
+                    "Defending cards must beat every attacking card one to one".to_string()
+                } else {
+                    format!(
+                        "Nothing you play beats {}",
+                        uncovered.iter().map(|card| card.to_string()).join(" ")
+                    )
+                }
+            }
+        }
+        _ => "The game is not running".to_string(),
+    }
+}
+
+fn hint_text(game: &Moska) -> String {
+    let hint = game.hint(HUMAN);
+    let cards = hint
+        .actions
+        .iter()
+        .filter(|action| action.action == 1)
+        .map(|action| action.card_index + 1)
+        .sorted()
+        .join(" ");
+    let swap = hint.actions.iter().any(|action| action.action == 4);
+
+    let reason = match hint.reason {
+        HintReason::Wait => return "Wait for your turn".to_string(),
+        HintReason::TakeCards => "take the attacking cards".to_string(),
+        HintReason::TakeToSaveTrumps => "take the cards to save your trumps".to_string(),
+        HintReason::DefendWithLowestCoveringCards => {
+            "defend with your lowest covering cards".to_string()
+        }
+        HintReason::AttackWithPairs => "attack with pairs".to_string(),
+        HintReason::AttackWithLowestCard => "attack with your lowest card".to_string(),
+        HintReason::PlayForcedWin => "this play wins by force".to_string(),
+    };
+
+    let mut text = String::from("Hint: ");
+    if swap {
+        text.push_str(&format!(
+            "swap the trump two for {}, then ",
+            game.trump_card
+        ));
+    }
+    if cards.is_empty() {
+        text.push_str("submit");
+    } else {
+        text.push_str(&format!("play {cards} and submit"));
+    }
+    text.push_str(&format!(" - {reason}"));
+    text
+}
+
+// Plays the bot's turn and describes it
+fn bot_turn(game: &mut Moska, bot: &mut Bot) -> String {
+    let player_index = game.table.player_index;
+    let name = player_name(player_index);
+    let defending = game.state == State::PlayerDefending;
+    let mut lines = vec![];
+
+    let mut played = vec![];
+    for action in bot.get_actions(game) {
+        match action.action {
+            1 => played.extend(
+                game.player_cards(player_index)
+                    .get(action.card_index)
+                    .copied(),
+            ),
+            4 => lines.push(format!(
+                "{name} swaps the two of trumps for {}",
+                game.trump_card
+            )),
+            _ => {}
+        }
+        game.player_action(action.action, action.card_index);
+    }
+
+    let attacker_cards = game.attacker_cards.clone();
+    let took = defending && played.is_empty();
+    if took || game.valid() {
+        lines.push(if !defending {
+            format!("{name} attacks with {}", cards_text(&played))
+        } else if played.is_empty() {
+            format!("{name} takes {}", cards_text(&attacker_cards))
+        } else {
+            format!("{name} defends with {}", cards_text(&played))
+        });
+    }
+    game.player_action(3, 0);
+
+    lines.join("\n")
+}
+
+// Applies a command for the human.
+// Returns an error message when the move is rejected.
+fn human_turn(game: &mut Moska, command: Command) -> Result<String, String> {
+    match command {
+        Command::Play(indexes) => {
+            let hand = game.player_cards(HUMAN);
+            if let Some(index) = indexes.iter().find(|index| **index >= hand.len()) {
+                return Err(format!("You have no card number {}", index + 1));
+            }
+            if game.state == State::PlayerDefending
+                && game.defender_cards.len() + indexes.len() > game.attacker_cards.len()
+            {
+                return Err(format!(
+                    "Only {} attacking cards to defend against",
+                    game.attacker_cards.len()
+                ));
+            }
+
+            // Remove from the back so the other indexes stay put
+            for index in indexes.iter().sorted().rev() {
+                game.player_action(1, *index);
+            }
+            Ok(format!(
+                "You put {} on the table",
+                indexes.iter().map(|index| hand[*index]).join(" ")
+            ))
+        }
+        Command::Take(indexes) => {
+            let table = if game.state == State::PlayerDefending {
+                game.defender_cards.clone()
+            } else {
+                game.attacker_cards.clone()
+            };
+            if let Some(index) = indexes.iter().find(|index| **index >= table.len()) {
+                return Err(format!(
+                    "There is no card number {} of yours on the table",
+                    index + 1
+                ));
+            }
+
+            for index in indexes.iter().sorted().rev() {
+                game.player_action(2, *index);
+            }
+            Ok(format!(
+                "You take back {}",
+                indexes.iter().map(|index| table[*index]).join(" ")
+            ))
+        }
+        Command::Submit => {
+            let defending = game.state == State::PlayerDefending;
+            let taken = game.attacker_cards.clone();
+            let took = defending && game.defender_cards.is_empty();
+
+            if !took && !game.valid() {
+                return Err(rejection(game));
+            }
+            game.player_action(3, 0);
+
+            Ok(if took {
+                format!("You take {}", cards_text(&taken))
+            } else if defending {
+                "You beat the attack".to_string()
+            } else {
+                "You attack".to_string()
+            })
+        }
+        Command::Swap => {
+            let trump_card = game.trump_card;
+            if game.player_action(4, 0) {
+                Ok(format!("You swap the two of trumps for {trump_card}"))
+            } else if game.table.deck.count() == 0 {
+                Err("The trump card is gone with the deck".to_string())
+            } else {
+                Err(format!("You need the two of {} to swap", trump_card.suit))
+            }
+        }
+        Command::Hint => Ok(hint_text(game)),
+        Command::Help => Ok(HELP.to_string()),
+        Command::Quit => Ok(String::new()),
+    }
+}
 
 fn main() {
-    println!("gg");
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "simulate") {
+        let config = parse_simulation(&args[1..])
+            .and_then(|options| options.config())
+            .unwrap_or_else(|err| {
+                eprintln!("{err}\n{USAGE}");
+                std::process::exit(1);
+            });
+        run_simulation(&config);
+        return;
+    }
+
+    let options = parse_options(&args).unwrap_or_else(|err| {
+        eprintln!("{err}\n{USAGE}");
+        std::process::exit(1);
+    });
+
+    let mut bots: Vec<Option<Bot>> = (0..options.players as usize)
+        .map(|index| {
+            if index == HUMAN {
+                return Ok(None);
+            }
+            let strategy = options
+                .bots
+                .get(index - 1)
+                .or(options.bots.last())
+                .unwrap_or(&Strategy::Bot(BotStrategy::Heuristic));
+            strategy.bot(index, options.players).map(Some)
+        })
+        .collect::<Result<_, String>>()
+        .unwrap_or_else(|err| {
+            eprintln!("{err}");
+            std::process::exit(1);
+        });
+
+    let mut game = Moska::new(options.players);
+    match options.seed {
+        Some(seed) => game.new_round_with_seed(seed),
+        None => game.new_round(),
+    }
+
+    println!(
+        "Moska with {} players. Type 'help' for commands.",
+        options.players
+    );
+
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        if game.state == State::GameOver {
+            render(&game);
+            match game.loser() {
+                Some(HUMAN) => println!("You are left holding the cards. Moska!"),
+                Some(index) => {
+                    println!("{} is left holding the cards. You win!", player_name(index))
+                }
+                None => println!("Nobody is left holding cards"),
+            }
+            return;
+        }
+
+        let player_index = game.table.player_index;
+        if let Some(bot) = &mut bots[player_index] {
+            println!("{}", bot_turn(&mut game, bot));
+            continue;
+        }
+
+        render(&game);
+        print!(
+            "{}> ",
+            if game.state == State::PlayerDefending {
+                "defend"
+            } else {
+                "attack"
+            }
+        );
+        io::stdout().flush().ok();
+
+        let Some(Ok(line)) = lines.next() else {
+            return;
+        };
+
+        match parse_command(&line).and_then(|command| {
+            let quit = command == Command::Quit;
+            human_turn(&mut game, command).map(|message| (message, quit))
+        }) {
+            Ok((_, true)) => return,
+            Ok((message, _)) => println!("{message}"),
+            Err(err) => println!("Rejected: {err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("play 3 5"), Ok(Command::Play(vec![2, 4])));
+        assert_eq!(parse_command(" take 1 "), Ok(Command::Take(vec![0])));
+        assert_eq!(parse_command("submit"), Ok(Command::Submit));
+        assert_eq!(parse_command("swap"), Ok(Command::Swap));
+        assert_eq!(parse_command("hint"), Ok(Command::Hint));
+
+        assert!(parse_command("").is_err());
+        assert!(parse_command("play").is_err());
+        assert!(parse_command("play 0").is_err());
+        assert!(parse_command("play 2 2").is_err());
+        assert!(parse_command("play x").is_err());
+        assert!(parse_command("submit 1").is_err());
+        assert!(parse_command("dance").is_err());
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let args = |line: &str| {
+            line.split_whitespace()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            parse_options(&args(
+                "--players 3 --bot heuristic --bot eval=w.txt --seed 4"
+            )),
+            Ok(Options {
+                players: 3,
+                bots: vec![
+                    Strategy::Bot(BotStrategy::Heuristic),
+                    Strategy::Eval("w.txt".to_string())
+                ],
+                seed: Some(4),
+            })
+        );
+        assert!(parse_options(&args("--players 1")).is_err());
+        assert!(parse_options(&args("--players")).is_err());
+        assert!(parse_options(&args("--bot best")).is_err());
+        assert_eq!(
+            parse_options(&args("--bot mcts")).map(|options| options.bots),
+            Ok(vec![Strategy::Bot(BotStrategy::Mcts)])
+        );
+        assert!(parse_options(&args("--bot external=")).is_err());
+
+        // commands with arguments are passed as one argument
+        assert_eq!(
+            Strategy::parse("external=python3 bot.py --fast"),
+            Ok(Strategy::External(vec![
+                "python3".to_string(),
+                "bot.py".to_string(),
+                "--fast".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_simulation() {
+        let args = |line: &str| {
+            line.split_whitespace()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            parse_simulation(&args("--games 50 --bot mcts --bot random --seed 2")),
+            Ok(SimulationOptions {
+                options: Options {
+                    players: 2,
+                    bots: vec![
+                        Strategy::Bot(BotStrategy::Mcts),
+                        Strategy::Bot(BotStrategy::Random)
+                    ],
+                    seed: Some(2),
+                },
+                games: 50,
+            })
+        );
+        let config = parse_simulation(&args("--bot montecarlo")).and_then(|o| o.config());
+        assert_eq!(
+            config.map(|config| config.bots),
+            Ok(vec![SeatBot::Strategy(BotStrategy::MonteCarlo)])
+        );
+        let config = parse_simulation(&[]).and_then(|o| o.config()).unwrap();
+        assert_eq!((config.games, config.seed), (1_000, 0));
+        assert!(config.bots.is_empty());
+        assert!(parse_simulation(&args("--games many")).is_err());
+        assert!(parse_simulation(&args("--players 9")).is_err());
+        assert!(parse_options(&args("--games 50")).is_err());
+
+        // eval weights are loaded and external programs rejected
+        let options = parse_simulation(&args("--bot eval=missing.txt")).unwrap();
+        assert_eq!(
+            options.options.bots,
+            vec![Strategy::Eval("missing.txt".to_string())]
+        );
+        assert!(options.config().is_err());
+        let options = parse_simulation(&args("--bot external=bot")).unwrap();
+        assert!(options.config().is_err());
+    }
+
+    #[test]
+    fn test_rejected_moves() {
+        let mut game = Moska::new(2);
+        game.new_round_with_seed(3);
+
+        assert!(human_turn(&mut game, Command::Play(vec![6])).is_err());
+        assert!(human_turn(&mut game, Command::Take(vec![0])).is_err());
+        assert!(human_turn(&mut game, Command::Submit).is_err());
+
+        // a single card is always a valid attack
+        assert!(human_turn(&mut game, Command::Play(vec![0])).is_ok());
+        assert!(human_turn(&mut game, Command::Submit).is_ok());
+        assert_eq!(game.state, State::PlayerDefending);
+    }
+
+    #[test]
+    fn test_bot_turns() {
+        let mut game = Moska::new(2);
+        game.new_round_with_seed(3);
+        let mut bot = Bot::Strategy(BotStrategy::Heuristic);
+
+        let text = bot_turn(&mut game, &mut bot);
+        assert!(text.contains("attacks with"));
+        assert_eq!(game.state, State::PlayerDefending);
+    }
 }
@@ -0,0 +1,332 @@
+/*
+ * Versioned JSON messages between game clients and servers.
+ *
+ * Every message is a JSON object with a `type` tag and a `version`:
+ *
+ *   {"version":2,"type":"Join","room":"lunch","players":3}
+ *   {"version":2,"type":"Start"}
+ *   {"version":2,"type":"Act","action":"AddCard","card_index":0}
+ *   {"version":2,"type":"Joined","room":"lunch","seat":0}
+ *   {"version":2,"type":"StateUpdate","view":{...}}
+ *   {"version":2,"type":"Rejected","reason":"Not your turn"}
+ *
+ * Versions:
+ *   1  Messages without `version`, actions as numbers 1-4.
+ *   2  `version` on every message, actions by name,
+ *      view includes turn and round.
+ *
+ * Newer versions only add fields with defaults, and readers
+ * ignore fields they do not know, so older messages stay readable.
+ */
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{game::moska::State, Card, Moska};
+
+pub const PROTOCOL_VERSION: u32 = 2;
+
+// Oldest version still understood
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+fn version_1() -> u32 {
+    1
+}
+
+// Message with its protocol version
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Envelope<T> {
+    // Missing in version 1
+    #[serde(default = "version_1")]
+    pub version: u32,
+    #[serde(flatten)]
+    pub msg: T,
+}
+
+// Engine actions, see `Moska::player_action`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum ActionKind {
+    AddCard = 1,
+    TakeCard = 2,
+    Submit = 3,
+    SwapTrumpCard = 4,
+}
+
+impl ActionKind {
+    pub fn from_code(code: usize) -> Option<Self> {
+        match code {
+            1 => Some(ActionKind::AddCard),
+            2 => Some(ActionKind::TakeCard),
+            3 => Some(ActionKind::Submit),
+            4 => Some(ActionKind::SwapTrumpCard),
+            _ => None,
+        }
+    }
+
+    // Action number for `Moska::player_action`
+    pub fn code(self) -> usize {
+        self as usize
+    }
+}
+
+// Reads actions by name, or by number as in version 1
+impl<'de> Deserialize<'de> for ActionKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Code(usize),
+            Name(String),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Code(code) => ActionKind::from_code(code)
+                .ok_or_else(|| serde::de::Error::custom(format!("unknown action {code}"))),
+            Repr::Name(name) => match name.as_str() {
+                "AddCard" => Ok(ActionKind::AddCard),
+                "TakeCard" => Ok(ActionKind::TakeCard),
+                "Submit" => Ok(ActionKind::Submit),
+                "SwapTrumpCard" => Ok(ActionKind::SwapTrumpCard),
+                _ => Err(serde::de::Error::custom(format!("unknown action '{name}'"))),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ClientMsg {
+    // Joins the room, creating it for the given number of players
+    Join {
+        room: String,
+        players: u8,
+    },
+    // Deals the cards, bots take the empty seats
+    Start,
+    // Card index is ignored by submitting and swapping
+    Act {
+        action: ActionKind,
+        #[serde(default)]
+        card_index: usize,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ServerMsg {
+    Joined { room: String, seat: usize },
+    StateUpdate { view: View },
+    Rejected { reason: String },
+}
+
+// Game as seen from a seat.
+// Only the seat's own hand is included.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct View {
+    pub seat: usize,
+    pub state: State,
+    // Player in turn
+    pub player_index: usize,
+    pub trump_card: Card,
+    pub deck_count: usize,
+    pub discarded_count: usize,
+    pub hand: Vec<Card>,
+    // Number of cards in each player's hand
+    pub hand_counts: Vec<usize>,
+    // Seats played by bots
+    pub bots: Vec<bool>,
+    pub attacker_cards: Vec<Card>,
+    pub defender_cards: Vec<Card>,
+    // Since version 2
+    #[serde(default)]
+    pub turn: usize,
+    #[serde(default)]
+    pub round: usize,
+}
+
+impl View {
+    pub fn new(game: &Moska, seat: usize, bots: Vec<bool>) -> Self {
+        Self {
+            seat,
+            state: game.state,
+            player_index: game.table.player_index,
+            trump_card: game.trump_card,
+            deck_count: game.table.deck.count(),
+            discarded_count: game.discarded.len(),
+            hand: game.player_cards(seat),
+            hand_counts: game.table.players.iter().map(|p| p.cards.len()).collect(),
+            bots,
+            attacker_cards: game.attacker_cards.clone(),
+            defender_cards: game.defender_cards.clone(),
+            turn: game.table.turn,
+            round: game.table.round,
+        }
+    }
+}
+
+// Writes the message in the current version
+pub fn encode<T: Serialize>(msg: T) -> String {
+    serde_json::to_string(&Envelope {
+        version: PROTOCOL_VERSION,
+        msg,
+    })
+    .expect("Failed to serialize message")
+}
+
+// Reads a message of any supported version
+pub fn decode<T: for<'de> Deserialize<'de>>(text: &str) -> Result<T, String> {
+    let envelope: Envelope<T> =
+        serde_json::from_str(text).map_err(|err| format!("Invalid message: {err}"))?;
+
+    if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&envelope.version) {
+        return Err(format!(
+            "Unsupported protocol version {}, expected {MIN_PROTOCOL_VERSION} to {PROTOCOL_VERSION}",
+            envelope.version
+        ));
+    }
+    Ok(envelope.msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Rank, Suit};
+
+    fn view() -> View {
+        View {
+            seat: 1,
+            state: State::PlayerDefending,
+            player_index: 1,
+            trump_card: Card::new(Suit::Hearts, Rank::Ace),
+            deck_count: 30,
+            discarded_count: 2,
+            hand: vec![Card::new(Suit::Spades, Rank::Ten)],
+            hand_counts: vec![5, 1],
+            bots: vec![true, false],
+            attacker_cards: vec![Card::new(Suit::Spades, Rank::Five)],
+            defender_cards: vec![],
+            turn: 3,
+            round: 1,
+        }
+    }
+
+    // Current version must keep producing exactly these
+    #[test]
+    fn test_schema() {
+        assert_eq!(
+            encode(ClientMsg::Join {
+                room: "lunch".to_string(),
+                players: 3
+            }),
+            r#"{"version":2,"type":"Join","room":"lunch","players":3}"#
+        );
+        assert_eq!(encode(ClientMsg::Start), r#"{"version":2,"type":"Start"}"#);
+        assert_eq!(
+            encode(ClientMsg::Act {
+                action: ActionKind::AddCard,
+                card_index: 2
+            }),
+            r#"{"version":2,"type":"Act","action":"AddCard","card_index":2}"#
+        );
+        assert_eq!(
+            encode(ServerMsg::Joined {
+                room: "lunch".to_string(),
+                seat: 0
+            }),
+            r#"{"version":2,"type":"Joined","room":"lunch","seat":0}"#
+        );
+        assert_eq!(
+            encode(ServerMsg::Rejected {
+                reason: "Not your turn".to_string()
+            }),
+            r#"{"version":2,"type":"Rejected","reason":"Not your turn"}"#
+        );
+        assert_eq!(
+            encode(ServerMsg::StateUpdate { view: view() }),
+            concat!(
+                r#"{"version":2,"type":"StateUpdate","view":{"seat":1,"state":"PlayerDefending","#,
+                r#""player_index":1,"trump_card":{"suit":"Hearts","rank":"Ace"},"deck_count":30,"#,
+                r#""discarded_count":2,"hand":[{"suit":"Spades","rank":"Ten"}],"hand_counts":[5,1],"#,
+                r#""bots":[true,false],"attacker_cards":[{"suit":"Spades","rank":"Five"}],"#,
+                r#""defender_cards":[],"turn":3,"round":1}}"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let msgs = [
+            ClientMsg::Join {
+                room: "a".to_string(),
+                players: 2,
+            },
+            ClientMsg::Start,
+            ClientMsg::Act {
+                action: ActionKind::SwapTrumpCard,
+                card_index: 0,
+            },
+        ];
+        for msg in msgs {
+            assert_eq!(decode::<ClientMsg>(&encode(&msg)), Ok(msg));
+        }
+
+        let msg = ServerMsg::StateUpdate { view: view() };
+        assert_eq!(decode::<ServerMsg>(&encode(&msg)), Ok(msg));
+    }
+
+    // Messages written by version 1 peers
+    #[test]
+    fn test_version_1() {
+        assert_eq!(
+            decode::<ClientMsg>(r#"{"type":"Act","action":3,"card_index":0}"#),
+            Ok(ClientMsg::Act {
+                action: ActionKind::Submit,
+                card_index: 0
+            })
+        );
+        assert_eq!(
+            decode::<ClientMsg>(r#"{"type":"Join","room":"a","players":4}"#),
+            Ok(ClientMsg::Join {
+                room: "a".to_string(),
+                players: 4
+            })
+        );
+
+        let mut v1_view = serde_json::to_value(view()).unwrap();
+        v1_view.as_object_mut().unwrap().remove("turn");
+        v1_view.as_object_mut().unwrap().remove("round");
+        let text = serde_json::json!({"type": "StateUpdate", "view": v1_view}).to_string();
+
+        let ServerMsg::StateUpdate { view: decoded } = decode(&text).unwrap() else {
+            panic!("Expected state update");
+        };
+        assert_eq!(decoded.hand, view().hand);
+        assert_eq!((decoded.turn, decoded.round), (0, 0));
+    }
+
+    #[test]
+    fn test_unknown_versions_and_fields() {
+        // fields added by newer minor changes are ignored
+        assert_eq!(
+            decode::<ClientMsg>(r#"{"version":2,"type":"Start","extra":true}"#),
+            Ok(ClientMsg::Start)
+        );
+
+        assert!(decode::<ClientMsg>(r#"{"version":3,"type":"Start"}"#).is_err());
+        assert!(decode::<ClientMsg>(r#"{"version":0,"type":"Start"}"#).is_err());
+        assert!(decode::<ClientMsg>(r#"{"type":"Act","action":9}"#).is_err());
+        assert!(decode::<ClientMsg>(r#"{"type":"Act","action":"Pass"}"#).is_err());
+        assert!(decode::<ClientMsg>(r#"{"type":"Dance"}"#).is_err());
+    }
+
+    #[test]
+    fn test_view_from_game() {
+        let mut game = Moska::new(3);
+        game.new_round_with_seed(6);
+
+        let view = View::new(&game, 2, vec![true, true, false]);
+        assert_eq!(view.hand, game.player_cards(2));
+        assert_eq!(view.hand_counts, vec![6, 6, 6]);
+        assert_eq!(view.deck_count, 34);
+        assert_eq!(view.round, 1);
+    }
+}
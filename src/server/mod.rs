@@ -0,0 +1,348 @@
+/*
+ * WebSocket server hosting Moska rooms.
+ *
+ * Clients join rooms by name and play their seat with engine actions.
+ * Each client sees its own hand and the public state of the game.
+ */
+
+pub mod room;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{unbounded_channel, UnboundedSender},
+};
+use tokio_tungstenite::tungstenite::{self, Message};
+
+use crate::protocol::{self, ClientMsg, ServerMsg};
+use room::Room;
+
+struct Client {
+    sender: UnboundedSender<ServerMsg>,
+    room: Option<String>,
+}
+
+// Rooms and connected clients
+struct Lobby {
+    rooms: HashMap<String, Room>,
+    clients: HashMap<usize, Client>,
+    next_client: usize,
+    // Deals rooms from this seed when set
+    seed: Option<u64>,
+}
+
+impl Lobby {
+    fn new(seed: Option<u64>) -> Self {
+        Self {
+            rooms: HashMap::new(),
+            clients: HashMap::new(),
+            next_client: 0,
+            seed,
+        }
+    }
+
+    fn connect(&mut self, sender: UnboundedSender<ServerMsg>) -> usize {
+        let id = self.next_client;
+        self.next_client += 1;
+        self.clients.insert(id, Client { sender, room: None });
+        id
+    }
+
+    fn disconnect(&mut self, client: usize) {
+        let Some(Client {
+            room: Some(name), ..
+        }) = self.clients.remove(&client)
+        else {
+            return;
+        };
+
+        if let Some(room) = self.rooms.get_mut(&name) {
+            room.leave(client);
+            if room.is_empty() {
+                self.rooms.remove(&name);
+            } else {
+                self.broadcast(&name);
+            }
+        }
+    }
+
+    fn send(&self, client: usize, msg: ServerMsg) {
+        if let Some(client) = self.clients.get(&client) {
+            // Closed connections are cleaned up by their reader
+            let _ = client.sender.send(msg);
+        }
+    }
+
+    // Sends each seated client their view of the room
+    fn broadcast(&self, name: &str) {
+        if let Some(room) = self.rooms.get(name) {
+            for (client, seat) in room.clients() {
+                self.send(
+                    client,
+                    ServerMsg::StateUpdate {
+                        view: room.view(seat),
+                    },
+                );
+            }
+        }
+    }
+
+    fn handle(&mut self, client: usize, text: &str) {
+        let result =
+            protocol::decode::<ClientMsg>(text).and_then(|msg| self.handle_msg(client, msg));
+
+        match result {
+            Ok(name) => self.broadcast(&name),
+            Err(reason) => self.send(client, ServerMsg::Rejected { reason }),
+        }
+    }
+
+    // Returns the name of the changed room
+    fn handle_msg(&mut self, client: usize, msg: ClientMsg) -> Result<String, String> {
+        let joined = self.clients.get(&client).and_then(|c| c.room.clone());
+
+        if let ClientMsg::Join {
+            room: name,
+            players,
+        } = msg
+        {
+            if joined.is_some() {
+                return Err("Already in a room".to_string());
+            }
+            if !self.rooms.contains_key(&name) && !(2..=8).contains(&players) {
+                return Err("Players must be from 2 to 8".to_string());
+            }
+
+            let seed = self.seed;
+            let room = self
+                .rooms
+                .entry(name.clone())
+                .or_insert_with(|| Room::new(players, seed));
+            let seat = room.join(client).ok_or("Room is full")?;
+
+            if let Some(client) = self.clients.get_mut(&client) {
+                client.room = Some(name.clone());
+            }
+            self.send(
+                client,
+                ServerMsg::Joined {
+                    room: name.clone(),
+                    seat,
+                },
+            );
+            return Ok(name);
+        }
+
+        let name = joined.ok_or("Join a room first")?;
+        let room = self.rooms.get_mut(&name).ok_or("Room is gone")?;
+
+        match msg {
+            ClientMsg::Start => room.start()?,
+            ClientMsg::Act { action, card_index } => {
+                let seat = room.seat_of(client).ok_or("No seat in the room")?;
+                room.act(seat, action.code(), card_index)?;
+            }
+            ClientMsg::Join { .. } => unreachable!(),
+        }
+        Ok(name)
+    }
+}
+
+// Accepts clients until the listener fails
+pub async fn serve(listener: TcpListener, seed: Option<u64>) -> std::io::Result<()> {
+    let lobby = Arc::new(Mutex::new(Lobby::new(seed)));
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let lobby = lobby.clone();
+        tokio::spawn(async move {
+            // Connection errors only concern the client itself
+            let _ = handle_connection(stream, lobby).await;
+        });
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    lobby: Arc<Mutex<Lobby>>,
+) -> Result<(), tungstenite::Error> {
+    let (mut sink, mut stream) = tokio_tungstenite::accept_async(stream).await?.split();
+
+    let (sender, mut receiver) = unbounded_channel();
+    let client = lobby.lock().unwrap().connect(sender);
+
+    let writer = tokio::spawn(async move {
+        while let Some(msg) = receiver.recv().await {
+            if sink
+                .send(Message::Text(protocol::encode(msg)))
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    let mut result = Ok(());
+    while let Some(message) = stream.next().await {
+        match message {
+            Ok(Message::Text(text)) => lobby.lock().unwrap().handle(client, &text),
+            Ok(Message::Close(_)) => break,
+            Ok(_) => {}
+            Err(err) => {
+                result = Err(err);
+                break;
+            }
+        }
+    }
+
+    lobby.lock().unwrap().disconnect(client);
+    writer.abort();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::moska::State,
+        protocol::{ActionKind, View},
+    };
+    use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+    type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    async fn connect(addr: std::net::SocketAddr) -> Socket {
+        connect_async(format!("ws://{addr}")).await.unwrap().0
+    }
+
+    async fn send(socket: &mut Socket, msg: ClientMsg) {
+        socket
+            .send(Message::Text(protocol::encode(msg)))
+            .await
+            .unwrap();
+    }
+
+    async fn recv(socket: &mut Socket) -> ServerMsg {
+        loop {
+            if let Message::Text(text) = socket.next().await.unwrap().unwrap() {
+                return protocol::decode(&text).unwrap();
+            }
+        }
+    }
+
+    async fn recv_view(socket: &mut Socket) -> View {
+        match recv(socket).await {
+            ServerMsg::StateUpdate { view } => view,
+            msg => panic!("Expected state update, got {msg:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_end_to_end() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Some(8)));
+
+        let mut alice = connect(addr).await;
+        let mut bob = connect(addr).await;
+
+        let join = || ClientMsg::Join {
+            room: "lunch".to_string(),
+            players: 3,
+        };
+        send(&mut alice, join()).await;
+        assert_eq!(
+            recv(&mut alice).await,
+            ServerMsg::Joined {
+                room: "lunch".to_string(),
+                seat: 0
+            }
+        );
+        recv_view(&mut alice).await;
+
+        send(&mut bob, join()).await;
+        assert!(matches!(
+            recv(&mut bob).await,
+            ServerMsg::Joined { seat: 1, .. }
+        ));
+        recv_view(&mut bob).await;
+        recv_view(&mut alice).await;
+
+        // acting before the game has started
+        send(
+            &mut alice,
+            ClientMsg::Act {
+                action: ActionKind::AddCard,
+                card_index: 0,
+            },
+        )
+        .await;
+        assert!(matches!(recv(&mut alice).await, ServerMsg::Rejected { .. }));
+
+        send(&mut alice, ClientMsg::Start).await;
+        let view = recv_view(&mut alice).await;
+        let bob_view = recv_view(&mut bob).await;
+        assert_eq!(view.state, State::PlayerAttacking);
+        assert_eq!(view.bots, vec![false, false, true]);
+        assert_eq!(view.hand.len(), 6);
+        assert_eq!(bob_view.hand_counts, vec![6, 6, 6]);
+        // hands stay private
+        assert!(view.hand.iter().all(|card| !bob_view.hand.contains(card)));
+
+        // bob is not in turn
+        send(
+            &mut bob,
+            ClientMsg::Act {
+                action: ActionKind::Submit,
+                card_index: 0,
+            },
+        )
+        .await;
+        assert!(matches!(recv(&mut bob).await, ServerMsg::Rejected { .. }));
+
+        // alice attacks bob with a single card
+        send(
+            &mut alice,
+            ClientMsg::Act {
+                action: ActionKind::AddCard,
+                card_index: 0,
+            },
+        )
+        .await;
+        recv_view(&mut alice).await;
+        recv_view(&mut bob).await;
+        send(
+            &mut alice,
+            ClientMsg::Act {
+                action: ActionKind::Submit,
+                card_index: 0,
+            },
+        )
+        .await;
+        recv_view(&mut alice).await;
+        let bob_view = recv_view(&mut bob).await;
+        assert_eq!(bob_view.player_index, 1);
+        assert_eq!(bob_view.state, State::PlayerDefending);
+        assert_eq!(bob_view.attacker_cards, vec![view.hand[0]]);
+
+        // version 1 clients are understood
+        bob.send(Message::Text(r#"{"type":"Act","action":3}"#.to_string()))
+            .await
+            .unwrap();
+        let bob_view = recv_view(&mut bob).await;
+        assert_eq!(bob_view.hand.len(), 7);
+        recv_view(&mut alice).await;
+
+        // bob leaves and a bot plays his seat
+        bob.close(None).await.unwrap();
+        let view = recv_view(&mut alice).await;
+        assert_eq!(view.bots, vec![false, true, true]);
+        assert_ne!(view.player_index, 1);
+    }
+}
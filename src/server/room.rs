@@ -0,0 +1,272 @@
+/*
+ * Game room with seats for clients and bots
+ */
+
+use crate::{ai::BotStrategy, game::moska::State, protocol::View, Moska};
+
+// Bots play until this many actions before the game is given up
+const MAX_BOT_STEPS: usize = 10_000;
+
+pub struct Room {
+    game: Moska,
+    // Client id of each seat, empty seats are played by bots
+    seats: Vec<Option<usize>>,
+    seed: Option<u64>,
+    started: bool,
+}
+
+impl Room {
+    pub fn new(players: u8, seed: Option<u64>) -> Self {
+        let mut game = Moska::new(players);
+        for seat in 0..players as usize {
+            game.set_bot(seat, BotStrategy::Heuristic);
+        }
+
+        Self {
+            game,
+            seats: vec![None; players as usize],
+            seed,
+            started: false,
+        }
+    }
+
+    pub fn game(&self) -> &Moska {
+        &self.game
+    }
+
+    pub fn started(&self) -> bool {
+        self.started
+    }
+
+    // Client ids with their seats
+    pub fn clients(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.seats
+            .iter()
+            .enumerate()
+            .filter_map(|(seat, client)| client.map(|client| (client, seat)))
+    }
+
+    pub fn seat_of(&self, client: usize) -> Option<usize> {
+        self.seats.iter().position(|seat| *seat == Some(client))
+    }
+
+    // Seats the client at the first free seat.
+    // Clients can take over bot seats of a started game.
+    pub fn join(&mut self, client: usize) -> Option<usize> {
+        if let Some(seat) = self.seat_of(client) {
+            return Some(seat);
+        }
+        let seat = self
+            .seats
+            .iter()
+            .zip(&self.game.table.players)
+            .position(|(client, player)| client.is_none() && !player.forfeited)?;
+        self.seats[seat] = Some(client);
+        self.game.set_human(seat);
+        Some(seat)
+    }
+
+    // Frees the client's seat for a bot.
+    // Before the game starts the seat goes back to the bots.
+    pub fn leave(&mut self, client: usize) {
+        if let Some(seat) = self.seat_of(client) {
+            self.seats[seat] = None;
+            match self.started {
+                true => self.game.disconnect(seat),
+                false => self.game.set_bot(seat, BotStrategy::Heuristic),
+            }
+            self.play_bots();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seats.iter().all(Option::is_none)
+    }
+
+    // Deals the cards, bots take the empty seats
+    pub fn start(&mut self) -> Result<(), String> {
+        if self.started {
+            return Err("Game has already started".to_string());
+        }
+        self.started = true;
+
+        match self.seed {
+            Some(seed) => self.game.new_round_with_seed(seed),
+            None => self.game.new_round(),
+        }
+        self.play_bots();
+        Ok(())
+    }
+
+    // Applies the seat's action after checking it with the engine
+    pub fn act(&mut self, seat: usize, action: usize, card_index: usize) -> Result<(), String> {
+        let game = &self.game;
+        if !self.started {
+            return Err("Game has not started".to_string());
+        }
+        if game.state == State::GameOver {
+            return Err("Game is over".to_string());
+        }
+        if game.table.player_index != seat {
+            return Err("Not your turn".to_string());
+        }
+
+        let table_cards = match game.state {
+            State::PlayerDefending => game.defender_cards.len(),
+            _ => game.attacker_cards.len(),
+        };
+
+        match action {
+            1 if card_index >= game.table.players[seat].cards.len() => {
+                return Err(format!("No card at index {card_index} in hand"));
+            }
+            2 if card_index >= table_cards => {
+                return Err(format!("No card at index {card_index} on the table"));
+            }
+            3 => {
+                let took = game.state == State::PlayerDefending && game.defender_cards.is_empty();
+                if !took && !game.valid() {
+                    return Err(match game.state {
+                        State::PlayerDefending => "Defending cards do not beat the attack",
+                        _ => "Attack must be a single card or pairs the defender can answer",
+                    }
+                    .to_string());
+                }
+            }
+            4 if !game.clone().player_action(4, 0) => {
+                return Err("Trump card cannot be swapped".to_string());
+            }
+            1..=4 => {}
+            _ => return Err(format!("Unknown action {action}")),
+        }
+
+        self.game.player_action(action, card_index);
+        self.play_bots();
+        Ok(())
+    }
+
+    // Plays the turns of bot seats until a client is in turn
+    fn play_bots(&mut self) {
+        if !self.started {
+            return;
+        }
+
+        for _ in 0..MAX_BOT_STEPS {
+            if self.game.state == State::GameOver {
+                return;
+            }
+            let Some(actions) = self.game.controller_actions() else {
+                return;
+            };
+
+            let seat = self.game.table.player_index;
+            let position = (self.game.table.turn, self.game.state);
+            for action in actions {
+                self.game.player_action(action.action, action.card_index);
+            }
+            self.game.player_action(3, 0);
+
+            // A bot that can't end its turn would hold up the room for good
+            if (self.game.table.turn, self.game.state) == position {
+                eprintln!("Bot in seat {seat} could not end its turn, forfeiting the seat");
+                self.game.forfeit(seat);
+            }
+        }
+        eprintln!("Bots did not finish the game in {MAX_BOT_STEPS} steps");
+    }
+
+    // Game as seen from the seat
+    pub fn view(&self, seat: usize) -> View {
+        View::new(
+            &self.game,
+            seat,
+            self.game.table.players.iter().map(|p| !p.human()).collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seats() {
+        let mut room = Room::new(3, Some(1));
+        assert_eq!(room.join(10), Some(0));
+        assert_eq!(room.join(11), Some(1));
+        assert_eq!(room.join(10), Some(0));
+        assert_eq!(room.join(12), Some(2));
+        assert_eq!(room.join(13), None);
+
+        room.leave(11);
+        assert_eq!(room.join(13), Some(1));
+        assert_eq!(room.clients().count(), 3);
+    }
+
+    #[test]
+    fn test_act() {
+        let mut room = Room::new(2, Some(4));
+        room.join(1);
+
+        assert!(room.act(0, 1, 0).is_err());
+        room.start().unwrap();
+        assert!(room.start().is_err());
+
+        assert!(room.act(1, 1, 0).is_err());
+        assert!(room.act(0, 1, 6).is_err());
+        assert!(room.act(0, 2, 0).is_err());
+        assert!(room.act(0, 3, 0).is_err());
+        assert!(room.act(0, 9, 0).is_err());
+
+        // bot answers the single card attack
+        room.act(0, 1, 0).unwrap();
+        room.act(0, 3, 0).unwrap();
+        assert_eq!(room.game().table.player_index, 0);
+
+        let view = room.view(0);
+        assert_eq!(view.hand, room.game().player_cards(0));
+        assert_eq!(view.bots, vec![false, true]);
+    }
+
+    #[test]
+    fn test_bots_finish_game() {
+        let mut room = Room::new(3, Some(2));
+        room.join(1);
+        room.start().unwrap();
+
+        // leaving hands the seat over to a bot
+        room.leave(1);
+        assert!(room.is_empty());
+        assert_eq!(room.game().state, State::GameOver);
+    }
+
+    #[test]
+    fn test_leave_mid_turn() {
+        let mut states = vec![];
+        for seed in 0..8 {
+            for turns in 0..3 {
+                let mut room = Room::new(3, Some(seed));
+                room.join(1);
+                room.start().unwrap();
+                for _ in 0..turns {
+                    for action in BotStrategy::Heuristic.get_actions(room.game()) {
+                        room.act(0, action.action, action.card_index).unwrap();
+                    }
+                    room.act(0, 3, 0).unwrap();
+                }
+                if room.game().state == State::GameOver {
+                    continue;
+                }
+                states.push(room.game().state);
+
+                // bot takes over from the cards the client put on the table
+                room.act(0, 1, 0).unwrap();
+                room.leave(1);
+                assert_eq!(room.game().state, State::GameOver);
+                assert!(room.game().table.players.iter().all(|p| !p.forfeited));
+            }
+        }
+        assert!(states.contains(&State::PlayerAttacking));
+        assert!(states.contains(&State::PlayerDefending));
+    }
+}
@@ -1,16 +1,61 @@
-use crate::deck::{Card, Deck};
+use crate::{
+    ai::BotStrategy,
+    card_set::CardSet,
+    deck::{Card, Deck},
+};
 use wasm_bindgen::prelude::*;
 
+// Who plays a seat
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Controller {
+    #[default]
+    Human,
+    Bot(BotStrategy),
+    // Human left the game, see `ForfeitRules`
+    Disconnected,
+}
+
 #[derive(Clone, Default)]
 #[wasm_bindgen(getter_with_clone)]
 pub struct Player {
     pub id: u8,
     pub cards: Vec<Card>,
+
+    #[wasm_bindgen(skip)]
+    pub controller: Controller,
+
+    // Seat has left the game for good
+    pub forfeited: bool,
+}
+
+#[wasm_bindgen]
+impl Player {
+    #[wasm_bindgen(getter)]
+    pub fn human(&self) -> bool {
+        self.controller == Controller::Human
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn connected(&self) -> bool {
+        self.controller != Controller::Disconnected
+    }
+
+    // Strategy of a bot seat
+    #[wasm_bindgen(getter)]
+    pub fn bot_strategy(&self) -> Option<BotStrategy> {
+        match self.controller {
+            Controller::Bot(strategy) => Some(strategy),
+            _ => None,
+        }
+    }
 }
 
 impl Player {
     pub fn new(id: u8) -> Self {
-        Player { id, cards: vec![] }
+        Player {
+            id,
+            ..Default::default()
+        }
     }
 
     pub fn hand(&self) -> Vec<Card> {
@@ -28,6 +73,11 @@ impl Player {
     pub fn card_index(&self, card: &Card) -> Option<usize> {
         self.cards.iter().position(|self_card| self_card == card)
     }
+
+    // Cards in hand as a set, losing their order
+    pub fn card_set(&self) -> CardSet {
+        self.cards.iter().collect()
+    }
 }
 
 #[derive(Clone)]
@@ -53,7 +103,7 @@ pub struct Table {
 impl Table {
     pub(crate) fn new(players: u8) -> Self {
         Table {
-            players: (0..players).into_iter().map(Player::new).collect(),
+            players: (0..players).map(Player::new).collect(),
             deck: Deck::new(),
             player_index: 0,
             turn: 0,
@@ -66,6 +116,7 @@ impl Table {
 
         for player in self.players.iter_mut() {
             player.cards = vec![];
+            player.forfeited = false;
         }
     }
 